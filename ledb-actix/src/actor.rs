@@ -1,12 +1,19 @@
-use actix::{Actor, Addr, Handler, Message, SyncArbiter, SyncContext};
+use actix::{
+    clock::{interval_at, Instant},
+    Actor, Addr, Arbiter, Handler, Message, SyncArbiter, SyncContext,
+};
 use ledb::{Result as LeResult, Storage as LeStorage};
 use serde::{de::DeserializeOwned, Serialize};
+use std::io::{Read, Write};
 use std::marker::PhantomData;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use super::{
-    Document, DocumentsIterator, Filter, Identifier, IndexKind, Info, KeyFields, KeyType, Modify,
-    Options, Order, Primary, Stats,
+    Affected, Aggregated, Aggregation, CollectionStats, Document, DocumentsIterator, Expiry,
+    ExportOptions, Filter, FindOptions, Identifier, ImportOptions, IndexKind, IndexOptions, Info,
+    Integrity, KeyField, KeyFields, KeyType, Modify, Options, Order, Primary, PrimaryType,
+    Progress, Returning, Revision, Schema, Stats, Watcher,
 };
 
 /// Storage actor
@@ -26,6 +33,35 @@ impl Storage {
     pub fn start(self, threads: usize) -> Addr<Self> {
         SyncArbiter::start(threads, move || self.clone())
     }
+
+    /// Schedule periodic sweeping of expired documents
+    ///
+    /// The `Sweep` messages will be sent by the timer on the arbiter of the current thread.
+    /// The timer stops when the actor stops.
+    ///
+    pub fn schedule_sweep(addr: &Addr<Self>, interval: Duration) {
+        let addr = addr.downgrade();
+
+        Arbiter::spawn(async move {
+            let mut timer = interval_at(Instant::now() + interval, interval);
+
+            loop {
+                timer.tick().await;
+
+                let addr = if let Some(addr) = addr.upgrade() {
+                    addr
+                } else {
+                    break;
+                };
+
+                match addr.send(Sweep).await {
+                    Ok(Ok(_)) => (),
+                    Ok(Err(error)) => eprintln!("Error when sweeping expired documents: {}", error),
+                    Err(_) => break,
+                }
+            }
+        });
+    }
 }
 
 impl Actor for Storage {
@@ -64,6 +100,97 @@ impl Handler<GetInfo> for Storage {
     }
 }
 
+/// Make backup copy of database
+#[allow(non_snake_case)]
+pub fn Backup<P: Into<PathBuf>>(path: P, compact: bool) -> BackupMsg {
+    BackupMsg(path.into(), compact)
+}
+
+/// Make backup copy of database
+///
+/// *NOTE: Use `Backup` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupMsg(PathBuf, bool);
+
+impl Message for BackupMsg {
+    type Result = LeResult<()>;
+}
+
+impl Handler<BackupMsg> for Storage {
+    type Result = <BackupMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        BackupMsg(path, compact): BackupMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.backup(path, compact)
+    }
+}
+
+/// Export collections with documents and indexes
+#[allow(non_snake_case)]
+pub fn Export<W: Write + Send + 'static>(out: W, opts: ExportOptions) -> ExportMsg {
+    ExportMsg(Box::new(out), opts)
+}
+
+/// Export collections with documents and indexes
+///
+/// The exported data is written to the given output.
+/// The total number of exported documents will be returned.
+///
+/// *NOTE: Use `Export` for creating message*
+pub struct ExportMsg(Box<dyn Write + Send>, ExportOptions);
+
+impl Message for ExportMsg {
+    type Result = LeResult<usize>;
+}
+
+impl Handler<ExportMsg> for Storage {
+    type Result = <ExportMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        ExportMsg(mut out, opts): ExportMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let total = self.0.export(&mut out, &opts, |_| ())?;
+        out.flush()?;
+        Ok(total)
+    }
+}
+
+/// Import collections with documents and indexes
+#[allow(non_snake_case)]
+pub fn Import<R: Read + Send + 'static>(inp: R, opts: ImportOptions) -> ImportMsg {
+    ImportMsg(Box::new(inp), opts)
+}
+
+/// Import collections with documents and indexes
+///
+/// The data is read from the given input until it ends.
+/// The final progress of each imported collection will be returned.
+///
+/// *NOTE: Use `Import` for creating message*
+pub struct ImportMsg(Box<dyn Read + Send>, ImportOptions);
+
+impl Message for ImportMsg {
+    type Result = LeResult<Vec<Progress>>;
+}
+
+impl Handler<ImportMsg> for Storage {
+    type Result = <ImportMsg as Message>::Result;
+
+    fn handle(&mut self, ImportMsg(inp, opts): ImportMsg, _: &mut Self::Context) -> Self::Result {
+        let mut states: Vec<Progress> = Vec::new();
+        self.0.import(inp, &opts, |state| match states.last_mut() {
+            Some(last) if last.collection == state.collection => *last = state.clone(),
+            _ => states.push(state.clone()),
+        })?;
+        Ok(states)
+    }
+}
+
 /// Get collections request
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct GetCollections;
@@ -86,14 +213,23 @@ impl Handler<GetCollections> for Storage {
 /// Ensure collection in storage
 #[allow(non_snake_case)]
 pub fn EnsureCollection<C: Into<Identifier>>(coll: C) -> EnsureCollectionMsg {
-    EnsureCollectionMsg(coll.into())
+    EnsureCollectionMsg(coll.into(), None)
+}
+
+/// Ensure collection with specified type of primary key in storage
+#[allow(non_snake_case)]
+pub fn EnsureCollectionWith<C: Into<Identifier>>(
+    coll: C,
+    primary: PrimaryType,
+) -> EnsureCollectionMsg {
+    EnsureCollectionMsg(coll.into(), Some(primary))
 }
 
 /// Ensure collection in storage
 ///
-/// *NOTE: Use `EnsureCollection` function instead*
+/// *NOTE: Use `EnsureCollection` or `EnsureCollectionWith` function instead*
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct EnsureCollectionMsg(Identifier);
+pub struct EnsureCollectionMsg(Identifier, Option<PrimaryType>);
 
 impl Message for EnsureCollectionMsg {
     type Result = LeResult<bool>;
@@ -104,15 +240,16 @@ impl Handler<EnsureCollectionMsg> for Storage {
 
     fn handle(
         &mut self,
-        EnsureCollectionMsg(name): EnsureCollectionMsg,
+        EnsureCollectionMsg(name, primary): EnsureCollectionMsg,
         _: &mut Self::Context,
     ) -> Self::Result {
-        Ok(if self.0.has_collection(&name)? {
-            false
+        let exists = self.0.has_collection(&name)?;
+        if let Some(primary) = primary {
+            self.0.collection_with(name, primary)?;
         } else {
             self.0.collection(name)?;
-            true
-        })
+        }
+        Ok(!exists)
     }
 }
 
@@ -200,7 +337,46 @@ impl Handler<SetIndexesMsg> for Storage {
     }
 }
 
+/// Get index of collection with the state of building
+#[allow(non_snake_case)]
+pub fn GetIndex<C: Into<Identifier>, F: Into<Identifier>>(coll: C, field: F) -> GetIndexMsg {
+    GetIndexMsg(coll.into(), field.into())
+}
+
+/// Get index of collection with the state of building
+///
+/// *NOTE: Use `GetIndex` function instead*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetIndexMsg(Identifier, Identifier);
+
+impl Message for GetIndexMsg {
+    type Result = LeResult<Option<KeyField>>;
+}
+
+impl Handler<GetIndexMsg> for Storage {
+    type Result = <GetIndexMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        GetIndexMsg(collection, field): GetIndexMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        Ok(self
+            .0
+            .collection(collection)?
+            .get_indexes()?
+            .into_iter()
+            .find(|index| index.path == field.as_ref()))
+    }
+}
+
 /// Ensure new index for collection
+///
+/// The index is built in background, so the message returns immediately.
+/// Use `GetIndex` to get the state of building.
+///
+/// *NOTE: Use `EnsureIndexFieldWith` with default options to build index synchronously*
+///
 #[allow(non_snake_case)]
 pub fn EnsureIndex<C: Into<Identifier>, F: Into<Identifier>>(
     coll: C,
@@ -228,10 +404,61 @@ impl Handler<EnsureIndexMsg> for Storage {
         &mut self,
         EnsureIndexMsg(collection, field, kind, key): EnsureIndexMsg,
         _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.ensure_index_with(
+            KeyField::new(field).with_type(key).with_kind(kind),
+            IndexOptions::background(),
+        )
+    }
+}
+
+/// Ensure new index for collection using key field definition
+///
+/// The key field may be compound.
+/// The index is built in background like with `EnsureIndex`.
+///
+#[allow(non_snake_case)]
+pub fn EnsureIndexField<C: Into<Identifier>, F: Into<KeyField>>(
+    coll: C,
+    field: F,
+) -> EnsureIndexFieldMsg {
+    EnsureIndexFieldMsg(coll.into(), field.into(), IndexOptions::background())
+}
+
+/// Ensure new index for collection using key field definition and index options
+///
+/// The index is built synchronously unless the background building is requested by options.
+///
+#[allow(non_snake_case)]
+pub fn EnsureIndexFieldWith<C: Into<Identifier>, F: Into<KeyField>>(
+    coll: C,
+    field: F,
+    opts: IndexOptions,
+) -> EnsureIndexFieldMsg {
+    EnsureIndexFieldMsg(coll.into(), field.into(), opts)
+}
+
+/// Ensure new index for collection using key field definition
+///
+/// *NOTE: Use `EnsureIndexField` or `EnsureIndexFieldWith` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnsureIndexFieldMsg(Identifier, KeyField, IndexOptions);
+
+impl Message for EnsureIndexFieldMsg {
+    type Result = LeResult<bool>;
+}
+
+impl Handler<EnsureIndexFieldMsg> for Storage {
+    type Result = <EnsureIndexFieldMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        EnsureIndexFieldMsg(collection, field, opts): EnsureIndexFieldMsg,
+        _: &mut Self::Context,
     ) -> Self::Result {
         self.0
             .collection(collection)?
-            .ensure_index(field, kind, key)
+            .ensure_index_with(field, opts)
     }
 }
 
@@ -263,7 +490,275 @@ impl Handler<DropIndexMsg> for Storage {
     }
 }
 
+/// Verify that the indexes of collection agrees with documents
+#[allow(non_snake_case)]
+pub fn Verify<C: Into<Identifier>>(coll: C) -> VerifyMsg {
+    VerifyMsg(coll.into())
+}
+
+/// Verify that the indexes of collection agrees with documents
+///
+/// *NOTE: Use `Verify` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyMsg(Identifier);
+
+impl Message for VerifyMsg {
+    type Result = LeResult<Integrity>;
+}
+
+impl Handler<VerifyMsg> for Storage {
+    type Result = <VerifyMsg as Message>::Result;
+
+    fn handle(&mut self, VerifyMsg(collection): VerifyMsg, _: &mut Self::Context) -> Self::Result {
+        self.0.collection(collection)?.verify()
+    }
+}
+
+/// Rebuild the indexes of collection which disagrees with documents
+///
+/// The integrity of collection before repairing will be returned.
+///
+#[allow(non_snake_case)]
+pub fn Repair<C: Into<Identifier>>(coll: C) -> RepairMsg {
+    RepairMsg(coll.into())
+}
+
+/// Rebuild the indexes of collection which disagrees with documents
+///
+/// *NOTE: Use `Repair` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairMsg(Identifier);
+
+impl Message for RepairMsg {
+    type Result = LeResult<Integrity>;
+}
+
+impl Handler<RepairMsg> for Storage {
+    type Result = <RepairMsg as Message>::Result;
+
+    fn handle(&mut self, RepairMsg(collection): RepairMsg, _: &mut Self::Context) -> Self::Result {
+        self.0.collection(collection)?.repair()
+    }
+}
+
+/// Get the statistics of collection and its indexes
+#[allow(non_snake_case)]
+pub fn GetCollectionStats<C: Into<Identifier>>(coll: C) -> GetCollectionStatsMsg {
+    GetCollectionStatsMsg(coll.into())
+}
+
+/// Get the statistics of collection and its indexes
+///
+/// *NOTE: Use `GetCollectionStats` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetCollectionStatsMsg(Identifier);
+
+impl Message for GetCollectionStatsMsg {
+    type Result = LeResult<CollectionStats>;
+}
+
+impl Handler<GetCollectionStatsMsg> for Storage {
+    type Result = <GetCollectionStatsMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        GetCollectionStatsMsg(collection): GetCollectionStatsMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.stats()
+    }
+}
+
+/// Remove expired documents from all collections
+///
+/// The number of removed documents will be returned.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sweep;
+
+impl Message for Sweep {
+    type Result = LeResult<usize>;
+}
+
+impl Handler<Sweep> for Storage {
+    type Result = <Sweep as Message>::Result;
+
+    fn handle(&mut self, _: Sweep, _: &mut Self::Context) -> Self::Result {
+        self.0.sweep()
+    }
+}
+
+/// Get expiration of documents in collection
+#[allow(non_snake_case)]
+pub fn GetExpiry<C: Into<Identifier>>(coll: C) -> GetExpiryMsg {
+    GetExpiryMsg(coll.into())
+}
+
+/// Get expiration of documents in collection
+///
+/// *NOTE: Use `GetExpiry` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetExpiryMsg(Identifier);
+
+impl Message for GetExpiryMsg {
+    type Result = LeResult<Option<Expiry>>;
+}
+
+impl Handler<GetExpiryMsg> for Storage {
+    type Result = <GetExpiryMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        GetExpiryMsg(collection): GetExpiryMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.get_expiry()
+    }
+}
+
+/// Set expiration of documents in collection
+#[allow(non_snake_case)]
+pub fn SetExpiry<C: Into<Identifier>>(coll: C, expiry: Expiry) -> SetExpiryMsg {
+    SetExpiryMsg(coll.into(), expiry)
+}
+
+/// Set expiration of documents in collection
+///
+/// *NOTE: Use `SetExpiry` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetExpiryMsg(Identifier, Expiry);
+
+impl Message for SetExpiryMsg {
+    type Result = LeResult<()>;
+}
+
+impl Handler<SetExpiryMsg> for Storage {
+    type Result = <SetExpiryMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        SetExpiryMsg(collection, expiry): SetExpiryMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.set_expiry(expiry)
+    }
+}
+
+/// Drop expiration of documents in collection
+#[allow(non_snake_case)]
+pub fn DropExpiry<C: Into<Identifier>>(coll: C) -> DropExpiryMsg {
+    DropExpiryMsg(coll.into())
+}
+
+/// Drop expiration of documents in collection
+///
+/// *NOTE: Use `DropExpiry` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropExpiryMsg(Identifier);
+
+impl Message for DropExpiryMsg {
+    type Result = LeResult<bool>;
+}
+
+impl Handler<DropExpiryMsg> for Storage {
+    type Result = <DropExpiryMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        DropExpiryMsg(collection): DropExpiryMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.drop_expiry()
+    }
+}
+
+/// Get schema of documents in collection
+#[allow(non_snake_case)]
+pub fn GetSchema<C: Into<Identifier>>(coll: C) -> GetSchemaMsg {
+    GetSchemaMsg(coll.into())
+}
+
+/// Get schema of documents in collection
+///
+/// *NOTE: Use `GetSchema` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetSchemaMsg(Identifier);
+
+impl Message for GetSchemaMsg {
+    type Result = LeResult<Option<Schema>>;
+}
+
+impl Handler<GetSchemaMsg> for Storage {
+    type Result = <GetSchemaMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        GetSchemaMsg(collection): GetSchemaMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.get_schema()
+    }
+}
+
+/// Set schema of documents in collection
+#[allow(non_snake_case)]
+pub fn SetSchema<C: Into<Identifier>>(coll: C, schema: Schema) -> SetSchemaMsg {
+    SetSchemaMsg(coll.into(), schema)
+}
+
+/// Set schema of documents in collection
+///
+/// *NOTE: Use `SetSchema` for creating message*
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetSchemaMsg(Identifier, Schema);
+
+impl Message for SetSchemaMsg {
+    type Result = LeResult<()>;
+}
+
+impl Handler<SetSchemaMsg> for Storage {
+    type Result = <SetSchemaMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        SetSchemaMsg(collection, schema): SetSchemaMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.set_schema(schema)
+    }
+}
+
+/// Drop schema of documents in collection
+#[allow(non_snake_case)]
+pub fn DropSchema<C: Into<Identifier>>(coll: C) -> DropSchemaMsg {
+    DropSchemaMsg(coll.into())
+}
+
+/// Drop schema of documents in collection
+///
+/// *NOTE: Use `DropSchema` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropSchemaMsg(Identifier);
+
+impl Message for DropSchemaMsg {
+    type Result = LeResult<bool>;
+}
+
+impl Handler<DropSchemaMsg> for Storage {
+    type Result = <DropSchemaMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        DropSchemaMsg(collection): DropSchemaMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.drop_schema()
+    }
+}
+
 /// Insert new document into collection
+///
+/// The documents for collections with string or binary primary keys must contain the id.
 #[allow(non_snake_case)]
 pub fn Insert<C: Into<Identifier>, T: Serialize>(coll: C, data: T) -> InsertMsg<T> {
     InsertMsg(coll.into(), data)
@@ -287,7 +782,13 @@ impl<T: Serialize + Document> Handler<InsertMsg<T>> for Storage {
         InsertMsg(collection, document): InsertMsg<T>,
         _: &mut Self::Context,
     ) -> Self::Result {
-        self.0.collection(collection)?.insert(&document)
+        let collection = self.0.collection(collection)?;
+        // the string and binary keys cannot be generated
+        if collection.primary_type().is_integer() {
+            collection.insert(&document)
+        } else {
+            collection.insert_with_id(&document)
+        }
     }
 }
 
@@ -319,6 +820,34 @@ impl<T: DeserializeOwned + Document + 'static> Handler<GetMsg<T>> for Storage {
     }
 }
 
+/// Get the previously inserted document with revision by primary key
+#[allow(non_snake_case)]
+pub fn GetWithRevision<C: Into<Identifier>, T>(coll: C, id: Primary) -> GetWithRevisionMsg<T> {
+    GetWithRevisionMsg(coll.into(), id, PhantomData)
+}
+
+/// Get the previously inserted document with revision by primary key
+///
+/// *NOTE: Use `GetWithRevision` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetWithRevisionMsg<T>(Identifier, Primary, PhantomData<T>);
+
+impl<T: 'static> Message for GetWithRevisionMsg<T> {
+    type Result = LeResult<Option<(T, Revision)>>;
+}
+
+impl<T: DeserializeOwned + Document + 'static> Handler<GetWithRevisionMsg<T>> for Storage {
+    type Result = <GetWithRevisionMsg<T> as Message>::Result;
+
+    fn handle(
+        &mut self,
+        GetWithRevisionMsg(collection, identifier, ..): GetWithRevisionMsg<T>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.get_with_revision(identifier)
+    }
+}
+
 /// Put new version of the previously inserted document
 #[allow(non_snake_case)]
 pub fn Put<C: Into<Identifier>, T>(coll: C, data: T) -> PutMsg<T> {
@@ -347,6 +876,43 @@ impl<T: Serialize + Document> Handler<PutMsg<T>> for Storage {
     }
 }
 
+/// Put new version of the document when it has expected revision
+///
+/// The zero revision means that the document must not exists.
+///
+#[allow(non_snake_case)]
+pub fn PutIfRevision<C: Into<Identifier>, T>(
+    coll: C,
+    data: T,
+    rev: Revision,
+) -> PutIfRevisionMsg<T> {
+    PutIfRevisionMsg(coll.into(), data, rev)
+}
+
+/// Put new version of the document when it has expected revision
+///
+/// *NOTE: Use `PutIfRevision` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PutIfRevisionMsg<T>(Identifier, T, Revision);
+
+impl<T: Serialize + Document> Message for PutIfRevisionMsg<T> {
+    type Result = LeResult<Revision>;
+}
+
+impl<T: Serialize + Document> Handler<PutIfRevisionMsg<T>> for Storage {
+    type Result = <PutIfRevisionMsg<T> as Message>::Result;
+
+    fn handle(
+        &mut self,
+        PutIfRevisionMsg(collection, document, rev): PutIfRevisionMsg<T>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0
+            .collection(collection)?
+            .put_if_revision(&document, rev)
+    }
+}
+
 /// Delete the previously inserted document
 #[allow(non_snake_case)]
 pub fn Delete<C: Into<Identifier>>(coll: C, id: Primary) -> DeleteMsg {
@@ -403,6 +969,79 @@ impl Handler<UpdateMsg> for Storage {
     }
 }
 
+impl UpdateMsg {
+    /// Get affected documents instead of the number of it
+    pub fn returning<T>(self, returning: Returning) -> UpdateReturningMsg<T> {
+        let UpdateMsg(collection, filter, modify) = self;
+        UpdateReturningMsg(collection, filter, modify, returning, PhantomData)
+    }
+}
+
+/// Update documents using filter and modifier and get affected documents
+///
+/// *NOTE: Use `Update(..).returning(..)` for creating message*
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateReturningMsg<T>(
+    Identifier,
+    Option<Filter>,
+    Modify,
+    Returning,
+    PhantomData<T>,
+);
+
+impl<T: 'static> Message for UpdateReturningMsg<T> {
+    type Result = LeResult<Vec<Affected<T>>>;
+}
+
+impl<T: DeserializeOwned + Document + 'static> Handler<UpdateReturningMsg<T>> for Storage {
+    type Result = <UpdateReturningMsg<T> as Message>::Result;
+
+    fn handle(
+        &mut self,
+        UpdateReturningMsg(collection, filter, modify, returning, ..): UpdateReturningMsg<T>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0
+            .collection(collection)?
+            .update_returning(filter, modify, returning)
+    }
+}
+
+/// Update documents using filter and modifier or insert new document when nothing found
+#[allow(non_snake_case)]
+pub fn Upsert<C: Into<Identifier>, T>(
+    coll: C,
+    filter: Option<Filter>,
+    modify: Modify,
+    data: T,
+) -> UpsertMsg<T> {
+    UpsertMsg(coll.into(), filter, modify, data)
+}
+
+/// Update documents using filter and modifier or insert new document when nothing found
+///
+/// *NOTE: Use `Upsert` for creating message*
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpsertMsg<T>(Identifier, Option<Filter>, Modify, T);
+
+impl<T: Serialize + Document> Message for UpsertMsg<T> {
+    type Result = LeResult<Option<Primary>>;
+}
+
+impl<T: Serialize + Document> Handler<UpsertMsg<T>> for Storage {
+    type Result = <UpsertMsg<T> as Message>::Result;
+
+    fn handle(
+        &mut self,
+        UpsertMsg(collection, filter, modify, document): UpsertMsg<T>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0
+            .collection(collection)?
+            .upsert(filter, modify, &document)
+    }
+}
+
 /// Remove documents using filter
 #[allow(non_snake_case)]
 pub fn Remove<C: Into<Identifier>>(coll: C, filter: Option<Filter>) -> RemoveMsg {
@@ -431,17 +1070,70 @@ impl Handler<RemoveMsg> for Storage {
     }
 }
 
+impl RemoveMsg {
+    /// Get removed documents instead of the number of it
+    pub fn returning<T>(self) -> RemoveReturningMsg<T> {
+        let RemoveMsg(collection, filter) = self;
+        RemoveReturningMsg(collection, filter, PhantomData)
+    }
+}
+
+/// Remove documents using filter and get removed documents
+///
+/// *NOTE: Use `Remove(..).returning()` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoveReturningMsg<T>(Identifier, Option<Filter>, PhantomData<T>);
+
+impl<T: 'static> Message for RemoveReturningMsg<T> {
+    type Result = LeResult<Vec<T>>;
+}
+
+impl<T: DeserializeOwned + Document + 'static> Handler<RemoveReturningMsg<T>> for Storage {
+    type Result = <RemoveReturningMsg<T> as Message>::Result;
+
+    fn handle(
+        &mut self,
+        RemoveReturningMsg(collection, filter, ..): RemoveReturningMsg<T>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.remove_returning(filter)
+    }
+}
+
 /// Find documents using filter and ordering
 #[allow(non_snake_case)]
 pub fn Find<C: Into<Identifier>, T>(coll: C, filter: Option<Filter>, order: Order) -> FindMsg<T> {
-    FindMsg(coll.into(), filter, order, PhantomData)
+    FindMsg(
+        coll.into(),
+        filter,
+        order,
+        FindOptions::default(),
+        PhantomData,
+    )
+}
+
+/// Find documents using filter, ordering and options
+#[allow(non_snake_case)]
+pub fn FindWith<C: Into<Identifier>, T>(
+    coll: C,
+    filter: Option<Filter>,
+    order: Order,
+    opts: FindOptions,
+) -> FindMsg<T> {
+    FindMsg(coll.into(), filter, order, opts, PhantomData)
 }
 
 /// Find documents using filter and ordering
 ///
-/// *NOTE: Use `Find` for creating message*
+/// *NOTE: Use `Find` or `FindWith` for creating message*
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FindMsg<T>(Identifier, Option<Filter>, Order, PhantomData<T>);
+pub struct FindMsg<T>(
+    Identifier,
+    Option<Filter>,
+    Order,
+    FindOptions,
+    PhantomData<T>,
+);
 
 impl<T: 'static> Message for FindMsg<T> {
     type Result = LeResult<DocumentsIterator<T>>;
@@ -452,22 +1144,94 @@ impl<T: DeserializeOwned + Document + 'static> Handler<FindMsg<T>> for Storage {
 
     fn handle(
         &mut self,
-        FindMsg(collection, filter, order, ..): FindMsg<T>,
+        FindMsg(collection, filter, order, opts, ..): FindMsg<T>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0
+            .collection(collection)?
+            .find_with(filter, order, opts)
+    }
+}
+
+/// Watch the changes of documents using filter
+#[allow(non_snake_case)]
+pub fn Watch<C: Into<Identifier>>(coll: C, filter: Option<Filter>) -> WatchMsg {
+    WatchMsg(coll.into(), filter)
+}
+
+/// Watch the changes of documents using filter
+///
+/// The watcher receives the changes which made by any actor or thread after the commit.
+///
+/// *NOTE: Use `Watch` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchMsg(Identifier, Option<Filter>);
+
+impl Message for WatchMsg {
+    type Result = LeResult<Watcher>;
+}
+
+impl Handler<WatchMsg> for Storage {
+    type Result = <WatchMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        WatchMsg(collection, filter): WatchMsg,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        self.0.collection(collection)?.watch(filter)
+    }
+}
+
+/// Aggregate documents using filter
+#[allow(non_snake_case)]
+pub fn Aggregate<C: Into<Identifier>>(
+    coll: C,
+    filter: Option<Filter>,
+    aggregation: Aggregation,
+) -> AggregateMsg {
+    AggregateMsg(coll.into(), filter, aggregation)
+}
+
+/// Aggregate documents using filter
+///
+/// *NOTE: Use `Aggregate` for creating message*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateMsg(Identifier, Option<Filter>, Aggregation);
+
+impl Message for AggregateMsg {
+    type Result = LeResult<Aggregated>;
+}
+
+impl Handler<AggregateMsg> for Storage {
+    type Result = <AggregateMsg as Message>::Result;
+
+    fn handle(
+        &mut self,
+        AggregateMsg(collection, filter, aggregation): AggregateMsg,
         _: &mut Self::Context,
     ) -> Self::Result {
-        self.0.collection(collection)?.find(filter, order)
+        self.0
+            .collection(collection)?
+            .aggregate(filter, &aggregation)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        Document, EnsureIndex, Find, Identifier, IndexKind, Insert, KeyType, Options, Primary,
-        Storage,
+        Document, DropCollection, EnsureIndex, EnsureIndexField, EnsureIndexFieldWith, Expiry,
+        Export, ExportOptions, Find, Get, GetCollectionStats, GetExpiry, GetIndex, Identifier,
+        Import, ImportOptions, IndexKind, IndexOptions, Insert, KeyField, KeyType, Options,
+        Primary, Progress, SetExpiry, Storage, Sweep,
     };
+    use actix::clock::delay_for;
     use serde::{Deserialize, Serialize};
-    use serde_json::{from_value, json};
-    use std::fs::remove_dir_all;
+    use serde_json::{from_value, json, Value};
+    use std::{
+        fs::{remove_dir_all, File},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
 
     macro_rules! json_val {
         ($($json:tt)+) => {
@@ -530,6 +1294,19 @@ mod tests {
         .unwrap()
         .unwrap();
 
+        // the index is built in background
+        while addr
+            .send(GetIndex("blog", "tags"))
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+            .build
+            .is_some()
+        {
+            delay_for(Duration::from_millis(10)).await;
+        }
+
         let mut docs = addr
             .send(Find::<_, BlogPost>(
                 "blog",
@@ -551,4 +1328,210 @@ mod tests {
         assert_eq!(&doc, &doc_data);
         assert!(docs.next().is_none());
     }
+
+    static EXCHANGE_DB_PATH: &str = ".test_dbs/exchange";
+    static EXCHANGE_DUMP_PATH: &str = ".test_dbs/exchange.jsonl";
+
+    #[actix_rt::test]
+    async fn export_import() {
+        let _ = remove_dir_all(EXCHANGE_DB_PATH);
+
+        let storage = Storage::new(EXCHANGE_DB_PATH, Options::default()).unwrap();
+
+        let addr = storage.start(1);
+
+        let doc: BlogPost = json_val!({
+            "id": 1,
+            "title": "Absurd",
+            "tags": ["absurd", "psychology"],
+            "content": "Still nothing..."
+        });
+
+        addr.send(Insert::<_, BlogPost>("blog", doc.clone()))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            addr.send(Export(
+                File::create(EXCHANGE_DUMP_PATH).unwrap(),
+                ExportOptions::default()
+            ))
+            .await
+            .unwrap()
+            .unwrap(),
+            1
+        );
+
+        assert!(addr.send(DropCollection("blog")).await.unwrap().unwrap());
+
+        assert_eq!(
+            addr.send(Import(
+                File::open(EXCHANGE_DUMP_PATH).unwrap(),
+                ImportOptions::default()
+            ))
+            .await
+            .unwrap()
+            .unwrap(),
+            vec![Progress {
+                collection: "blog".into(),
+                documents: 1
+            }]
+        );
+
+        assert_eq!(
+            addr.send(Get::<_, BlogPost>("blog", 1.into()))
+                .await
+                .unwrap()
+                .unwrap(),
+            Some(doc)
+        );
+    }
+
+    static SWEEP_DB_PATH: &str = ".test_dbs/sweep";
+
+    #[actix_rt::test]
+    async fn sweep() {
+        let _ = remove_dir_all(SWEEP_DB_PATH);
+
+        let storage = Storage::new(SWEEP_DB_PATH, Options::default()).unwrap();
+
+        let addr = storage.start(1);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let expiry = Expiry::new("seen", Duration::from_secs(60));
+
+        addr.send(SetExpiry("sessions", expiry.clone()))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            addr.send(GetExpiry("sessions")).await.unwrap().unwrap(),
+            Some(expiry)
+        );
+
+        for seen in &[now - 120, now, now - 60] {
+            addr.send(Insert::<_, Value>("sessions", json!({ "seen": seen })))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        assert_eq!(addr.send(Sweep).await.unwrap().unwrap(), 2);
+        assert_eq!(addr.send(Sweep).await.unwrap().unwrap(), 0);
+
+        addr.send(Insert::<_, Value>("sessions", json!({ "seen": now - 120 })))
+            .await
+            .unwrap()
+            .unwrap();
+
+        Storage::schedule_sweep(&addr, Duration::from_millis(10));
+        delay_for(Duration::from_millis(100)).await;
+
+        assert_eq!(addr.send(Sweep).await.unwrap().unwrap(), 0);
+    }
+
+    static INDEX_DB_PATH: &str = ".test_dbs/index";
+
+    #[actix_rt::test]
+    async fn background_index() {
+        let _ = remove_dir_all(INDEX_DB_PATH);
+
+        let storage = Storage::new(INDEX_DB_PATH, Options::default()).unwrap();
+
+        let addr = storage.start(1);
+
+        for n in 1..=10 {
+            addr.send(Insert::<_, Value>("numbers", json!({ "n": n })))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        assert!(addr
+            .send(EnsureIndexField(
+                "numbers",
+                KeyField::new("n").with_type(KeyType::Int),
+            ))
+            .await
+            .unwrap()
+            .unwrap());
+
+        loop {
+            let index = addr.send(GetIndex("numbers", "n")).await.unwrap().unwrap();
+            if index.unwrap().build.is_none() {
+                break;
+            }
+            delay_for(Duration::from_millis(10)).await;
+        }
+
+        let docs = addr
+            .send(Find::<_, Value>(
+                "numbers",
+                json_val!({ "n": { "$gt": 7 } }),
+                json_val!("$asc"),
+            ))
+            .await
+            .unwrap()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            docs,
+            vec![
+                json!({ "n": 8, "$": 8 }),
+                json!({ "n": 9, "$": 9 }),
+                json!({ "n": 10, "$": 10 }),
+            ]
+        );
+
+        // the synchronous index is ready when the message returns
+        assert!(addr
+            .send(EnsureIndexFieldWith(
+                "numbers",
+                KeyField::new("m").with_type(KeyType::Int),
+                IndexOptions::default(),
+            ))
+            .await
+            .unwrap()
+            .unwrap());
+
+        let index = addr.send(GetIndex("numbers", "m")).await.unwrap().unwrap();
+        assert_eq!(index.unwrap().build, None);
+    }
+
+    static STATS_DB_PATH: &str = ".test_dbs/stats";
+
+    #[actix_rt::test]
+    async fn collection_stats() {
+        let _ = remove_dir_all(STATS_DB_PATH);
+
+        let storage = Storage::new(STATS_DB_PATH, Options::default()).unwrap();
+
+        let addr = storage.start(1);
+
+        for n in &[1, 2, 3] {
+            addr.send(Insert::<_, Value>("numbers", json!({ "n": n })))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let stats = addr
+            .send(GetCollectionStats("numbers"))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(stats.documents, 3);
+        assert_eq!(stats.pages.data_entries, 3);
+        assert!(stats.total_size > 0);
+        assert!(stats.indexes.is_empty());
+    }
 }
@@ -117,6 +117,33 @@ __GET__ /info
 
 __GET__ /stats
 
+#### make backup copy of database
+
+__POST__ /backup?name=_$backup_name_
+
+__POST__ /backup?name=_$backup_name_&compact=true
+
+The compacting copy omits free pages so it may be smaller.
+
+The backups is disabled by default. Use `storage_with_backups(dir)` instead of `storage()` to enable it.
+The backup copy is placed into the configured directory, so the name must be a plain file name.
+
+#### export collections with documents and indexes
+
+__GET__ /export?format=_$format_&collections=_$collection_names_
+
+The _$format_ is `json` ([JSON Lines](http://jsonlines.org/), default) or `cbor` (sequence of CBOR items).
+The optional _$collection_names_ is JSON array of names like `["post","user"]`.
+
+#### import collections with documents and indexes
+
+__POST__ /import?format=_$format_&mode=_$mode_&collections=_$collection_names_
+
+The _$mode_ is `append` (default), `replace` or `upsert`.
+The number of imported documents of each collection will be returned.
+
+The exported and imported data is streamed, so it isn't kept in memory entirely.
+
 ### Collection API
 
 #### get list of collections
@@ -127,10 +154,34 @@ __GET__ /collection
 
 __POST__ /collection?name=_$collection_name_
 
+#### create new empty collection with string, binary or 64-bit integer primary keys
+
+__POST__ /collection?name=_$collection_name_&primary=_$primary_type_
+
+The primary type is one of `int` (default), `bigint`, `string` or `binary`.
+The documents which inserted into collections with string or binary primary keys must contain the `$` field with id.
+
 #### drop collection with all documents
 
 __DELETE__ /collection/_$collection_name_
 
+#### verify that indexes of collection agrees with documents
+
+__GET__ /collection/_$collection_name_/verify
+
+The integrity of each index is reported with the ids of `dangling`, `missing` and `duplicates` documents.
+
+#### rebuild damaged indexes of collection
+
+__POST__ /collection/_$collection_name_/repair
+
+The integrity of collection before repairing will be returned.
+The unique index with duplicated keys cannot be repaired, so the response is `409 Conflict` in that case.
+
+#### get statistics of collection and its indexes
+
+__GET__ /collection/_$collection_name_/stats
+
 ### Index API
 
 #### get indexes of collection
@@ -141,7 +192,23 @@ __GET__ /collection/_$collection_name_/index
 
 __POST__ /collection/_$collection_name_/index?path=_$field_name_&kind=_$index_kind_&key=_$key_type_
 
-#### drop index of collection
+The compound index can be created using comma-separated field names and the key types of the rest fields:
+
+__POST__ /collection/_$collection_name_/index?path=_$field_name_,_$field_name_&kind=_$index_kind_&key=_$key_type_&compound=[_$key_type_]
+
+The index is built in background, so the response is `202 Accepted` with the location of index.
+
+The index is built by batches of 1000 documents by default, the size of batch can be changed using `batch` parameter:
+
+__POST__ /collection/_$collection_name_/index?path=_$field_name_&kind=_$index_kind_&key=_$key_type_&batch=_$batch_size_
+
+#### get index of collection with the state of building
+
+__GET__ /collection/_$collection_name_/index/_$index_name_
+
+The index which isn't ready yet has the `build` field like `{"done":1000,"total":5000}`.
+
+#### drop index of collection (cancels building)
 
 __DELETE__ /collection/_$collection_name_/document/_$index_name_
 
@@ -149,9 +216,11 @@ __DELETE__ /collection/_$collection_name_/document/_$index_name_
 
 #### find documents using query
 
-__GET__ /collection/_$collection_name_/document?filter=_$query_&order=_$ordering_&offset=_$skip_&length=_$take_
+__GET__ /collection/_$collection_name_/document?filter=_$query_&order=_$ordering_&offset=_$skip_&length=_$take_&after=_$primary_&projection=_$projection_
 
-__GET__ /collection/_$collection_name_?filter=_$query_&order=_$ordering_&offset=_$skip_&length=_$take_
+__GET__ /collection/_$collection_name_?filter=_$query_&order=_$ordering_&offset=_$skip_&length=_$take_&after=_$primary_&projection=_$projection_
+
+The projection is a JSON like `{"$include":["field","other.field"]}` or `{"$exclude":["field"]}`.
 
 #### modify documents using query
 
@@ -159,12 +228,36 @@ __PUT__ /collection/_$collection_name_/document?filter=_$query_&modify=_$modific
 
 __PATCH__ /collection/_$collection_name_?filter=_$query_&modify=_$modifications_
 
+#### modify documents using query and get affected documents
+
+__PUT__ /collection/_$collection_name_/document?filter=_$query_&modify=_$modifications_&returning=_$returning_
+
+__PATCH__ /collection/_$collection_name_?filter=_$query_&modify=_$modifications_&returning=_$returning_
+
+The _$returning_ is `before`, `after` or `both`.
+The affected documents are returned like `[{"id":1,"old":{..},"new":{..}}]`.
+
+#### modify documents using query or insert default document when nothing found
+
+__PUT__ /collection/_$collection_name_/document?filter=_$query_&modify=_$modifications_&upsert=_$document_
+
+__PATCH__ /collection/_$collection_name_?filter=_$query_&modify=_$modifications_&upsert=_$document_
+
+The modifications are applied to the default document too.
+When the document was inserted the `201 Created` with location of it will be returned.
+
 #### remove documents using query
 
 __DELETE__ /collection/_$collection_name_/document?filter=_$query_
 
 __PUT__ /collection/_$collection_name_?filter=_$query_
 
+#### remove documents using query and get removed documents
+
+__DELETE__ /collection/_$collection_name_/document?filter=_$query_&returning=true
+
+__PUT__ /collection/_$collection_name_?filter=_$query_&returning=true
+
 #### insert new document
 
 __POST__ /collection/_$collection_name_/document
@@ -177,18 +270,42 @@ __GET__ /collection/_$collection_name_/document/_$document_id_
 
 __GET__ /collection/_$collection_name_/_$document_id_
 
+The revision of document is returned in the `revision` header.
+
 #### replace document
 
 __PUT__ /collection/_$collection_name_/document/_$document_id_
 
 __PUT__ /collection/_$collection_name_/_$document_id_
 
+#### replace document when it has expected revision
+
+__PUT__ /collection/_$collection_name_/document/_$document_id_?rev=_$revision_
+
+__PUT__ /collection/_$collection_name_/_$document_id_?rev=_$revision_
+
+The `409 Conflict` will be returned when the document has another revision.
+The zero revision means that the document must not exists.
+The new revision of document is returned in the `revision` header.
+
 #### remove document
 
 __DELETE__ /collection/_$collection_name_/document/_$document_id_
 
 __DELETE__ /collection/_$collection_name_/_$document_id_
 
+#### aggregate documents using query
+
+__GET__ /collection/_$collection_name_/aggregate?filter=_$query_&aggregation=_$aggregation_
+
+The aggregation is a JSON like `"$count"`, `{"$distinct":"field"}`, `{"$max":"field"}` or `{"$group":{"by":"field","fields":{"total":{"$sum":"value"}}}}`.
+
+#### watch changes of documents
+
+__GET__ /collection/_$collection_name_/watch?filter=_$query_
+
+The changes are streamed as [server-sent events](https://html.spec.whatwg.org/multipage/server-sent-events.html) with JSON data like `{"kind":"update","id":1,"old":{..},"new":{..}}`.
+
 */
 
 mod actor;
@@ -198,9 +315,12 @@ mod macros;
 mod scope;
 
 pub use ledb::{
-    KeyType, Modify, Options, Order, OrderKind, Primary, Stats, _query_impl, query_extr, Action,
-    Comp, Cond, Document, DocumentsIterator, Filter, Identifier, IndexKind, Info, KeyData,
-    KeyField, KeyFields, Value,
+    KeyType, Modify, Options, Order, OrderKind, Primary, PrimaryType, Stats, _query_impl,
+    query_extr, Accumulator, Action, Affected, Aggregated, Aggregation, Change, ChangeKind,
+    CollectionStats, Comp, Cond, DataFormat, Document, DocumentsIterator, Expiry, ExportOptions,
+    Filter, FindOptions, Identifier, ImportMode, ImportOptions, IndexBuild, IndexIntegrity,
+    IndexKind, IndexOptions, IndexStats, Info, Integrity, KeyData, KeyField, KeyFields, Progress,
+    Projection, Returning, Revision, Schema, SchemaType, Value, Watcher,
 };
 
 pub use actor::*;
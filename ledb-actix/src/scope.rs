@@ -1,30 +1,72 @@
-use actix::Addr;
+use actix::{spawn, Addr};
 use actix_web::{
     error::{
-        ErrorBadRequest, ErrorInternalServerError, ErrorNotFound, ErrorServiceUnavailable, Result,
+        Error, ErrorBadRequest, ErrorConflict, ErrorForbidden, ErrorInsufficientStorage,
+        ErrorInternalServerError, ErrorNotFound, ErrorServiceUnavailable, ErrorUnprocessableEntity,
+        Result,
+    },
+    web::{
+        delete, get, patch, post, put, resource, scope, Bytes, Data, Json, Path, Payload, Query,
     },
-    web::{delete, get, patch, post, put, resource, scope, Data, Json, Path, Query},
     HttpRequest, HttpResponse, Scope,
 };
+use futures::{
+    channel::mpsc::{channel, unbounded, Receiver, Sender},
+    executor::block_on,
+    future::join,
+    SinkExt, StreamExt,
+};
+use ledb::{Error as LeError, Result as LeResult};
 use serde::{Deserialize, Serialize};
 use serde_with::json::nested as json_str;
-use std::usize;
+use std::{
+    io::{self, BufWriter, Read, Write},
+    path::{Component, PathBuf},
+    thread,
+    time::Duration,
+};
 
 use super::{
-    Delete, Document, DropCollection, DropIndex, EnsureCollection, EnsureIndex, Filter, Find, Get,
-    GetCollections, GetIndexes, GetInfo, GetStats, Info, Insert, KeyField, ListCollections, Modify,
-    Order, Primary, Put, Remove, Stats, Storage, Update, Value,
+    Affected, Aggregate, Aggregated, Aggregation, Backup, Change, ChangeKind, CollectionStats,
+    DataFormat, Delete, Document, DropCollection, DropIndex, EnsureCollection, EnsureCollectionWith,
+    EnsureIndexFieldWith, Export, ExportOptions, Filter, FindOptions, FindWith, GetCollectionStats,
+    GetCollections, GetIndex, GetIndexes, GetInfo, GetStats, GetWithRevision, Import, ImportMode,
+    ImportOptions, IndexKind, IndexOptions, Info, Insert, Integrity, KeyField, KeyType,
+    ListCollections, Modify, Order, Primary, PrimaryType, Progress, Projection, Put, PutIfRevision,
+    Remove, Repair, Returning, Revision, Stats, Storage, Update, Upsert, Value, Verify, Watch,
 };
 
 /// Storage actor address type
 pub type StorageAddr = Addr<Storage>;
 
+/// The directory where the backups of database is placed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupDir(pub PathBuf);
+
 /// Scoped storage adapter for **actix-web**
+///
+/// The backups is disabled, use `storage_with_backups` to enable it.
+///
 pub fn storage() -> Scope {
     scope("")
         .service(resource("/").name("usage").route(get().to(get_usage)))
         .service(resource("/info").name("info").route(get().to(get_info)))
         .service(resource("/stats").name("stats").route(get().to(get_stats)))
+        .service(
+            resource("/backup")
+                .name("backup")
+                .route(post().to(make_backup)),
+        )
+        .service(
+            resource("/export")
+                .name("export")
+                .route(get().to(export_data)),
+        )
+        .service(
+            resource("/import")
+                .name("import")
+                .route(post().to(import_data)),
+        )
         .service(
             resource("/collection")
                 .name("collections")
@@ -55,6 +97,7 @@ pub fn storage() -> Scope {
                             scope("/index").service(
                                 resource("/{index}")
                                     .name("index")
+                                    .route(get().to(get_index))
                                     .route(delete().to(drop_index)),
                             ),
                         )
@@ -75,6 +118,31 @@ pub fn storage() -> Scope {
                                     .route(delete().to(delete_document)),
                             ),
                         )
+                        .service(
+                            resource("/aggregate")
+                                .name("aggregate")
+                                .route(get().to(aggregate_documents)),
+                        )
+                        .service(
+                            resource("/watch")
+                                .name("watch")
+                                .route(get().to(watch_documents)),
+                        )
+                        .service(
+                            resource("/verify")
+                                .name("verify")
+                                .route(get().to(verify_collection)),
+                        )
+                        .service(
+                            resource("/repair")
+                                .name("repair")
+                                .route(post().to(repair_collection)),
+                        )
+                        .service(
+                            resource("/stats")
+                                .name("collection_stats")
+                                .route(get().to(get_collection_stats)),
+                        )
                         .service(
                             resource("/{id}")
                                 .name("document_short")
@@ -86,6 +154,14 @@ pub fn storage() -> Scope {
         )
 }
 
+/// Scoped storage adapter for **actix-web** with backups enabled
+///
+/// The backups is made into the given directory only.
+///
+pub fn storage_with_backups<P: Into<PathBuf>>(dir: P) -> Scope {
+    storage().data(BackupDir(dir.into()))
+}
+
 /// Usage info handler
 pub async fn get_usage(req: HttpRequest) -> String {
     format!(
@@ -97,6 +173,12 @@ Storage API:
     GET {info}
     # get database statistics
     GET {stats}
+    # make backup copy of database into backups directory (optionally compacted)
+    POST {backup}?name=$backup_name&compact=true
+    # export collections with documents and indexes (json or cbor)
+    GET {export}?format=json&collections=$collection_names
+    # import collections with documents and indexes (append, replace or upsert)
+    POST {import}?format=json&mode=append&collections=$collection_names
 
 Collection API:
 
@@ -104,29 +186,50 @@ Collection API:
     GET {collections}
     # create new empty collection
     POST {collections}?name=$collection_name
+    # create new empty collection with specified primary key type (int, bigint, string, binary)
+    POST {collections}?name=$collection_name&primary=$primary_type
     # drop collection with all documents
     DELETE {collection}
+    # verify that indexes of collection agrees with documents
+    GET {verify}
+    # rebuild damaged indexes of collection (returns integrity before repairing)
+    POST {repair}
+    # get statistics of collection and its indexes
+    GET {collection_stats}
 
 Index API:
 
     # get indexes of collection
     GET {indexes}
-    # create new index for collection
+    # create new index for collection (built in background)
     POST {indexes}?path=$field_path&kind=$index_kind&key=$key_type
-    # drop index of collection
+    # create new index for collection using batches of specified size
+    POST {indexes}?path=$field_path&kind=$index_kind&key=$key_type&batch=1000
+    # get index of collection with the state of building
+    GET {index}
+    # drop index of collection (cancels building)
     DELETE {index}
 
 Document API:
 
     # find documents using query
-    GET {documents}?filter=$query&order=$ordering&offset=10&length=10
-    GET {collection}?filter=$query&order=$ordering&offset=10&length=10
+    GET {documents}?filter=$query&order=$ordering&offset=10&length=10&after=$id&projection=$projection
+    GET {collection}?filter=$query&order=$ordering&offset=10&length=10&after=$id&projection=$projection
     # modify documents using query
     PUT {documents}?filter=$query&modify=$modifications
     PATCH {collection}?filter=$query&modify=$modifications
+    # modify documents using query and get affected documents (before, after or both)
+    PUT {documents}?filter=$query&modify=$modifications&returning=$returning
+    PATCH {collection}?filter=$query&modify=$modifications&returning=$returning
+    # modify documents using query or insert default document when nothing found
+    PUT {documents}?filter=$query&modify=$modifications&upsert=$document
+    PATCH {collection}?filter=$query&modify=$modifications&upsert=$document
     # remove documents using query
     DELETE {documents}?filter=$query
     PUT {collection}?filter=$query
+    # remove documents using query and get removed documents
+    DELETE {documents}?filter=$query&returning=true
+    PUT {collection}?filter=$query&returning=true
 
     # insert new document
     POST {documents}
@@ -137,14 +240,25 @@ Document API:
     # replace document
     PUT {document}
     PUT {document_short}
+    # replace document when it has expected revision
+    PUT {document}?rev=$revision
+    PUT {document_short}?rev=$revision
     # remove document
     DELETE {document}
     DELETE {document_short}
 
+    # aggregate documents using query
+    GET {aggregate}?filter=$query&aggregation=$aggregation
+
+    # watch the changes of documents as server-sent events
+    GET {watch}?filter=$query
+
 Supported index kinds:
 
     index -- Normal index which may contain duplicated keys
     unique -- Index which contains unique keys only
+    fulltext -- Index which contains words of strings for full-text search
+    spatial -- Index which contains geographic points
 
 Supported key types:
 
@@ -152,6 +266,7 @@ Supported key types:
     float  -- 64-bit floating point number
     bool   -- boolean value
     string -- UTF-8 string
+    folded -- case-insensitive UTF-8 string
     binary -- binary data
 
 See documentation: {documentation}
@@ -160,6 +275,9 @@ See documentation: {documentation}
         documentation = env!("CARGO_PKG_HOMEPAGE"),
         info = req.url_for_static("info").unwrap(),
         stats = req.url_for_static("stats").unwrap(),
+        backup = req.url_for_static("backup").unwrap(),
+        export = req.url_for_static("export").unwrap(),
+        import = req.url_for_static("import").unwrap(),
         collections = req.url_for_static("collections").unwrap(),
         collection = req.url_for("collection", &["$collection_name"]).unwrap(),
         indexes = req.url_for("indexes", &["$collection_name"]).unwrap(),
@@ -173,6 +291,13 @@ See documentation: {documentation}
         document_short = req
             .url_for("document_short", &["$collection_name", "$document_id"])
             .unwrap(),
+        aggregate = req.url_for("aggregate", &["$collection_name"]).unwrap(),
+        watch = req.url_for("watch", &["$collection_name"]).unwrap(),
+        verify = req.url_for("verify", &["$collection_name"]).unwrap(),
+        repair = req.url_for("repair", &["$collection_name"]).unwrap(),
+        collection_stats = req
+            .url_for("collection_stats", &["$collection_name"])
+            .unwrap(),
     )
 }
 
@@ -181,7 +306,7 @@ pub async fn get_info(addr: Data<StorageAddr>) -> Result<Json<Info>> {
     addr.send(GetInfo)
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map(Json).map_err(ErrorInternalServerError))
+        .and_then(|res| res.map(Json).map_err(storage_error))
 }
 
 /// Storage stats handler
@@ -189,7 +314,209 @@ pub async fn get_stats(addr: Data<StorageAddr>) -> Result<Json<Stats>> {
     addr.send(GetStats)
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map(Json).map_err(ErrorInternalServerError))
+        .and_then(|res| res.map(Json).map_err(storage_error))
+}
+
+/// Backup parameters
+#[derive(Serialize, Deserialize)]
+pub struct BackupParams {
+    pub name: String,
+    #[serde(default)]
+    pub compact: bool,
+}
+
+/// Storage backup handler
+pub async fn make_backup(
+    addr: Data<StorageAddr>,
+    dir: Option<Data<BackupDir>>,
+    params: Query<BackupParams>,
+) -> Result<HttpResponse> {
+    let dir = dir.ok_or_else(|| ErrorForbidden("Backups is disabled"))?;
+    let BackupParams { name, compact } = params.into_inner();
+    let name = PathBuf::from(name);
+
+    // the backup must be placed into the backups directory
+    let mut components = name.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => (),
+        _ => return Err(ErrorBadRequest("Invalid backup name")),
+    }
+
+    addr.send(Backup(dir.0.join(name), compact))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map_err(storage_error))
+        .map(|_| HttpResponse::NoContent().finish())
+}
+
+/// Export parameters
+#[derive(Serialize, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: DataFormat,
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub collections: Option<Vec<String>>,
+}
+
+/// The number of data chunks which is buffered while exporting or importing
+const STREAM_CHUNKS: usize = 16;
+
+/// The size of exported data chunk
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Blocking writer which sends the exported data to the response stream
+struct StreamWriter(Sender<io::Result<Bytes>>);
+
+impl Write for StreamWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on(self.0.send(Ok(Bytes::copy_from_slice(buf))))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Response stream closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Blocking reader which receives the imported data from the request payload
+struct StreamReader {
+    receiver: Receiver<io::Result<Bytes>>,
+    chunk: Bytes,
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match block_on(self.receiver.next()) {
+                Some(chunk) => self.chunk = chunk?,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+/// Storage export handler
+///
+/// The data is streamed to the client while the export is running.
+///
+pub async fn export_data(
+    addr: Data<StorageAddr>,
+    params: Query<ExportParams>,
+) -> Result<HttpResponse> {
+    let ExportParams {
+        format,
+        collections,
+    } = params.into_inner();
+
+    let (sender, receiver) = channel(STREAM_CHUNKS);
+    let mut errors = sender.clone();
+    let out = BufWriter::with_capacity(EXPORT_CHUNK_SIZE, StreamWriter(sender));
+    let export = addr.send(Export(
+        out,
+        ExportOptions {
+            format,
+            collections,
+        },
+    ));
+
+    spawn(async move {
+        if let Err(error) = export
+            .await
+            .map_err(|error| error.to_string())
+            .and_then(|res| res.map_err(|error| error.to_string()))
+        {
+            // the response is already started so it can be interrupted only
+            let _ = errors
+                .send(Err(io::Error::new(io::ErrorKind::Other, error)))
+                .await;
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(match format {
+            DataFormat::Json => "application/x-ndjson",
+            DataFormat::Cbor => "application/cbor-seq",
+        })
+        .streaming(receiver))
+}
+
+/// Import parameters
+#[derive(Serialize, Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    pub format: DataFormat,
+    #[serde(default)]
+    pub mode: ImportMode,
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub collections: Option<Vec<String>>,
+}
+
+/// Storage import handler
+///
+/// The payload is streamed to the storage while the import is running.
+///
+pub async fn import_data(
+    addr: Data<StorageAddr>,
+    params: Query<ImportParams>,
+    mut payload: Payload,
+) -> Result<Json<Vec<Progress>>> {
+    let ImportParams {
+        format,
+        mode,
+        collections,
+    } = params.into_inner();
+
+    let (mut sender, receiver) = channel(STREAM_CHUNKS);
+    let inp = StreamReader {
+        receiver,
+        chunk: Bytes::new(),
+    };
+    let import = addr.send(Import(
+        inp,
+        ImportOptions {
+            format,
+            mode,
+            collections,
+        },
+    ));
+
+    let forward = async move {
+        while let Some(chunk) = payload.next().await {
+            let chunk =
+                chunk.map_err(|error| io::Error::new(io::ErrorKind::Other, error.to_string()));
+            if sender.send(chunk).await.is_err() {
+                // the import is finished or failed
+                break;
+            }
+        }
+    };
+
+    let (res, _) = join(import, forward).await;
+
+    res.map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map(Json).map_err(import_error))
+}
+
+/// Map import error
+///
+/// The errors of payload decoding is reported as bad request,
+/// the other errors is mapped like the errors of storage.
+///
+fn import_error(error: LeError) -> Error {
+    match &error {
+        LeError::DocError(_)
+        | LeError::StrError(_)
+        | LeError::DataError(_)
+        | LeError::JsonError(_)
+        | LeError::IoError(_) => ErrorBadRequest(error),
+        _ => storage_error(error),
+    }
 }
 
 /// Storage collections handler
@@ -197,13 +524,15 @@ pub async fn get_collections(addr: Data<StorageAddr>) -> Result<Json<ListCollect
     addr.send(GetCollections)
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map(Json).map_err(ErrorInternalServerError))
+        .and_then(|res| res.map(Json).map_err(storage_error))
 }
 
 /// Collection parameters
 #[derive(Serialize, Deserialize)]
 pub struct CollectionParams {
     pub name: String,
+    #[serde(default)]
+    pub primary: Option<PrimaryType>,
 }
 
 /// Ensure collection handler
@@ -212,13 +541,17 @@ pub async fn ensure_collection(
     params: Query<CollectionParams>,
     req: HttpRequest,
 ) -> Result<HttpResponse> {
-    let CollectionParams { name } = params.into_inner();
+    let CollectionParams { name, primary } = params.into_inner();
     match req.url_for("collection", &[&name]) {
         Ok(url) => addr
-            .send(EnsureCollection(name))
+            .send(if let Some(primary) = primary {
+                EnsureCollectionWith(name, primary)
+            } else {
+                EnsureCollection(name)
+            })
             .await
             .map_err(ErrorServiceUnavailable)
-            .and_then(|res| res.map_err(ErrorInternalServerError))
+            .and_then(|res| res.map_err(storage_error))
             .map(move |res| {
                 if res {
                     HttpResponse::Created()
@@ -240,7 +573,7 @@ pub async fn drop_collection(addr: Data<StorageAddr>, coll: Path<String>) -> Res
     addr.send(DropCollection(coll.into_inner()))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .and_then(|res| {
             if res {
                 Ok(HttpResponse::NoContent().finish())
@@ -250,6 +583,39 @@ pub async fn drop_collection(addr: Data<StorageAddr>, coll: Path<String>) -> Res
         })
 }
 
+/// Verify collection handler
+pub async fn verify_collection(
+    addr: Data<StorageAddr>,
+    coll: Path<String>,
+) -> Result<Json<Integrity>> {
+    addr.send(Verify(coll.into_inner()))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map(Json).map_err(storage_error))
+}
+
+/// Repair collection handler
+pub async fn repair_collection(
+    addr: Data<StorageAddr>,
+    coll: Path<String>,
+) -> Result<Json<Integrity>> {
+    addr.send(Repair(coll.into_inner()))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map(Json).map_err(storage_error))
+}
+
+/// Collection stats handler
+pub async fn get_collection_stats(
+    addr: Data<StorageAddr>,
+    coll: Path<String>,
+) -> Result<Json<CollectionStats>> {
+    addr.send(GetCollectionStats(coll.into_inner()))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map(Json).map_err(storage_error))
+}
+
 /// Get indexes handler
 pub async fn get_indexes(
     addr: Data<StorageAddr>,
@@ -258,26 +624,59 @@ pub async fn get_indexes(
     addr.send(GetIndexes(coll.into_inner()))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .map(|indexes| Json(indexes.into_iter().collect()))
 }
 
+/// Ensure index query parameters
+#[derive(Serialize, Deserialize)]
+pub struct IndexParams {
+    pub path: String,
+    #[serde(default)]
+    pub kind: IndexKind,
+    #[serde(default)]
+    pub key: KeyType,
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub compound: Vec<KeyType>,
+    #[serde(default)]
+    pub batch: Option<usize>,
+}
+
 /// Ensure index handler
 pub async fn ensure_index(
     addr: Data<StorageAddr>,
     coll: Path<String>,
-    params: Query<KeyField>,
+    params: Query<IndexParams>,
     req: HttpRequest,
 ) -> Result<HttpResponse> {
-    let KeyField { path, kind, key } = params.into_inner();
+    let IndexParams {
+        path,
+        kind,
+        key,
+        compound,
+        batch,
+    } = params.into_inner();
     if let Ok(url) = req.url_for("index", &[&coll, &path]) {
-        addr.send(EnsureIndex(coll.into_inner(), path, kind, key))
+        let field = KeyField {
+            path,
+            kind,
+            key,
+            compound,
+            build: None,
+        };
+        let opts = IndexOptions {
+            background: true,
+            batch,
+        };
+        addr.send(EnsureIndexFieldWith(coll.into_inner(), field, opts))
             .await
             .map_err(ErrorServiceUnavailable)
-            .and_then(|res| res.map_err(ErrorInternalServerError))
+            .and_then(|res| res.map_err(storage_error))
             .map(move |res| {
                 if res {
-                    HttpResponse::Created()
+                    // the index is building in background
+                    HttpResponse::Accepted()
                 } else {
                     HttpResponse::Ok()
                 }
@@ -289,6 +688,22 @@ pub async fn ensure_index(
     }
 }
 
+/// Get index handler
+pub async fn get_index(
+    addr: Data<StorageAddr>,
+    path: Path<(String, String)>,
+) -> Result<Json<KeyField>> {
+    let (coll, idx) = path.into_inner();
+    addr.send(GetIndex(coll, idx))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map_err(storage_error))
+        .and_then(|res| {
+            res.map(Json)
+                .ok_or_else(|| ErrorNotFound("Index not found"))
+        })
+}
+
 /// Drop index handler
 pub async fn drop_index(
     addr: Data<StorageAddr>,
@@ -298,7 +713,7 @@ pub async fn drop_index(
     addr.send(DropIndex(coll, idx))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .and_then(|res| {
             if res {
                 Ok(HttpResponse::NoContent().finish())
@@ -318,7 +733,7 @@ pub async fn insert_document(
     addr.send(Insert(&*coll, doc.into_inner()))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .and_then(move |id| {
             req.url_for("document", &[&coll.into_inner(), &id.to_string()])
                 .map_err(ErrorInternalServerError)
@@ -343,6 +758,11 @@ pub struct FindParams {
     pub offset: Option<usize>,
     #[serde(default)]
     pub length: Option<usize>,
+    #[serde(default)]
+    pub after: Option<Primary>,
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub projection: Option<Projection>,
 }
 
 /// Find documents query handler
@@ -356,19 +776,24 @@ pub async fn find_documents(
         order,
         offset,
         length,
+        after,
+        projection,
     } = query.into_inner();
-    addr.send(Find::<_, Value>(coll.into_inner(), filter, order))
+    let opts = FindOptions {
+        offset,
+        limit: length,
+        after,
+        projection,
+    };
+    addr.send(FindWith::<_, Value>(coll.into_inner(), filter, order, opts))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .and_then(move |docs| {
-            docs.skip(offset.unwrap_or(0))
-                .take(length.unwrap_or(usize::MAX))
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(ErrorInternalServerError)
+            docs.collect::<Result<Vec<_>, _>>()
+                .map_err(storage_error)
                 .map(Json)
         })
-        .map_err(ErrorInternalServerError)
 }
 
 /// Update query parameters
@@ -378,19 +803,66 @@ pub struct UpdateParams {
     #[serde(with = "json_str")]
     pub filter: Option<Filter>,
     pub modify: Modify,
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub upsert: Option<Value>,
+    #[serde(default)]
+    pub returning: Option<Returning>,
 }
 
 /// Update documents query handler
+///
+/// When the default document for upsert is specified and nothing found, it will be inserted.
+/// When the returning option is specified the affected documents will be returned.
+///
 pub async fn update_documents(
     addr: Data<StorageAddr>,
     coll: Path<String>,
     query: Query<UpdateParams>,
+    req: HttpRequest,
 ) -> Result<HttpResponse> {
-    let UpdateParams { filter, modify } = query.into_inner();
-    addr.send(Update(coll.into_inner(), filter, modify))
+    let UpdateParams {
+        filter,
+        modify,
+        upsert,
+        returning,
+    } = query.into_inner();
+    let coll = coll.into_inner();
+
+    if let Some(doc) = upsert {
+        return addr
+            .send(Upsert(coll.clone(), filter, modify, doc))
+            .await
+            .map_err(ErrorServiceUnavailable)
+            .and_then(|res| res.map_err(storage_error))
+            .and_then(move |id| {
+                if let Some(id) = id {
+                    req.url_for("document", &[&coll, &id.to_string()])
+                        .map_err(ErrorInternalServerError)
+                        .map(|url| {
+                            HttpResponse::Created()
+                                .header("location", url.as_str())
+                                .finish()
+                        })
+                } else {
+                    Ok(HttpResponse::NoContent().finish())
+                }
+            });
+    }
+
+    if let Some(returning) = returning {
+        return addr
+            .send(Update(coll, filter, modify).returning::<Value>(returning))
+            .await
+            .map_err(ErrorServiceUnavailable)
+            .and_then(|res| res.map_err(storage_error))
+            .map(|docs: Vec<Affected<Value>>| HttpResponse::Ok().json(docs));
+    }
+
+    addr.send(Update(coll, filter, modify))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .map(|affected_docs| {
             HttpResponse::NoContent()
                 .header("affected", affected_docs.to_string())
@@ -404,17 +876,30 @@ pub struct RemoveParams {
     #[serde(default)]
     #[serde(with = "json_str")]
     pub filter: Option<Filter>,
+    #[serde(default)]
+    pub returning: bool,
 }
 
 /// Remove documents query handler
+///
+/// When the returning option is set the removed documents will be returned.
+///
 pub async fn remove_documents(
     (addr, coll, query): (Data<StorageAddr>, Path<String>, Query<RemoveParams>),
 ) -> Result<HttpResponse> {
-    let RemoveParams { filter } = query.into_inner();
+    let RemoveParams { filter, returning } = query.into_inner();
+    if returning {
+        return addr
+            .send(Remove(coll.into_inner(), filter).returning::<Value>())
+            .await
+            .map_err(ErrorServiceUnavailable)
+            .and_then(|res| res.map_err(storage_error))
+            .map(|docs| HttpResponse::Ok().json(docs));
+    }
     addr.send(Remove(coll.into_inner(), filter))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .map(|affected_docs| {
             HttpResponse::NoContent()
                 .header("affected", affected_docs.to_string())
@@ -423,18 +908,23 @@ pub async fn remove_documents(
 }
 
 /// Get document handler
+///
+/// The revision of document is returned in the `revision` header.
+///
 pub async fn get_document(
     addr: Data<StorageAddr>,
     path: Path<(String, Primary)>,
-) -> Result<Json<Value>> {
+) -> Result<HttpResponse> {
     let (coll, id) = path.into_inner();
-    addr.send(Get(coll, id))
+    addr.send(GetWithRevision::<_, Value>(coll, id))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
-        .and_then(|res| {
-            res.map(Json)
-                .ok_or_else(|| ErrorNotFound("Document not found"))
+        .and_then(|res| res.map_err(storage_error))
+        .and_then(|res| res.ok_or_else(|| ErrorNotFound("Document not found")))
+        .map(|(doc, rev)| {
+            HttpResponse::Ok()
+                .header("revision", rev.to_string())
+                .json(doc)
         })
 }
 
@@ -448,10 +938,22 @@ pub struct DocumentWithId {
 
 impl Document for DocumentWithId {}
 
+/// Put query parameters
+#[derive(Serialize, Deserialize)]
+pub struct PutParams {
+    #[serde(default)]
+    pub rev: Option<Revision>,
+}
+
 /// Put document handler
+///
+/// When the revision is specified the document will be replaced only if it has that revision.
+/// The new revision of document is returned in the `revision` header.
+///
 pub async fn put_document(
     addr: Data<StorageAddr>,
     path: Path<(String, Primary)>,
+    query: Query<PutParams>,
     data: Json<Value>,
 ) -> Result<HttpResponse> {
     let (coll, id) = path.into_inner();
@@ -459,11 +961,37 @@ pub async fn put_document(
         id,
         val: data.into_inner(),
     };
-    addr.send(Put(coll, doc))
-        .await
-        .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
-        .map(|_| HttpResponse::NoContent().finish())
+    if let Some(rev) = query.into_inner().rev {
+        addr.send(PutIfRevision(coll, doc, rev))
+            .await
+            .map_err(ErrorServiceUnavailable)
+            .and_then(|res| res.map_err(storage_error))
+            .map(|rev| {
+                HttpResponse::NoContent()
+                    .header("revision", rev.to_string())
+                    .finish()
+            })
+    } else {
+        addr.send(Put(coll, doc))
+            .await
+            .map_err(ErrorServiceUnavailable)
+            .and_then(|res| res.map_err(storage_error))
+            .map(|_| HttpResponse::NoContent().finish())
+    }
+}
+
+/// Map storage error to response with appropriate status
+fn storage_error(error: LeError) -> Error {
+    match &error {
+        LeError::NotFound { .. } => ErrorNotFound(error),
+        LeError::ConflictError(_) | LeError::DuplicateKey { .. } | LeError::DuplicateId { .. } => {
+            ErrorConflict(error)
+        }
+        LeError::ValidationError(_) => ErrorUnprocessableEntity(error),
+        LeError::MissingIndex { .. } | LeError::MissingId => ErrorBadRequest(error),
+        LeError::MapFull => ErrorInsufficientStorage(error),
+        _ => ErrorInternalServerError(error),
+    }
 }
 
 /// Delete document handler
@@ -475,7 +1003,7 @@ pub async fn delete_document(
     addr.send(Delete(coll, id))
         .await
         .map_err(ErrorServiceUnavailable)
-        .and_then(|res| res.map_err(ErrorInternalServerError))
+        .and_then(|res| res.map_err(storage_error))
         .and_then(|res| {
             if res {
                 Ok(HttpResponse::NoContent().finish())
@@ -484,3 +1012,105 @@ pub async fn delete_document(
             }
         })
 }
+
+/// Aggregate query parameters
+#[derive(Serialize, Deserialize)]
+pub struct AggregateParams {
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub filter: Option<Filter>,
+    #[serde(with = "json_str")]
+    pub aggregation: Aggregation,
+}
+
+/// Aggregate documents query handler
+pub async fn aggregate_documents(
+    addr: Data<StorageAddr>,
+    coll: Path<String>,
+    query: Query<AggregateParams>,
+) -> Result<Json<Aggregated>> {
+    let AggregateParams {
+        filter,
+        aggregation,
+    } = query.into_inner();
+    addr.send(Aggregate(coll.into_inner(), filter, aggregation))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map_err(storage_error))
+        .map(Json)
+}
+
+/// Watch query parameters
+#[derive(Serialize, Deserialize)]
+pub struct WatchParams {
+    #[serde(default)]
+    #[serde(with = "json_str")]
+    pub filter: Option<Filter>,
+}
+
+/// Document change event
+#[derive(Serialize)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub id: Primary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+impl ChangeEvent {
+    fn from_change(change: &Change) -> LeResult<Self> {
+        Ok(ChangeEvent {
+            kind: change.kind,
+            id: change.id.clone(),
+            old: change.old_doc()?,
+            new: change.new_doc()?,
+        })
+    }
+
+    fn to_sse(change: &Change) -> Bytes {
+        let data = Self::from_change(change)
+            .map_err(|error| error.to_string())
+            .and_then(|event| serde_json::to_string(&event).map_err(|error| error.to_string()));
+        Bytes::from(match data {
+            Ok(data) => format!("data: {}\n\n", data),
+            Err(error) => format!("event: error\ndata: {}\n\n", error),
+        })
+    }
+}
+
+/// Watch documents changes handler
+///
+/// The changes are streamed as server-sent events.
+///
+pub async fn watch_documents(
+    addr: Data<StorageAddr>,
+    coll: Path<String>,
+    query: Query<WatchParams>,
+) -> Result<HttpResponse> {
+    let WatchParams { filter } = query.into_inner();
+    let watcher = addr
+        .send(Watch(coll.into_inner(), filter))
+        .await
+        .map_err(ErrorServiceUnavailable)
+        .and_then(|res| res.map_err(storage_error))?;
+
+    let (sender, receiver) = unbounded();
+
+    // the watcher blocks so the changes is forwarded from separate thread
+    thread::spawn(move || {
+        while !sender.is_closed() {
+            if let Some(change) = watcher.next_timeout(Duration::from_secs(1)) {
+                if sender.unbounded_send(ChangeEvent::to_sse(&change)).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("cache-control", "no-cache")
+        .streaming(receiver.map(Ok::<_, Error>)))
+}
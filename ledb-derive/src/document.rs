@@ -12,17 +12,23 @@ pub fn derive_document_wrapped(input: &DeriveInput) -> Result<TokenStream, Strin
 fn derive_document(input: &DeriveInput) -> Result<TokenStream, String> {
     let type_name = &input.ident;
     let is_nested = has_nested_attribute(input);
+    let compound_attrs = get_compound_attributes(input);
     let mut primary_field = None;
+    let mut primary_type = None;
     let mut index_fields = Vec::new();
+    let mut compound_fields = Vec::new();
     let mut nested_docs = Vec::new();
 
     match &input.data {
         Data::Struct(data) => match &data.fields {
             Fields::Named(fields) => {
                 for field in &fields.named {
-                    if let Some(primary_field_name) = get_primary_attribute(&field) {
+                    if let Some((primary_field_name, primary_field_type)) =
+                        get_primary_attribute(&field)
+                    {
                         if primary_field.is_none() {
                             primary_field = get_serde_rename(&field).or(Some(primary_field_name));
+                            primary_type = primary_field_type;
                         } else {
                             return Err("Only one primary key field per document allowed".into());
                         }
@@ -45,6 +51,36 @@ fn derive_document(input: &DeriveInput) -> Result<TokenStream, String> {
                         ));
                     }
                 }
+
+                for (index_kind, field_names) in compound_attrs {
+                    if field_names.len() < 2 {
+                        return Err("Compound index must contain at least two fields".into());
+                    }
+
+                    let mut components = Vec::new();
+
+                    for field_name in field_names {
+                        if let Some(field) = fields.named.iter().find(|field| {
+                            field
+                                .ident
+                                .as_ref()
+                                .map(|ident| ident == &field_name)
+                                .unwrap_or(false)
+                        }) {
+                            components.push((
+                                get_serde_rename(field).unwrap_or(field_name),
+                                field.ty.clone(),
+                            ));
+                        } else {
+                            return Err(format!(
+                                "Unknown field '{}' of compound index",
+                                field_name
+                            ));
+                        }
+                    }
+
+                    compound_fields.push((components, index_kind));
+                }
             }
             _ => return Err("Only struct with named fields can be represented as document".into()),
         },
@@ -67,7 +103,27 @@ fn derive_document(input: &DeriveInput) -> Result<TokenStream, String> {
         }
     };
 
-    let key_fields_fn = if index_fields.is_empty() && nested_docs.is_empty() {
+    let primary_type_fn = if let Some(primary_type) = primary_type {
+        let primary_type = match primary_type.as_ref() {
+            "int" | "integer" => quote!(Int),
+            "bigint" => quote!(BigInt),
+            "str" | "string" => quote!(String),
+            "bin" | "binary" => quote!(Binary),
+            _ => return Err(format!("Unknown primary key type '{}'", primary_type)),
+        };
+        quote! {
+            fn primary_type() -> _ledb_types::PrimaryType {
+                _ledb_types::PrimaryType::#primary_type
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let key_fields_fn = if index_fields.is_empty()
+        && compound_fields.is_empty()
+        && nested_docs.is_empty()
+    {
         TokenStream::new()
     } else {
         let index_fields = index_fields
@@ -80,17 +136,32 @@ fn derive_document(input: &DeriveInput) -> Result<TokenStream, String> {
                     }
                     Err(key_type) => quote! { _ledb_types::KeyType::#key_type },
                 };
-                let index_kind = match index_kind.as_str() {
-                    "unique" => quote! { _ledb_types::IndexKind::Unique },
-                    "index" => quote! { _ledb_types::IndexKind::Index },
-                    _ => unreachable!(),
-                };
+                let index_kind = index_kind_tokens(&index_kind);
 
                 quote! {
                     (#field_name, #field_type, #index_kind)
                 }
             });
 
+        let compound_fields = compound_fields.into_iter().map(|(components, index_kind)| {
+            let mut components = components.into_iter().map(|(field_name, field_type)| {
+                let field_name = Lit::Str(LitStr::new(&field_name, Span::call_site()));
+                let field_type =
+                    quote! { <#field_type as _ledb_types::DocumentKeyType>::key_type() };
+                (field_name, field_type)
+            });
+            let (field_name, field_type) = components.next().unwrap();
+            let (component_names, component_types): (Vec<_>, Vec<_>) = components.unzip();
+            let index_kind = index_kind_tokens(&index_kind);
+
+            quote! {
+                _ledb_types::KeyField::new(#field_name)
+                    .with_type(#field_type)
+                    .with_kind(#index_kind)
+                    #(.with_component(#component_names, #component_types))*
+            }
+        });
+
         let nested_docs = nested_docs
             .into_iter()
             .map(|(field_name, field_type, is_flatten)| {
@@ -111,6 +182,7 @@ fn derive_document(input: &DeriveInput) -> Result<TokenStream, String> {
             fn key_fields() -> _ledb_types::KeyFields {
                 _ledb_types::KeyFields::new()
                     #(.with_field(#index_fields))*
+                    #(.with_field(#compound_fields))*
                     #(.with_fields(#nested_docs))*
             }
         }
@@ -119,34 +191,52 @@ fn derive_document(input: &DeriveInput) -> Result<TokenStream, String> {
     Ok(quote! {
         impl _ledb_types::Document for #type_name {
             #primary_field_fn
+            #primary_type_fn
             #key_fields_fn
         }
     })
 }
 
-fn has_primary_token(token: TokenTree) -> bool {
+fn index_kind_tokens(index_kind: &str) -> TokenStream {
+    match index_kind {
+        "unique" => quote! { _ledb_types::IndexKind::Unique },
+        "index" => quote! { _ledb_types::IndexKind::Index },
+        "fulltext" => quote! { _ledb_types::IndexKind::FullText },
+        "spatial" => quote! { _ledb_types::IndexKind::Spatial },
+        _ => unreachable!(),
+    }
+}
+
+fn get_primary_token(token: TokenTree) -> Option<Option<String>> {
     if let TokenTree::Group(group) = token {
-        group.stream().into_iter().any(|token| {
-            if let TokenTree::Ident(ident) = token {
-                ident == "primary"
-            } else {
-                false
+        let mut tokens = group.stream().into_iter();
+        while let Some(token) = tokens.next() {
+            match token {
+                TokenTree::Ident(ident) if ident == "primary" => {
+                    // the type of primary key may follow
+                    return Some(match tokens.next() {
+                        Some(TokenTree::Ident(key)) => Some(key.to_string()),
+                        _ => None,
+                    });
+                }
+                _ => (),
             }
-        })
-    } else {
-        false
+        }
     }
+    None
 }
 
-fn get_primary_attribute(field: &Field) -> Option<String> {
+fn get_primary_attribute(field: &Field) -> Option<(String, Option<String>)> {
     if let Some(ident) = &field.ident {
         for attr in &field.attrs {
             if attr.path.leading_colon.is_none()
                 && attr.path.segments.len() == 1
                 && attr.path.segments.first().unwrap().ident == "document"
-                && attr.tokens.clone().into_iter().any(has_primary_token)
             {
-                return Some(ident.to_string());
+                if let Some(key_type) = attr.tokens.clone().into_iter().find_map(get_primary_token)
+                {
+                    return Some((ident.to_string(), key_type));
+                }
             }
         }
     }
@@ -165,16 +255,25 @@ fn get_index_attribute(field: &Field) -> Option<(String, Result<Type, TokenStrea
                     if let TokenTree::Group(group) = token {
                         let mut tokens = group.stream().into_iter();
                         match &tokens.next() {
-                            Some(TokenTree::Ident(kind)) if kind == "unique" || kind == "index" => {
+                            Some(TokenTree::Ident(kind))
+                                if kind == "unique"
+                                    || kind == "index"
+                                    || kind == "fulltext"
+                                    || kind == "spatial" =>
+                            {
                                 let key_type = if let Some(TokenTree::Ident(key)) = &tokens.next() {
                                     match key.to_string().as_ref() {
                                         "int" | "integer" => Err(quote!(Int)),
                                         "float" => Err(quote!(Float)),
                                         "str" | "string" => Err(quote!(String)),
+                                        "folded" => Err(quote!(Folded)),
                                         "bin" | "binary" => Err(quote!(Binary)),
                                         "bool" | "boolean" => Err(quote!(Bool)),
                                         _ => Ok(field.ty.clone()),
                                     }
+                                } else if kind == "spatial" {
+                                    // the points has no key type
+                                    Err(quote!(Float))
                                 } else {
                                     Ok(field.ty.clone())
                                 };
@@ -215,6 +314,41 @@ fn has_nested_attribute(input: &DeriveInput) -> bool {
     false
 }
 
+fn get_compound_attributes(input: &DeriveInput) -> Vec<(String, Vec<String>)> {
+    let mut compounds = Vec::new();
+
+    for attr in &input.attrs {
+        if attr.path.leading_colon.is_none()
+            && attr.path.segments.len() == 1
+            && attr.path.segments.first().unwrap().ident == "document"
+        {
+            for token in attr.tokens.clone() {
+                if let TokenTree::Group(group) = token {
+                    let mut tokens = group.stream().into_iter();
+                    match (&tokens.next(), &tokens.next()) {
+                        (Some(TokenTree::Ident(kind)), Some(TokenTree::Group(fields)))
+                            if kind == "unique" || kind == "index" || kind == "spatial" =>
+                        {
+                            let fields = fields
+                                .stream()
+                                .into_iter()
+                                .filter_map(|token| match token {
+                                    TokenTree::Ident(name) => Some(name.to_string()),
+                                    _ => None,
+                                })
+                                .collect();
+                            compounds.push((kind.to_string(), fields));
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    compounds
+}
+
 fn get_nested_attribute(field: &Field) -> Option<(String, Type)> {
     if let Some(ident) = &field.ident {
         for attr in &field.attrs {
@@ -329,6 +463,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn document_primary_type() {
+        let src: DeriveInput = parse_quote! {
+            #[derive(Document)]
+            struct TestDoc {
+                #[document(primary string)]
+                id: Option<Primary>,
+            }
+        };
+
+        let res = derive_document(&src).unwrap();
+
+        assert_eq!(
+            res.to_string(),
+            quote! {
+                impl _ledb_types::Document for TestDoc {
+                    fn primary_field() -> _ledb_types::Identifier {
+                        "id".into()
+                    }
+                    fn primary_type() -> _ledb_types::PrimaryType {
+                        _ledb_types::PrimaryType::String
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
     #[test]
     fn document_primary_missing() {
         let src: DeriveInput = parse_quote! {
@@ -627,4 +789,66 @@ mod test {
             }.to_string()
         );
     }
+
+    #[test]
+    fn document_compound() {
+        let src: DeriveInput = parse_quote! {
+            #[derive(Document)]
+            #[document(index(status, timestamp))]
+            #[document(unique(owner, title, rev))]
+            struct TestDoc {
+                #[document(primary)]
+                id: u32,
+                status: String,
+                #[serde(rename = "created")]
+                timestamp: i64,
+                owner: String,
+                title: String,
+                rev: u32,
+            }
+        };
+
+        let res = derive_document(&src).unwrap();
+
+        assert_eq!(
+            res.to_string(),
+            quote! {
+                impl _ledb_types::Document for TestDoc {
+                    fn primary_field() -> _ledb_types::Identifier {
+                        "id".into()
+                    }
+
+                    fn key_fields() -> _ledb_types::KeyFields {
+                        _ledb_types::KeyFields::new()
+                            .with_field(_ledb_types::KeyField::new("status")
+                                        .with_type(<String as _ledb_types::DocumentKeyType>::key_type())
+                                        .with_kind(_ledb_types::IndexKind::Index)
+                                        .with_component("created", <i64 as _ledb_types::DocumentKeyType>::key_type()))
+                            .with_field(_ledb_types::KeyField::new("owner")
+                                        .with_type(<String as _ledb_types::DocumentKeyType>::key_type())
+                                        .with_kind(_ledb_types::IndexKind::Unique)
+                                        .with_component("title", <String as _ledb_types::DocumentKeyType>::key_type())
+                                        .with_component("rev", <u32 as _ledb_types::DocumentKeyType>::key_type()))
+                    }
+                }
+            }.to_string()
+        );
+    }
+
+    #[test]
+    fn document_compound_unknown() {
+        let src: DeriveInput = parse_quote! {
+            #[derive(Document)]
+            #[document(index(status, created))]
+            struct TestDoc {
+                #[document(primary)]
+                id: u32,
+                status: String,
+            }
+        };
+
+        let res = derive_document(&src).unwrap_err();
+
+        assert_eq!(res, "Unknown field 'created' of compound index");
+    }
 }
@@ -23,7 +23,7 @@ struct MyDoc {
 
 This generates `Document` trait implementation for struct `MyDoc`.
 It requires single field marked as primary key per document.
-Currently primary key should be an integer only.
+By default the primary key is an integer which generated automatically on insertion.
 Also it not needed to be an optional field, but in this case you should take care of parsing (for example add `serde(default)` annotation).
 
 ## Defining key fields for indexing
@@ -52,9 +52,36 @@ struct MyDoc {
     // unique int key
     #[document(unique)]
     timestamp: u64,
+    // full-text index of words
+    #[document(fulltext)]
+    content: String,
 }
 ```
 
+## Primary key types
+
+The string or binary primary keys can be used instead of integers.
+In this case the type of primary key should be set using annotation like so:
+
+```rust
+# extern crate serde;
+# extern crate ledb;
+#
+use serde::{Serialize, Deserialize};
+use ledb::{Document};
+
+#[derive(Serialize, Deserialize, Document)]
+struct MyDoc {
+    // the key types is: int (default), bigint, string, binary
+    #[document(primary string)]
+    name: String,
+    // ...
+}
+```
+
+The documents with string and binary keys must be inserted using `insert_with_id`
+into collection which opened using `Storage::collection_for`.
+
 ## Overriding key types
 
 In some cases it may be ambiguous to determine actual type of key by field type.
@@ -81,6 +108,58 @@ struct MyDoc {
 }
 ```
 
+## Compound keys
+
+To index several fields together you can add compound index annotation to document struct:
+
+```rust
+# extern crate serde;
+# extern crate ledb;
+#
+use serde::{Serialize, Deserialize};
+use ledb::{Document};
+
+#[derive(Serialize, Deserialize, Document)]
+#[document(index(status, created))]
+#[document(unique(author, title))]
+struct MyDoc {
+    #[document(primary)]
+    id: u32,
+    status: String,
+    created: u64,
+    author: String,
+    title: String,
+}
+```
+
+The key types of components are determined by field types.
+The compound keys can be queried by the prefix of components and ordered in the order of components.
+
+## Spatial keys
+
+The geographic points can be indexed using spatial index annotation.
+The point field should be an object with `lat` and `lon` fields like `GeoPoint` or GeoJSON point,
+also the pair of latitude and longitude fields can be indexed together:
+
+```rust
+# extern crate serde;
+# extern crate ledb;
+#
+use serde::{Serialize, Deserialize};
+use ledb::{Document, GeoPoint};
+
+#[derive(Serialize, Deserialize, Document)]
+#[document(spatial(lat, lon))]
+struct MyDoc {
+    #[document(primary)]
+    id: u32,
+    #[document(spatial)]
+    home: GeoPoint,
+    lat: f64,
+    lon: f64,
+}
+```
+
 ## Nested documents
 
 Of course you can add nested documents which may also have key fields:
@@ -1,4 +1,6 @@
-export type Primary = number;
+export type Primary = number | string | ArrayBufferLike;
+
+export type PrimaryType = 'int' | 'bigint' | 'string' | 'binary';
 
 export interface GenericDocument {}
 
@@ -15,19 +17,40 @@ export interface Documents<T> {
     count(): number;
 }
 
+export type ChangeKind = 'insert' | 'update' | 'delete';
+
+export interface Change<T> {
+    kind: ChangeKind;
+    id: Primary;
+    old: Document<T> | null;
+    new: Document<T> | null;
+}
+
+// The changes are received after commit
+export interface Watcher<T> {
+    // get next change or null (waits for change up to timeout in milliseconds when it specified)
+    next(timeout?: number): Change<T> | null;
+    // unsubscribe
+    close(): void;
+}
+
 export type KeyType
     = 'int'
     | 'float'
     | 'bool'
     | 'string'
+    | 'folded'
     | 'binary'
     ;
 
-export type KeyData = number | string | boolean | ArrayBufferLike;
+export type KeyData = number | string | boolean | ArrayBufferLike | KeyTuple;
+
+// components of compound key (or its prefix)
+export type KeyTuple = (number | string | boolean | ArrayBufferLike)[];
 
 export type Value = any;
 
-export type IndexKind = 'index' | 'unique';
+export type IndexKind = 'index' | 'unique' | 'fulltext' | 'spatial';
 
 export interface KeyField {
     // field path
@@ -36,10 +59,46 @@ export interface KeyField {
     key: KeyType,
     // index kind
     kind: IndexKind,
+    // key types of the rest components of compound key
+    // (the path of compound key consists of comma-separated paths of components)
+    compound?: KeyType[],
+    // the state of building when the index isn't ready yet
+    build?: IndexBuild,
+}
+
+export interface IndexBuild {
+    // number of indexed documents
+    done: number,
+    // total number of documents when building started
+    total: number,
+    // error which stopped building
+    error?: string,
 }
 
 export type KeyFields = KeyField[];
 
+export interface IndexIntegrity {
+    // field path
+    path: string,
+    // number of entries in index
+    entries: number,
+    // documents which referred by entries but missing or has no such keys
+    dangling?: Primary[],
+    // documents which keys is missing in index
+    missing?: Primary[],
+    // documents which has same keys in unique index
+    duplicates?: Primary[],
+}
+
+export interface Integrity {
+    // number of documents
+    documents: number,
+    // documents which cannot be decoded
+    corrupted?: Primary[],
+    // integrity of each ready index
+    indexes: IndexIntegrity[],
+}
+
 export type Filter
     = FilterCond
     | { [field: string]: FilterComp }
@@ -65,6 +124,13 @@ export type FilterComp
     | FilterGe
     | FilterBw
     | FilterHas
+    | FilterPrefix
+    | FilterRegex
+    | FilterIEq
+    | FilterIPrefix
+    | FilterMatch
+    | FilterWithin
+    | FilterNear
     ;
 
 export interface FilterEq { $eq: KeyData }
@@ -77,17 +143,45 @@ export interface FilterBw { $in: [KeyData, boolean, KeyData, boolean] }
 
 export type FilterHas = '$has';
 
+export interface FilterPrefix { $prefix: string }
+export interface FilterRegex { $regex: string }
+export interface FilterIEq { $ieq: string }
+export interface FilterIPrefix { $iprefix: string }
+export interface FilterMatch { $match: string }
+export interface FilterWithin { $within: GeoBox }
+export interface FilterNear { $near: [GeoPoint, number] }
+
+export interface GeoPoint { lat: number, lon: number }
+export interface GeoBox { sw: GeoPoint, ne: GeoPoint }
+
 export type FilterNone = null;
 
 export type Order
     = OrderByPrimary
-    | OrderByField;
+    | OrderByField
+    | OrderByNear;
 
 export type OrderByPrimary = OrderKind;
 export type OrderByField = [string, OrderKind];
+export interface OrderByNear { [field: string]: { $near: GeoPoint } }
 
 export type OrderKind = '$asc' | '$desc';
 
+export interface FindOptions {
+    offset?: number,
+    limit?: number,
+    after?: Primary,
+    projection?: Projection,
+}
+
+export type Projection
+    = ProjectionInclude
+    | ProjectionExclude
+    ;
+
+export interface ProjectionInclude { $include: string[] }
+export interface ProjectionExclude { $exclude: string[] }
+
 export type Modify = [string, Action][];
 
 export type Action
@@ -116,6 +210,45 @@ export interface ActionReplace { $replace: [string, string] }
 export interface ActionSplice { $splice: [number, number, ...Value[]] }
 export interface ActionMerge { $merge: Value }
 
+export type Aggregation
+    = AggregationCount
+    | AggregationDistinct
+    | AggregationSum
+    | AggregationAvg
+    | AggregationMin
+    | AggregationMax
+    | AggregationGroup
+    ;
+
+export type AggregationCount = '$count';
+export interface AggregationDistinct { $distinct: string }
+export interface AggregationSum { $sum: string }
+export interface AggregationAvg { $avg: string }
+export interface AggregationMin { $min: string }
+export interface AggregationMax { $max: string }
+export interface AggregationGroup { $group: { by: string, fields?: Record<string, Accumulator> } }
+
+export type Accumulator
+    = AccumulatorCount
+    | AccumulatorSum
+    | AccumulatorAvg
+    | AccumulatorMin
+    | AccumulatorMax
+    ;
+
+export type AccumulatorCount = '$count';
+export interface AccumulatorSum { $sum: string }
+export interface AccumulatorAvg { $avg: string }
+export interface AccumulatorMin { $min: string }
+export interface AccumulatorMax { $max: string }
+
+export interface Group {
+    key: KeyData,
+    values: Record<string, KeyData | null>,
+}
+
+export type Aggregated = number | KeyData | KeyData[] | Group[] | null;
+
 // Storage info
 export interface Info {
     map_size: number,
@@ -123,6 +256,10 @@ export interface Info {
     last_transaction: number,
     max_readers: number,
     num_readers: number,
+    // size of used pages
+    used_size: number,
+    // size which the memory map may grow to (null when unlimited)
+    max_size: number | null,
 }
 
 // Storage stats
@@ -135,10 +272,39 @@ export interface Stats {
     data_entries: number,
 }
 
+// Index stats
+export interface IndexStats {
+    // field path
+    path: string,
+    // number of entries in index
+    entries: number,
+    // number of distinct keys in index
+    keys: number,
+    // page usage of index
+    pages: Stats,
+}
+
+// Collection stats
+export interface CollectionStats {
+    // number of documents
+    documents: number,
+    // total size of encoded documents in bytes
+    total_size: number,
+    // average size of encoded document in bytes
+    average_size: number,
+    // page usage of collection
+    pages: Stats,
+    // stats of each ready index
+    indexes: IndexStats[],
+}
+
 // Storage options
 export interface Options {
     // options
     map_size?: number,
+    // growing of memory map when storage is full
+    map_grow_step?: number,
+    map_max_size?: number,
     max_readers?: number,
     max_dbs?: number,
     // flags
@@ -152,6 +318,29 @@ export interface Options {
     no_tls?: boolean,
     read_only?: boolean,
     write_map?: boolean,
+    // behavior
+    no_full_scan?: boolean,
+    reuse_ids?: boolean,
+}
+
+export type DataFormat = 'json' | 'cbor';
+
+export type ImportMode = 'append' | 'replace' | 'upsert';
+
+export interface ExportOptions {
+    format?: DataFormat,
+    collections?: string[],
+}
+
+export interface ImportOptions {
+    format?: DataFormat,
+    mode?: ImportMode,
+    collections?: string[],
+}
+
+export interface Progress {
+    collection: string,
+    documents: number,
 }
 
 // Storage handle interface
@@ -161,29 +350,45 @@ export class Storage {
     get_info(): Info;
     get_stats(): Stats;
 
+    // make backup copy of storage (optionally compacted)
+    backup(path: string, compact?: boolean): void;
+
+    // export collections with documents and indexes into file
+    export(path: string, opts?: ExportOptions, progress?: (state: Progress) => void): number;
+    // import collections with documents and indexes from file
+    import(path: string, opts?: ImportOptions, progress?: (state: Progress) => void): number;
+
     has_collection(name: string): boolean;
-    collection(name: string): Collection;
+    collection(name: string, primary?: PrimaryType): Collection;
     drop_collection(name: string): boolean;
     get_collections(): string[];
 }
 
 // Collection handle interface
 export class Collection {
-    constructor(storage: Storage, name: string);
+    constructor(storage: Storage, name: string, primary?: PrimaryType);
     
     insert<T extends GenericDocument>(doc: T): Primary;
-    find<T extends GenericDocument>(filter: Filter, order?: Order): Documents<T>;
+    insert_with_id<T extends GenericDocument>(doc: T): Primary;
+    find<T extends GenericDocument>(filter: Filter, order?: Order, opts?: FindOptions): Documents<T>;
     update(filter: Filter, modify: Modify): number;
+    upsert<T extends GenericDocument>(filter: Filter, modify: Modify, doc: T): Primary | null;
     remove(filter: Filter): number;
 
+    aggregate(filter: Filter, aggregation: Aggregation): Aggregated;
+
     dump<T extends GenericDocument>(): Documents<T>;
     load<T extends GenericDocument>(docs: Documents<T>): number;
 
+    watch<T extends GenericDocument>(filter?: Filter): Watcher<T>;
+
     purge(): void;
 
     has(id: Primary): boolean;
     get<T extends GenericDocument>(id: Primary): T | null;
     put<T extends GenericDocument>(doc: T): void;
+    put_if_revision<T extends GenericDocument>(doc: T, rev: number): number;
+    revision(id: Primary): number | null;
     delete(id: Primary): boolean;
 
     get_indexes(): KeyFields;
@@ -191,6 +396,11 @@ export class Collection {
     has_index(path: string): void;
     ensure_index(path: string, kind: IndexKind, key: KeyType): boolean;
     drop_index(path: string): boolean;
+
+    verify(): Integrity;
+    repair(): Integrity;
+
+    stats(): CollectionStats;
 }
 
 // Get openned databases
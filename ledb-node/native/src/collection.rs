@@ -1,11 +1,9 @@
-use std::u32;
-
 use neon::prelude::*;
 use neon_serde::{from_value, to_value};
 
-use ledb::{Collection, Filter, Identifier, IndexKind, KeyType, Modify, Order, Primary, Value};
+use ledb::{Aggregation, Collection, Filter, FindOptions, Identifier, IndexKind, KeyType, Modify, Order, Primary, PrimaryType, Revision, Value};
 
-use super::{JsDocuments, JsStorage, refine};
+use super::{JsDocuments, JsStorage, JsWatcher, refine};
 
 declare_types! {
     /// A collection class
@@ -13,10 +11,19 @@ declare_types! {
         init(mut cx) {
             let storage = cx.argument::<JsStorage>(0)?;
             let name = cx.argument::<JsString>(1)?.value();
+            let primary: Option<PrimaryType> = if let Some(primary) = cx.argument_opt(2) {
+                from_value(&mut cx, primary)?
+            } else {
+                None
+            };
             let collection = js_try!(cx, {
                 let guard = cx.lock();
                 let storage = storage.borrow(&guard);
-                storage.collection(&name)
+                if let Some(primary) = primary {
+                    storage.collection_with(&name, primary)
+                } else {
+                    storage.collection(&name)
+                }
             });
             Ok(collection)
         }
@@ -33,7 +40,22 @@ declare_types! {
                 collection.insert(&doc)
             });
 
-            Ok(cx.number(id).upcast())
+            Ok(js_try!(cx, to_value(&mut cx, &id)).upcast())
+        }
+
+        method insert_with_id(mut cx) {
+            let raw = cx.argument(0)?;
+            let doc: Value = from_value(&mut cx, raw)?;
+
+            let this = cx.this();
+
+            let id = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.insert_with_id(&doc)
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &id)).upcast())
         }
 
         method find(mut cx) {
@@ -49,12 +71,18 @@ declare_types! {
                 Order::default()
             };
 
+            let opts: FindOptions = if let Some(opts) = cx.argument_opt(2) {
+                from_value(&mut cx, opts)?
+            } else {
+                FindOptions::default()
+            };
+
             let this = cx.this();
 
             let iter = js_try!(cx, {
                 let guard = cx.lock();
                 let collection = this.borrow(&guard);
-                collection.find(filter, order)
+                collection.find_with(filter, order, opts)
             });
 
             let mut docs = JsDocuments::new(&mut cx, vec![JsUndefined::new()])?;
@@ -89,6 +117,30 @@ declare_types! {
             Ok(cx.number(affected as u32).upcast())
         }
 
+        method upsert(mut cx) {
+            let filter: Option<Filter> = if let Some(filter) = cx.argument_opt(0) {
+                from_value(&mut cx, filter)?
+            } else {
+                None
+            };
+
+            let modify_raw = cx.argument(1)?;
+            let modify: Modify = from_value(&mut cx, modify_raw)?;
+
+            let raw = cx.argument(2)?;
+            let doc: Value = from_value(&mut cx, raw)?;
+
+            let this = cx.this();
+
+            let id = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.upsert(filter, modify, &doc)
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &id)).upcast())
+        }
+
         method remove(mut cx) {
             let filter: Option<Filter> = if let Some(filter) = cx.argument_opt(0) {
                 from_value(&mut cx, filter)?
@@ -107,6 +159,27 @@ declare_types! {
             Ok(cx.number(affected as u32).upcast())
         }
 
+        method aggregate(mut cx) {
+            let filter: Option<Filter> = if let Some(filter) = cx.argument_opt(0) {
+                from_value(&mut cx, filter)?
+            } else {
+                None
+            };
+
+            let aggregation_raw = cx.argument(1)?;
+            let aggregation: Aggregation = from_value(&mut cx, aggregation_raw)?;
+
+            let this = cx.this();
+
+            let result = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.aggregate(filter, &aggregation)
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &result)).upcast())
+        }
+
         method dump(mut cx) {
             let this = cx.this();
 
@@ -129,6 +202,32 @@ declare_types! {
 
         //method load(mut cx) {}
 
+        method watch(mut cx) {
+            let filter: Option<Filter> = if let Some(filter) = cx.argument_opt(0) {
+                from_value(&mut cx, filter)?
+            } else {
+                None
+            };
+
+            let this = cx.this();
+
+            let watcher = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.watch(filter)
+            });
+
+            let mut changes = JsWatcher::new(&mut cx, vec![JsUndefined::new()])?;
+
+            {
+                let guard = cx.lock();
+                let mut changes = changes.borrow_mut(&guard);
+                changes.0 = Some(watcher);
+            }
+
+            Ok(changes.upcast())
+        }
+
         method purge(mut cx) {
             let this = cx.this();
 
@@ -142,13 +241,7 @@ declare_types! {
         }
 
         method has(mut cx) {
-            let id = cx.argument::<JsNumber>(0)?.value();
-
-            if id < 1.0 || id > f64::from(u32::MAX) {
-                return cx.throw_range_error("Document id must be in range 1..N");
-            }
-
-            let id: Primary = id as Primary;
+            let id = id_argument(&mut cx, 0)?;
 
             let this = cx.this();
 
@@ -162,13 +255,7 @@ declare_types! {
         }
 
         method get(mut cx) {
-            let id = cx.argument::<JsNumber>(0)?.value();
-
-            if id < 1.0 || id > f64::from(u32::MAX) {
-                return cx.throw_range_error("Document id must be in range 1..N");
-            }
-
-            let id: Primary = id as Primary;
+            let id = id_argument(&mut cx, 0)?;
 
             let this = cx.this();
 
@@ -198,14 +285,42 @@ declare_types! {
             Ok(cx.undefined().upcast())
         }
 
-        method delete(mut cx) {
-            let id = cx.argument::<JsNumber>(0)?.value();
+        method put_if_revision(mut cx) {
+            let raw = cx.argument(0)?;
+            let doc: Value = from_value(&mut cx, raw)?;
+            let rev = cx.argument::<JsNumber>(1)?.value();
 
-            if id < 1.0 || id > f64::from(u32::MAX) {
-                return cx.throw_range_error("Document id must be in range 1..N");
+            if rev < 0.0 || rev.fract() != 0.0 {
+                return cx.throw_range_error("Document revision must be in range 0..N");
             }
 
-            let id: Primary = id as Primary;
+            let this = cx.this();
+
+            let rev = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.put_if_revision(&doc, rev as Revision)
+            });
+
+            Ok(cx.number(rev as f64).upcast())
+        }
+
+        method revision(mut cx) {
+            let id = id_argument(&mut cx, 0)?;
+
+            let this = cx.this();
+
+            let rev = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.revision(id)
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &rev)).upcast())
+        }
+
+        method delete(mut cx) {
+            let id = id_argument(&mut cx, 0)?;
 
             let this = cx.this();
 
@@ -285,5 +400,64 @@ declare_types! {
             });
             Ok(cx.boolean(status).upcast())
         }
+
+        method verify(mut cx) {
+            let this = cx.this();
+
+            let integrity = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.verify()
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &integrity)).upcast())
+        }
+
+        method repair(mut cx) {
+            let this = cx.this();
+
+            let integrity = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.repair()
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &integrity)).upcast())
+        }
+
+        method stats(mut cx) {
+            let this = cx.this();
+
+            let stats = js_try!(cx, {
+                let guard = cx.lock();
+                let collection = this.borrow(&guard);
+                collection.stats()
+            });
+
+            Ok(js_try!(cx, to_value(&mut cx, &stats)).upcast())
+        }
+    }
+}
+
+/// Get the primary key/identifier from number, string or buffer argument
+fn id_argument(cx: &mut MethodContext<JsCollection>, i: i32) -> NeonResult<Primary> {
+    let id = cx.argument::<JsValue>(i)?;
+
+    if let Ok(id) = id.downcast::<JsNumber>() {
+        let id = id.value();
+
+        // the max safe integer of javascript
+        if id < 1.0 || id.fract() != 0.0 || id > 9_007_199_254_740_991.0 {
+            return cx.throw_range_error("Document id must be in range 1..N");
+        }
+
+        Ok(Primary::Int(id as u64))
+    } else if let Ok(id) = id.downcast::<JsString>() {
+        Ok(Primary::String(id.value()))
+    } else if let Ok(id) = id.downcast::<JsBuffer>() {
+        let id = cx.borrow(&id, |data| data.as_slice::<u8>().to_vec());
+        Ok(Primary::Binary(id))
+    } else {
+        cx.throw_type_error("Document id must be a number, string or buffer")
     }
 }
@@ -4,6 +4,7 @@ mod collection;
 mod documents;
 mod storage;
 mod refine;
+mod watcher;
 
 use collection::JsCollection;
 use documents::JsDocuments;
@@ -12,6 +13,7 @@ use neon::prelude::*;
 use neon_serde::to_value;
 use storage::JsStorage;
 use refine::refine;
+use watcher::JsWatcher;
 
 fn list_openned_storages(mut cx: FunctionContext) -> JsResult<JsValue> {
     let list = js_try!(cx, Storage::openned());
@@ -23,5 +25,6 @@ register_module!(mut cx, {
     cx.export_class::<JsStorage>("Storage")?;
     cx.export_class::<JsCollection>("Collection")?;
     cx.export_class::<JsDocuments>("Documents")?;
+    cx.export_class::<JsWatcher>("Watcher")?;
     Ok(())
 });
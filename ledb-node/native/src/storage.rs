@@ -1,7 +1,12 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
 use neon::prelude::*;
 use neon_serde::{from_value, to_value};
 
-use ledb::{Options, Storage};
+use ledb::{ExportOptions, ImportOptions, Options, Progress, Storage};
 
 use super::JsCollection;
 
@@ -38,6 +43,66 @@ declare_types! {
             Ok(to_value(&mut cx, &stats)?)
         }
 
+        method backup(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let compact = if let Some(compact) = cx.argument_opt(1) {
+                compact.downcast_or_throw::<JsBoolean, _>(&mut cx)?.value()
+            } else {
+                false
+            };
+            let this = cx.this();
+            js_try!(cx, {
+                let guard = cx.lock();
+                let storage = this.borrow(&guard);
+                storage.backup(&path, compact)
+            });
+            Ok(cx.undefined().upcast())
+        }
+
+        method export(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let opts: ExportOptions = if let Some(opts) = cx.argument_opt(1) {
+                from_value(&mut cx, opts)?
+            } else {
+                ExportOptions::default()
+            };
+            let callback = progress_argument(&mut cx, 2)?;
+            let this = cx.this();
+            let storage = {
+                let guard = cx.lock();
+                let storage = this.borrow(&guard);
+                storage.clone()
+            };
+            let file = js_try!(cx, File::create(&path));
+            let mut reporter = ProgressReporter::new(callback);
+            let count = storage.export(BufWriter::new(file), &opts, |state| reporter.report(&mut cx, state));
+            reporter.result()?;
+            let count = js_try!(cx, count);
+            Ok(cx.number(count as f64).upcast())
+        }
+
+        method import(mut cx) {
+            let path = cx.argument::<JsString>(0)?.value();
+            let opts: ImportOptions = if let Some(opts) = cx.argument_opt(1) {
+                from_value(&mut cx, opts)?
+            } else {
+                ImportOptions::default()
+            };
+            let callback = progress_argument(&mut cx, 2)?;
+            let this = cx.this();
+            let storage = {
+                let guard = cx.lock();
+                let storage = this.borrow(&guard);
+                storage.clone()
+            };
+            let file = js_try!(cx, File::open(&path));
+            let mut reporter = ProgressReporter::new(callback);
+            let count = storage.import(BufReader::new(file), &opts, |state| reporter.report(&mut cx, state));
+            reporter.result()?;
+            let count = js_try!(cx, count);
+            Ok(cx.number(count as f64).upcast())
+        }
+
         method has_collection(mut cx) {
             let name = cx.argument::<JsString>(0)?.value();
             let this = cx.this();
@@ -52,7 +117,11 @@ declare_types! {
         method collection(mut cx) {
             let name = cx.argument::<JsString>(0)?;
             let this = cx.this();
-            Ok(JsCollection::new(&mut cx, vec![this.upcast::<JsValue>(), name.upcast::<JsValue>()])?.upcast())
+            let mut args = vec![this.upcast::<JsValue>(), name.upcast::<JsValue>()];
+            if let Some(primary) = cx.argument_opt(1) {
+                args.push(primary);
+            }
+            Ok(JsCollection::new(&mut cx, args)?.upcast())
         }
 
         method drop_collection(mut cx) {
@@ -77,3 +146,36 @@ declare_types! {
         }
     }
 }
+
+fn progress_argument<'a>(cx: &mut MethodContext<'a, JsStorage>, i: i32) -> NeonResult<Option<Handle<'a, JsFunction>>> {
+    if let Some(callback) = cx.argument_opt(i) {
+        Ok(Some(callback.downcast_or_throw::<JsFunction, _>(cx)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Calls progress callback until it throws
+struct ProgressReporter<'a> {
+    callback: Option<Handle<'a, JsFunction>>,
+    result: NeonResult<()>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    fn new(callback: Option<Handle<'a, JsFunction>>) -> Self {
+        Self { callback, result: Ok(()) }
+    }
+
+    fn report<C: Context<'a>>(&mut self, cx: &mut C, state: &Progress) {
+        if let (Some(callback), Ok(())) = (self.callback, &self.result) {
+            self.result = to_value(cx, state).and_then(|state| {
+                let this = cx.undefined();
+                callback.call(cx, this, vec![state]).map(|_| ())
+            });
+        }
+    }
+
+    fn result(self) -> NeonResult<()> {
+        self.result
+    }
+}
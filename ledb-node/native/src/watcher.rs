@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use neon::prelude::*;
+use neon_serde::to_value;
+use serde::Serialize;
+
+use ledb::{Change, ChangeKind, Primary, Result, Value, Watcher};
+
+use super::{refine};
+
+pub struct Changes(pub(crate) Option<Watcher>);
+
+#[derive(Serialize)]
+struct ChangeEvent {
+    kind: ChangeKind,
+    id: Primary,
+    old: Option<Value>,
+    new: Option<Value>,
+}
+
+impl ChangeEvent {
+    fn from_change(change: Change) -> Result<Self> {
+        Ok(ChangeEvent {
+            kind: change.kind,
+            old: change.old_doc()?.map(refine),
+            new: change.new_doc()?.map(refine),
+            id: change.id,
+        })
+    }
+}
+
+static INVALID_RANGE: &str = "Argument not in range 0..N";
+static INVALID_WATCHER: &str = "Invalid changes watcher";
+
+declare_types! {
+    /// A changes of collection
+    pub class JsWatcher for Changes {
+        init(_cx) {
+            Ok(Changes(None))
+        }
+
+        method next(mut cx) {
+            let timeout = if let Some(timeout) = cx.argument_opt(0) {
+                let timeout = timeout.downcast_or_throw::<JsNumber, _>(&mut cx)?.value();
+
+                if timeout < 0.0 || timeout > u32::MAX as f64 {
+                    return cx.throw_range_error(INVALID_RANGE);
+                }
+
+                Some(Duration::from_millis(timeout as u64))
+            } else {
+                None
+            };
+
+            let this = cx.this();
+
+            let change = js_try!(cx, {
+                let guard = cx.lock();
+                let this = this.borrow(&guard);
+
+                if let Some(watcher) = &this.0 {
+                    Ok(if let Some(timeout) = timeout {
+                        watcher.next_timeout(timeout)
+                    } else {
+                        watcher.try_next()
+                    })
+                } else {
+                    Err(INVALID_WATCHER)
+                }
+            });
+
+            let event = js_try!(cx, change.map(ChangeEvent::from_change).transpose());
+
+            Ok(js_try!(cx, to_value(&mut cx, &event)).upcast())
+        }
+
+        method close(mut cx) {
+            let mut this = cx.this();
+
+            {
+                let guard = cx.lock();
+                let mut this = this.borrow_mut(&guard);
+                this.0 = None;
+            }
+
+            Ok(cx.undefined().upcast())
+        }
+    }
+}
@@ -1,6 +1,6 @@
-import { deepStrictEqual as dse } from 'assert';
+import { deepStrictEqual as dse, throws } from 'assert';
 import { removeSync } from 'fs-extra';
-import { Storage, Collection, KeyFields } from '../';
+import { Storage, Collection, KeyFields, Progress } from '../';
 
 removeSync("test_db");
 
@@ -20,6 +20,27 @@ describe('storage', () => {
         dse(typeof stats, "object");
         dse(typeof stats.page_size, "number");
     });
+
+    it('backup', () => {
+        storage.collection("post").insert({ title: "Foo" });
+        storage.backup("test_db/storage.backup", true);
+
+        const backup = new Storage("test_db/storage.backup");
+
+        dse(backup.collection("post").get(1), { $: 1, title: "Foo" });
+    });
+
+    it('export import', () => {
+        const states: Progress[] = [];
+
+        dse(storage.export("test_db/storage.jsonl", { collections: ["post"] }, state => { states.push(state); }), 1);
+        dse(states, [{ collection: "post", documents: 1 }]);
+
+        const dest = new Storage("test_db/storage.import");
+
+        dse(dest.import("test_db/storage.jsonl", { mode: "upsert" }), 1);
+        dse(dest.collection("post").get(1), { $: 1, title: "Foo" });
+    });
 });
 
 describe('collection', () => {
@@ -167,4 +188,100 @@ describe('documents', () => {
         dse(coll.find(null).take(3).skip(1).count(), 2);
         dse(coll.find(null).take(2).skip(1).count(), 1);
     });
+
+    it('projection', () => {
+        const coll = storage.collection("post");
+
+        dse(coll.find({ title: { $eq: "Foo" } }, "$asc", { projection: { $include: ["title"] } }).next(), { $: 1, title: "Foo" });
+        dse(coll.find({ title: { $eq: "Foo" } }, "$asc", { projection: { $exclude: ["tag"] } }).next(), { $: 1, title: "Foo", timestamp: 1234567890 });
+    });
+
+    it('strings', () => {
+        const coll = storage.collection("post");
+
+        dse(coll.find({ title: { $prefix: "Ba" } }).count(), 2);
+        dse(coll.find({ title: { $regex: "^B.r$" } }).next(), { $: 2, title: "Bar", tag: ["Foo", "Baz"], timestamp: 1234567899 });
+        dse(coll.find({ title: { $ieq: "foo" } }).count(), 1);
+        dse(coll.find({ title: { $iprefix: "b" } }).count(), 2);
+        dse(coll.find({ title: { $match: "foo | act" } }).count(), 2);
+    });
+
+    it('aggregate', () => {
+        const coll = storage.collection("post");
+
+        dse(coll.aggregate(null, "$count"), 4);
+        dse(coll.aggregate({ tag: { $eq: "Foo" } }, "$count"), 3);
+        dse(coll.aggregate(null, { $distinct: "timestamp" }), [1234567819, 1234567890, 1234567899]);
+        dse(coll.aggregate(null, { $max: "timestamp" }), 1234567899);
+        dse(coll.aggregate({ tag: { $eq: "Bar" } }, { $min: "timestamp" }), 1234567819);
+    });
+});
+
+describe('spatial', () => {
+    const storage = new Storage("test_db/spatial");
+
+    it('near', () => {
+        const coll = storage.collection("device");
+
+        dse(coll.ensure_index("loc", "spatial", "float"), true);
+        dse(coll.insert({ loc: { lat: 55.7558, lon: 37.6173 } }), 1);
+        dse(coll.insert({ loc: { lat: 59.9343, lon: 30.3351 } }), 2);
+        dse(coll.insert({ loc: { type: "Point", coordinates: [-0.1278, 51.5074] } }), 3);
+
+        dse(coll.find({ loc: { $within: { sw: { lat: 50, lon: 25 }, ne: { lat: 60, lon: 40 } } } }).count(), 2);
+        dse(coll.find({ loc: { $near: [{ lat: 55.7558, lon: 37.6173 }, 100000] } }).count(), 1);
+        dse(coll.find(null, { loc: { $near: { lat: 51.5, lon: 0 } } }).next(), { $: 3, loc: { type: "Point", coordinates: [-0.1278, 51.5074] } });
+    });
+});
+
+describe('revision', () => {
+    const storage = new Storage("test_db/revision");
+
+    it('upsert', () => {
+        const coll = storage.collection("post");
+
+        dse(coll.upsert({ title: { $eq: "Foo" } }, { title: { $set: "Foo" } }, { title: "Bar" }), 1);
+        dse(coll.upsert({ title: { $eq: "Foo" } }, { title: { $set: "Baz" } }, { title: "Bar" }), null);
+        dse(coll.get(1), { $: 1, title: "Baz" });
+    });
+
+    it('put_if_revision', () => {
+        const coll = storage.collection("post");
+
+        dse(coll.revision(1), 2);
+        dse(coll.put_if_revision({ $: 1, title: "Foo" }, 2), 3);
+        dse(coll.revision(1), 3);
+        throws(() => coll.put_if_revision({ $: 1, title: "Bar" }, 2));
+        dse(coll.get(1), { $: 1, title: "Foo" });
+    });
+});
+
+describe('watcher', () => {
+    const storage = new Storage("test_db/watcher");
+
+    it('next', () => {
+        const coll = storage.collection("post");
+        const all = coll.watch();
+        const foo = coll.watch({ title: { $eq: "Foo" } });
+
+        dse(all.next(), null);
+
+        coll.insert({ title: "Foo" });
+        coll.insert({ title: "Bar" });
+        coll.put({ $: 1, title: "Baz" });
+        coll.delete(2);
+
+        dse(all.next(), { kind: "insert", id: 1, old: null, new: { $: 1, title: "Foo" } });
+        dse(all.next(), { kind: "insert", id: 2, old: null, new: { $: 2, title: "Bar" } });
+        dse(all.next(), { kind: "update", id: 1, old: { $: 1, title: "Foo" }, new: { $: 1, title: "Baz" } });
+        dse(all.next(), { kind: "delete", id: 2, old: { $: 2, title: "Bar" }, new: null });
+        dse(all.next(10), null);
+
+        dse(foo.next()!.kind, "insert");
+        dse(foo.next()!.kind, "update");
+        dse(foo.next(), null);
+
+        all.close();
+        foo.close();
+    });
 });
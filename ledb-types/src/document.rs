@@ -1,4 +1,4 @@
-use super::{Identifier, KeyFields};
+use super::{Identifier, KeyFields, PrimaryType};
 use std::{
     borrow::Cow,
     hash::BuildHasher,
@@ -7,9 +7,6 @@ use std::{
     sync::{Arc, Mutex, RwLock, Weak as ArcWeak},
 };
 
-/// Primary key (document identifier)
-pub type Primary = u32;
-
 /// Identified document representation
 pub trait Document {
     /// Get the name of primary field
@@ -17,6 +14,11 @@ pub trait Document {
         "$".into()
     }
 
+    /// Get the type of primary key
+    fn primary_type() -> PrimaryType {
+        PrimaryType::default()
+    }
+
     /// Get other key fields (indexes)
     fn key_fields() -> KeyFields {
         KeyFields::new()
@@ -28,6 +30,10 @@ impl<'a, T: Document> Document for &'a T {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -38,6 +44,10 @@ impl<'a, T: Document> Document for &'a mut T {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -48,6 +58,10 @@ impl<'a, T: Document> Document for &'a [T] {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -58,6 +72,10 @@ impl<'a, T: Document> Document for &'a mut [T] {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -68,6 +86,10 @@ impl<T: Document> Document for [T] {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -78,6 +100,10 @@ impl<T: Document> Document for Vec<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -88,6 +114,10 @@ impl<T: Document> Document for VecDeque<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -98,6 +128,10 @@ impl<T: Document, S: BuildHasher> Document for HashSet<T, S> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -108,6 +142,10 @@ impl<K, T: Document, S: BuildHasher> Document for HashMap<K, T, S> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields().with_parent("*")
     }
@@ -118,6 +156,10 @@ impl<T: Document> Document for BTreeSet<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -128,6 +170,10 @@ impl<K, T: Document> Document for BTreeMap<K, T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields().with_parent("*")
     }
@@ -138,6 +184,10 @@ impl<'a, T: Document> Document for Box<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -148,6 +198,10 @@ impl<'a, T: Document> Document for Rc<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -158,6 +212,10 @@ impl<'a, T: Document> Document for RcWeak<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -168,6 +226,10 @@ impl<'a, T: Document> Document for Arc<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -178,6 +240,10 @@ impl<'a, T: Document> Document for ArcWeak<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -188,6 +254,10 @@ impl<'a, T: Document> Document for Mutex<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -198,6 +268,10 @@ impl<'a, T: Document> Document for RwLock<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -208,6 +282,10 @@ impl<'a, T: Document + Clone> Document for Cow<'a, T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
@@ -218,6 +296,10 @@ impl<T: Document> Document for Option<T> {
         T::primary_field()
     }
 
+    fn primary_type() -> PrimaryType {
+        T::primary_type()
+    }
+
     fn key_fields() -> KeyFields {
         T::key_fields()
     }
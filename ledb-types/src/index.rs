@@ -22,6 +22,16 @@ pub struct KeyField {
     pub key: KeyType,
     #[serde(default)]
     pub kind: IndexKind,
+    /// The key types of the rest components of compound key
+    ///
+    /// The compound key field has comma-separated paths of components.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compound: Vec<KeyType>,
+    /// The state of building when the index isn't ready yet
+    ///
+    /// This is filled by storage only and ignored when creating index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<IndexBuild>,
 }
 
 impl KeyField {
@@ -32,6 +42,8 @@ impl KeyField {
             path: path.to_string(),
             key: KeyType::default(),
             kind: IndexKind::default(),
+            compound: Vec::new(),
+            build: None,
         }
     }
 
@@ -49,13 +61,48 @@ impl KeyField {
         self
     }
 
+    /// Add component to compound key
+    ///
+    /// This makes key field to be compound
+    #[inline]
+    pub fn with_component<S: AsRef<str>>(mut self, path: S, key: KeyType) -> Self {
+        self.path.push(',');
+        self.path.push_str(path.as_ref());
+        self.compound.push(key);
+        self
+    }
+
+    /// Key field is compound
+    #[inline]
+    pub fn is_compound(&self) -> bool {
+        !self.compound.is_empty()
+    }
+
+    /// Index is ready to use
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.build.is_none()
+    }
+
+    /// Get the key types of all components
+    pub fn key_types(&self) -> Vec<KeyType> {
+        let mut keys = vec![self.key];
+        keys.extend(&self.compound);
+        keys
+    }
+
     /// Set parent path
     ///
     /// This makes key field to be child for parent path
     #[inline]
     pub fn set_parent<S: AsRef<str>>(&mut self, parent: S) {
-        self.path.insert_str(0, ".");
-        self.path.insert_str(0, parent.as_ref());
+        let parent = parent.as_ref();
+        self.path = self
+            .path
+            .split(',')
+            .map(|path| format!("{}.{}", parent, path))
+            .collect::<Vec<_>>()
+            .join(",");
     }
 
     /// Add parent path
@@ -110,6 +157,8 @@ impl<S: ToString> From<(S, KeyType, IndexKind)> for KeyField {
             path: path.to_string(),
             key,
             kind,
+            compound: Vec::new(),
+            build: None,
         }
     }
 }
@@ -120,6 +169,8 @@ impl<'a, S: ToString> From<&'a (S, KeyType, IndexKind)> for KeyField {
             path: path.to_string(),
             key: *key,
             kind: *kind,
+            compound: Vec::new(),
+            build: None,
         }
     }
 }
@@ -130,6 +181,8 @@ impl<S: ToString> From<(S, IndexKind, KeyType)> for KeyField {
             path: path.to_string(),
             key,
             kind,
+            compound: Vec::new(),
+            build: None,
         }
     }
 }
@@ -140,24 +193,38 @@ impl<'a, S: ToString> From<&'a (S, IndexKind, KeyType)> for KeyField {
             path: path.to_string(),
             key: *key,
             kind: *kind,
+            compound: Vec::new(),
+            build: None,
         }
     }
 }
 
 impl Into<(String, KeyType, IndexKind)> for KeyField {
     fn into(self) -> (String, KeyType, IndexKind) {
-        let KeyField { path, key, kind } = self;
+        let KeyField { path, key, kind, .. } = self;
         (path, key, kind)
     }
 }
 
 impl Into<(String, IndexKind, KeyType)> for KeyField {
     fn into(self) -> (String, IndexKind, KeyType) {
-        let KeyField { path, key, kind } = self;
+        let KeyField { path, key, kind, .. } = self;
         (path, kind, key)
     }
 }
 
+/// The state of index which is building in background
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexBuild {
+    /// The number of indexed documents
+    pub done: usize,
+    /// The total number of documents when building started
+    pub total: usize,
+    /// The error which stopped building
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 /// Indexed fields definition
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct KeyFields(Vec<KeyField>);
@@ -287,6 +354,9 @@ pub enum KeyType {
     Float,
     #[serde(rename = "string")]
     String,
+    /// Case-insensitive string (stored in lower case)
+    #[serde(rename = "folded")]
+    Folded,
     #[serde(rename = "binary")]
     Binary,
     #[serde(rename = "bool")]
@@ -308,6 +378,19 @@ pub enum IndexKind {
     /// Index which contains unique keys only
     #[serde(rename = "unique")]
     Unique,
+    /// Index of words for full-text search
+    ///
+    /// The string values are split to lower case words.
+    ///
+    #[serde(rename = "fulltext")]
+    FullText,
+    /// Index of geographic points
+    ///
+    /// The field is a point like `{"lat": 55.7, "lon": 37.6}` or GeoJSON point,
+    /// or a pair of latitude and longitude fields.
+    ///
+    #[serde(rename = "spatial")]
+    Spatial,
 }
 
 impl Default for IndexKind {
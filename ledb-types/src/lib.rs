@@ -76,7 +76,9 @@ This trait maps rust types to key types.
 mod document;
 mod identifier;
 mod index;
+mod primary;
 
 pub use self::document::*;
 pub use self::identifier::*;
 pub use self::index::*;
+pub use self::primary::*;
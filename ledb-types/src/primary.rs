@@ -0,0 +1,233 @@
+use serde::{
+    de::{Error as DeError, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Primary key (document identifier)
+///
+/// The integer identifiers are used by default.
+/// The collection may be created with string or binary (like UUID) identifiers instead.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Primary {
+    /// Integer identifier
+    Int(u64),
+    /// String identifier
+    String(String),
+    /// Binary identifier
+    Binary(Vec<u8>),
+}
+
+/// The type of primary key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum PrimaryType {
+    /// Unsigned 32-bit integer (default)
+    #[default]
+    #[serde(rename = "int")]
+    Int,
+    /// Unsigned 64-bit integer
+    #[serde(rename = "bigint")]
+    BigInt,
+    /// UTF-8 string
+    #[serde(rename = "string")]
+    String,
+    /// Binary data (like UUID)
+    #[serde(rename = "binary")]
+    Binary,
+}
+
+impl PrimaryType {
+    /// The type is default
+    pub fn is_default(&self) -> bool {
+        *self == PrimaryType::Int
+    }
+
+    /// The identifiers of that type can be generated automatically
+    pub fn is_integer(&self) -> bool {
+        matches!(self, PrimaryType::Int | PrimaryType::BigInt)
+    }
+}
+
+impl Display for PrimaryType {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str(match self {
+            PrimaryType::Int => "int",
+            PrimaryType::BigInt => "bigint",
+            PrimaryType::String => "string",
+            PrimaryType::Binary => "binary",
+        })
+    }
+}
+
+impl Primary {
+    /// Get the integer identifier
+    pub fn as_int(&self) -> Option<u64> {
+        if let Primary::Int(id) = self {
+            Some(*id)
+        } else {
+            None
+        }
+    }
+
+    /// Convert identifier into specified type
+    ///
+    /// The strings which comes from paths or queries will be parsed as integers
+    /// or hex-encoded binaries when it needed.
+    ///
+    pub fn to_type(&self, typ: PrimaryType) -> Option<Primary> {
+        use self::Primary::*;
+        Some(match (typ, self) {
+            (PrimaryType::Int, Int(id)) if *id > u64::from(u32::MAX) => return None,
+            (PrimaryType::Int, Int(..)) | (PrimaryType::BigInt, Int(..)) => self.clone(),
+            (PrimaryType::Int, String(id)) | (PrimaryType::BigInt, String(id)) => {
+                return id.parse().ok().and_then(|id| Int(id).to_type(typ));
+            }
+            (PrimaryType::String, String(..)) | (PrimaryType::Binary, Binary(..)) => self.clone(),
+            (PrimaryType::String, Int(id)) => String(id.to_string()),
+            (PrimaryType::Binary, String(id)) => Binary(from_hex(id)?),
+            _ => return None,
+        })
+    }
+}
+
+impl Display for Primary {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Primary::Int(id) => id.fmt(f),
+            Primary::String(id) => id.fmt(f),
+            Primary::Binary(id) => {
+                for byte in id {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for Primary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Primary::Int(id) => serializer.serialize_u64(*id),
+            Primary::String(id) => serializer.serialize_str(id),
+            Primary::Binary(id) => serializer.serialize_bytes(id),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Primary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PrimaryVisitor)
+    }
+}
+
+struct PrimaryVisitor;
+
+impl<'de> Visitor<'de> for PrimaryVisitor {
+    type Value = Primary;
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str("unsigned integer, string or binary identifier")
+    }
+
+    fn visit_u64<E: DeError>(self, id: u64) -> Result<Primary, E> {
+        Ok(Primary::Int(id))
+    }
+
+    fn visit_i64<E: DeError>(self, id: i64) -> Result<Primary, E> {
+        if id < 0 {
+            Err(E::custom("Negative identifier"))
+        } else {
+            Ok(Primary::Int(id as u64))
+        }
+    }
+
+    fn visit_str<E: DeError>(self, id: &str) -> Result<Primary, E> {
+        Ok(Primary::String(id.into()))
+    }
+
+    fn visit_string<E: DeError>(self, id: String) -> Result<Primary, E> {
+        Ok(Primary::String(id))
+    }
+
+    fn visit_bytes<E: DeError>(self, id: &[u8]) -> Result<Primary, E> {
+        Ok(Primary::Binary(id.into()))
+    }
+
+    fn visit_byte_buf<E: DeError>(self, id: Vec<u8>) -> Result<Primary, E> {
+        Ok(Primary::Binary(id))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Primary, A::Error> {
+        let mut id = Vec::new();
+        while let Some(byte) = seq.next_element()? {
+            id.push(byte);
+        }
+        Ok(Primary::Binary(id))
+    }
+}
+
+fn from_hex(src: &str) -> Option<Vec<u8>> {
+    // allow UUID-like dashes
+    let src = src.chars().filter(|c| *c != '-').collect::<String>();
+    if src.len() % 2 != 0 || !src.is_ascii() {
+        return None;
+    }
+    (0..src.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&src[i..i + 2], 16).ok())
+        .collect()
+}
+
+impl From<u64> for Primary {
+    fn from(id: u64) -> Self {
+        Primary::Int(id)
+    }
+}
+
+impl From<String> for Primary {
+    fn from(id: String) -> Self {
+        Primary::String(id)
+    }
+}
+
+impl<'a> From<&'a str> for Primary {
+    fn from(id: &'a str) -> Self {
+        Primary::String(id.into())
+    }
+}
+
+impl From<Vec<u8>> for Primary {
+    fn from(id: Vec<u8>) -> Self {
+        Primary::Binary(id)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Primary {
+    fn from(id: &'a [u8]) -> Self {
+        Primary::Binary(id.into())
+    }
+}
+
+impl<'a> From<&'a Primary> for Primary {
+    fn from(id: &'a Primary) -> Self {
+        id.clone()
+    }
+}
+
+impl PartialEq<u64> for Primary {
+    fn eq(&self, other: &u64) -> bool {
+        self.as_int() == Some(*other)
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Primary {
+    fn eq(&self, other: &&'a str) -> bool {
+        if let Primary::String(id) = self {
+            id == other
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use lmdb::ConstTransaction;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    extract_field_values, Collection, Filter, Index, KeyData, KeyType, OrderKind, Primary,
+    RawDocument, Result, Selection,
+};
+
+/// Accumulator of grouped values
+///
+/// The numeric accumulators ignores non-numeric values.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Accumulator {
+    /// The number of documents in group
+    #[serde(rename = "$count")]
+    Count,
+    /// The sum of field values
+    #[serde(rename = "$sum")]
+    Sum(String),
+    /// The average of field values
+    #[serde(rename = "$avg")]
+    Avg(String),
+    /// The minimum of field values
+    #[serde(rename = "$min")]
+    Min(String),
+    /// The maximum of field values
+    #[serde(rename = "$max")]
+    Max(String),
+}
+
+/// Aggregation operator
+///
+/// The aggregation is applied to the documents which passes the filter.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggregation {
+    /// The number of documents
+    #[serde(rename = "$count")]
+    Count,
+    /// The unique values of field
+    ///
+    /// The values are served from index when the field values have the type of index keys.
+    ///
+    #[serde(rename = "$distinct")]
+    Distinct(String),
+    /// The sum of field values
+    #[serde(rename = "$sum")]
+    Sum(String),
+    /// The average of field values
+    #[serde(rename = "$avg")]
+    Avg(String),
+    /// The minimum of field values
+    ///
+    /// The value is served from index when the field values have the type of index keys.
+    ///
+    #[serde(rename = "$min")]
+    Min(String),
+    /// The maximum of field values
+    ///
+    /// The value is served from index when the field values have the type of index keys.
+    ///
+    #[serde(rename = "$max")]
+    Max(String),
+    /// Group documents by field values and accumulate values in each group
+    ///
+    /// The document with several values of field belongs to several groups.
+    /// The documents without values of field are skipped.
+    ///
+    #[serde(rename = "$group")]
+    Group {
+        by: String,
+        #[serde(default)]
+        fields: BTreeMap<String, Accumulator>,
+    },
+}
+
+impl Aggregation {
+    pub fn distinct<F: Into<String>>(field: F) -> Self {
+        Aggregation::Distinct(field.into())
+    }
+
+    pub fn sum<F: Into<String>>(field: F) -> Self {
+        Aggregation::Sum(field.into())
+    }
+
+    pub fn avg<F: Into<String>>(field: F) -> Self {
+        Aggregation::Avg(field.into())
+    }
+
+    pub fn min<F: Into<String>>(field: F) -> Self {
+        Aggregation::Min(field.into())
+    }
+
+    pub fn max<F: Into<String>>(field: F) -> Self {
+        Aggregation::Max(field.into())
+    }
+
+    pub fn group<F: Into<String>>(by: F) -> Self {
+        Aggregation::Group {
+            by: by.into(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Add accumulator to group
+    ///
+    /// *Note*: It does nothing with other operators.
+    ///
+    pub fn with<N: Into<String>>(mut self, name: N, acc: Accumulator) -> Self {
+        if let Aggregation::Group { fields, .. } = &mut self {
+            fields.insert(name.into(), acc);
+        }
+        self
+    }
+}
+
+/// The group of documents with accumulated values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    /// The value of field which documents grouped by
+    pub key: KeyData,
+    /// The accumulated values
+    ///
+    /// The value is missing when there is nothing to accumulate.
+    ///
+    pub values: BTreeMap<String, Option<KeyData>>,
+}
+
+/// The result of aggregation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Aggregated {
+    /// The number of documents
+    Count(usize),
+    /// The single value like sum, average, minimum or maximum
+    Value(Option<KeyData>),
+    /// The unique values
+    Values(Vec<KeyData>),
+    /// The groups of documents
+    Groups(Vec<Group>),
+}
+
+/// The sum of numeric values
+///
+/// The sum of integers is integer unless it overflows.
+///
+#[derive(Default)]
+struct Numbers {
+    int: i64,
+    float: f64,
+    is_float: bool,
+    count: usize,
+}
+
+impl Numbers {
+    fn add(&mut self, val: &KeyData) {
+        match val {
+            KeyData::Int(val) => {
+                if let Some(sum) = self.int.checked_add(*val) {
+                    self.int = sum;
+                } else {
+                    self.float += *val as f64;
+                    self.is_float = true;
+                }
+            }
+            KeyData::Float(OrderedFloat(val)) => {
+                self.float += val;
+                self.is_float = true;
+            }
+            _ => return,
+        }
+        self.count += 1;
+    }
+
+    fn sum(&self) -> KeyData {
+        if self.is_float {
+            KeyData::Float(OrderedFloat(self.int as f64 + self.float))
+        } else {
+            KeyData::Int(self.int)
+        }
+    }
+
+    fn avg(&self) -> Option<KeyData> {
+        if self.count > 0 {
+            Some(KeyData::Float(OrderedFloat(
+                (self.int as f64 + self.float) / self.count as f64,
+            )))
+        } else {
+            None
+        }
+    }
+}
+
+/// The state of accumulator
+enum State {
+    Count(usize),
+    Sum(Numbers),
+    Avg(Numbers),
+    Min(Option<KeyData>),
+    Max(Option<KeyData>),
+}
+
+impl State {
+    fn new(acc: &Accumulator) -> Self {
+        use self::Accumulator::*;
+        match acc {
+            Count => State::Count(0),
+            Sum(_) => State::Sum(Numbers::default()),
+            Avg(_) => State::Avg(Numbers::default()),
+            Min(_) => State::Min(None),
+            Max(_) => State::Max(None),
+        }
+    }
+
+    fn add(&mut self, acc: &Accumulator, doc: &RawDocument) {
+        let path = match acc {
+            Accumulator::Count => {
+                if let State::Count(count) = self {
+                    *count += 1;
+                }
+                return;
+            }
+            Accumulator::Sum(path)
+            | Accumulator::Avg(path)
+            | Accumulator::Min(path)
+            | Accumulator::Max(path) => path,
+        };
+
+        let mut vals = Vec::new();
+        extract_field_values(doc, None, &path.split('.'), &mut vals);
+
+        for val in vals {
+            match self {
+                State::Sum(nums) | State::Avg(nums) => nums.add(&val),
+                State::Min(min) => {
+                    if min.as_ref().map(|min| val < *min).unwrap_or(true) {
+                        *min = Some(val);
+                    }
+                }
+                State::Max(max) => {
+                    if max.as_ref().map(|max| val > *max).unwrap_or(true) {
+                        *max = Some(val);
+                    }
+                }
+                State::Count(_) => (),
+            }
+        }
+    }
+
+    fn result(self) -> Option<KeyData> {
+        match self {
+            State::Count(count) => Some(KeyData::Int(count as i64)),
+            State::Sum(nums) => Some(nums.sum()),
+            State::Avg(nums) => nums.avg(),
+            State::Min(val) | State::Max(val) => val,
+        }
+    }
+}
+
+impl Collection {
+    /// Aggregate documents using optional filter
+    ///
+    /// When none filter specified then all documents will be aggregated.
+    ///
+    pub fn aggregate(
+        &self,
+        filter: Option<Filter>,
+        aggregation: &Aggregation,
+    ) -> Result<Aggregated> {
+        let txn = self.read_txn()?;
+        self.aggregate_txn(&txn, filter, aggregation)
+    }
+
+    /// Count documents using optional filter
+    pub fn count(&self, filter: Option<Filter>) -> Result<usize> {
+        match self.aggregate(filter, &Aggregation::Count)? {
+            Aggregated::Count(count) => Ok(count),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Get unique values of field of documents using optional filter
+    pub fn distinct<F: Into<String>>(
+        &self,
+        filter: Option<Filter>,
+        field: F,
+    ) -> Result<Vec<KeyData>> {
+        match self.aggregate(filter, &Aggregation::distinct(field))? {
+            Aggregated::Values(vals) => Ok(vals),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Get minimum value of field of documents using optional filter
+    pub fn min<F: Into<String>>(
+        &self,
+        filter: Option<Filter>,
+        field: F,
+    ) -> Result<Option<KeyData>> {
+        match self.aggregate(filter, &Aggregation::min(field))? {
+            Aggregated::Value(val) => Ok(val),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Get maximum value of field of documents using optional filter
+    pub fn max<F: Into<String>>(
+        &self,
+        filter: Option<Filter>,
+        field: F,
+    ) -> Result<Option<KeyData>> {
+        match self.aggregate(filter, &Aggregation::max(field))? {
+            Aggregated::Value(val) => Ok(val),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Group documents using optional filter by values of field and accumulate values in each group
+    pub fn group_by<F: Into<String>>(
+        &self,
+        filter: Option<Filter>,
+        by: F,
+        fields: BTreeMap<String, Accumulator>,
+    ) -> Result<Vec<Group>> {
+        let aggregation = Aggregation::Group {
+            by: by.into(),
+            fields,
+        };
+        match self.aggregate(filter, &aggregation)? {
+            Aggregated::Groups(groups) => Ok(groups),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn aggregate_txn(
+        &self,
+        txn: &ConstTransaction,
+        filter: Option<Filter>,
+        aggregation: &Aggregation,
+    ) -> Result<Aggregated> {
+        let sel = self
+            .live_filter(filter)?
+            .map(|filter| filter.apply(txn, self))
+            .transpose()?;
+        let sel = sel.as_ref();
+
+        Ok(match aggregation {
+            Aggregation::Count => Aggregated::Count(match sel {
+                None => self.count_all_txn(txn)?,
+                Some(Selection { ids, inv: false }) => ids.len(),
+                // the excluded documents is a subset of all documents
+                Some(Selection { ids, inv: true }) => self.count_all_txn(txn)? - ids.len(),
+            }),
+            Aggregation::Distinct(path) => Aggregated::Values(self.distinct_txn(txn, sel, path)?),
+            Aggregation::Min(path) => {
+                Aggregated::Value(self.extremum_txn(txn, sel, path, OrderKind::Asc)?)
+            }
+            Aggregation::Max(path) => {
+                Aggregated::Value(self.extremum_txn(txn, sel, path, OrderKind::Desc)?)
+            }
+            Aggregation::Sum(path) | Aggregation::Avg(path) => {
+                let mut nums = Numbers::default();
+                self.visit_txn(txn, sel, |doc| {
+                    let mut vals = Vec::new();
+                    extract_field_values(&doc, None, &path.split('.'), &mut vals);
+                    vals.iter().for_each(|val| nums.add(val));
+                    Ok(())
+                })?;
+                Aggregated::Value(if let Aggregation::Sum(_) = aggregation {
+                    Some(nums.sum())
+                } else {
+                    nums.avg()
+                })
+            }
+            Aggregation::Group { by, fields } => {
+                let mut groups: BTreeMap<KeyData, Vec<State>> = BTreeMap::new();
+                self.visit_txn(txn, sel, |doc| {
+                    let mut keys = HashSet::new();
+                    extract_field_values(&doc, None, &by.split('.'), &mut keys);
+                    for key in keys {
+                        let states = groups
+                            .entry(key)
+                            .or_insert_with(|| fields.values().map(State::new).collect());
+                        for (state, acc) in states.iter_mut().zip(fields.values()) {
+                            state.add(acc, &doc);
+                        }
+                    }
+                    Ok(())
+                })?;
+                Aggregated::Groups(
+                    groups
+                        .into_iter()
+                        .map(|(key, states)| Group {
+                            key,
+                            values: fields
+                                .keys()
+                                .cloned()
+                                .zip(states.into_iter().map(State::result))
+                                .collect(),
+                        })
+                        .collect(),
+                )
+            }
+        })
+    }
+
+    fn distinct_txn(
+        &self,
+        txn: &ConstTransaction,
+        sel: Option<&Selection>,
+        path: &str,
+    ) -> Result<Vec<KeyData>> {
+        if let Some(index) = self.get_index(path)?.filter(Index::is_plain) {
+            let mut vals = Vec::new();
+            let mut ids = Vec::new();
+            index.visit_keys(txn, OrderKind::Asc, false, |key, id| {
+                if sel.map(|sel| sel.has(&id)).unwrap_or(true) {
+                    if vals.last() != Some(&key) {
+                        vals.push(key);
+                    }
+                    ids.push(id);
+                }
+                Ok(true)
+            })?;
+            if self.has_values_of_txn(txn, &ids, path, index.key())? {
+                return Ok(vals);
+            }
+        }
+
+        let mut vals = BTreeSet::new();
+        self.visit_txn(txn, sel, |doc| {
+            extract_field_values(&doc, None, &path.split('.'), &mut vals);
+            Ok(())
+        })?;
+        Ok(vals.into_iter().collect())
+    }
+
+    fn extremum_txn(
+        &self,
+        txn: &ConstTransaction,
+        sel: Option<&Selection>,
+        path: &str,
+        order: OrderKind,
+    ) -> Result<Option<KeyData>> {
+        if let Some(index) = self.get_index(path)?.filter(Index::is_plain) {
+            let mut val = None;
+            let mut ids = Vec::new();
+            // the first key of selected documents in order is an extremum
+            index.visit_keys(txn, order, false, |key, id| {
+                if val.as_ref().map(|val| val != &key).unwrap_or(false) {
+                    return Ok(false);
+                }
+                if sel.map(|sel| sel.has(&id)).unwrap_or(true) {
+                    val = Some(key);
+                    ids.push(id);
+                }
+                Ok(true)
+            })?;
+            if self.has_values_of_txn(txn, &ids, path, index.key())? {
+                return Ok(val);
+            }
+        }
+
+        let acc = if order == OrderKind::Asc {
+            Accumulator::Min(path.into())
+        } else {
+            Accumulator::Max(path.into())
+        };
+        let mut state = State::new(&acc);
+        self.visit_txn(txn, sel, |doc| {
+            state.add(&acc, &doc);
+            Ok(())
+        })?;
+        Ok(state.result())
+    }
+
+    /// Check that the field values of documents has the type of index keys
+    ///
+    /// The values of other types is converted when indexing,
+    /// so the keys cannot be used as values.
+    ///
+    fn has_values_of_txn(
+        &self,
+        txn: &ConstTransaction,
+        ids: &[Primary],
+        path: &str,
+        typ: KeyType,
+    ) -> Result<bool> {
+        // the folded keys is lowercased
+        if typ == KeyType::Folded {
+            return Ok(false);
+        }
+        for id in ids {
+            let mut vals = Vec::new();
+            if let Some(doc) = self.get_txn(txn, id.clone())? {
+                extract_field_values(&doc, None, &path.split('.'), &mut vals);
+            }
+            if vals.iter().any(|val| val.as_type(typ).is_none()) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,145 @@
+use std::thread::spawn;
+
+use lmdb::{LmdbResultExt, WriteTransaction};
+use serde::{Deserialize, Serialize};
+
+use super::{Collection, Index, OrderKind, PrimaryIterator, RawDocument, Result, ResultWrap};
+
+/// The default number of documents which indexed in single write transaction
+const DEFAULT_BATCH: usize = 1000;
+
+/// The options of index creation
+///
+/// By default the index is fulfilled synchronously using single write transaction.
+///
+/// The background index is fulfilled by separate thread in batches,
+/// so the writers is blocked during the batch only.
+/// The building index is not used by queries until it is ready,
+/// the progress of building is reported by `Collection::get_indexes()`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct IndexOptions {
+    /// Build the index in background
+    #[serde(default)]
+    pub background: bool,
+    /// The number of documents which indexed in single write transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch: Option<usize>,
+}
+
+impl IndexOptions {
+    /// Build the index in background using batches of default size
+    pub fn background() -> Self {
+        Self {
+            background: true,
+            batch: None,
+        }
+    }
+
+    /// Set the number of documents in batch
+    pub fn with_batch(mut self, batch: usize) -> Self {
+        self.batch = Some(batch);
+        self
+    }
+
+    pub(crate) fn batch_size(&self) -> usize {
+        self.batch
+            .filter(|batch| *batch > 0)
+            .unwrap_or(DEFAULT_BATCH)
+    }
+}
+
+impl Collection {
+    /// Index the next batch of documents
+    ///
+    /// Returns `true` when the building is finished or cancelled.
+    ///
+    pub(crate) fn build_batch(&self, index: &Index, batch: usize) -> Result<bool> {
+        let res = self.storage().write(|txn| {
+            // the progress is locked until the batch is committed
+            // so the writers which goes next will see it consistent
+            let progress = index.lock_build();
+
+            if index.is_deleted() {
+                // the building was cancelled
+                return Ok(None);
+            }
+
+            let ids = PrimaryIterator::new(txn, self.clone(), OrderKind::Asc, progress.last())?
+                .take(batch)
+                .collect::<Result<Vec<_>>>()?;
+
+            {
+                let mut access = txn.access();
+
+                for id in &ids {
+                    let doc = RawDocument::from_bin(
+                        access
+                            .get::<[u8], [u8]>(self, self.id_to_raw(id)?.as_slice())
+                            .wrap_err()?,
+                    )?
+                    .with_id(id.clone());
+
+                    index.update_index(&mut access, None, Some(&doc))?;
+                }
+            }
+
+            let done = ids.len() < batch;
+
+            if done {
+                self.finish_build_txn(txn, index)?;
+            }
+
+            Ok(Some((progress, ids.last().cloned(), ids.len(), done)))
+        })?;
+
+        Ok(match res {
+            // the progress is changed only when the batch is committed successfully
+            Some((progress, last, count, done)) => {
+                progress.advance(last, count, done);
+                done
+            }
+            None => true,
+        })
+    }
+
+    /// Remove the marker of building index
+    pub(crate) fn finish_build_txn(&self, txn: &WriteTransaction, index: &Index) -> Result<()> {
+        let mut access = txn.access();
+
+        access
+            .del_key(self.storage().main_db(), index.build_key())
+            .to_opt()
+            .wrap_err()?;
+
+        Ok(())
+    }
+}
+
+/// The background thread which fulfills index
+pub(crate) struct IndexBuilder;
+
+impl IndexBuilder {
+    /// Start building the index
+    ///
+    /// The thread stops when the index is ready, building failed or the index was removed.
+    ///
+    pub(crate) fn start(collection: Collection, index: Index, batch: usize) {
+        spawn(move || loop {
+            match collection.build_batch(&index, batch) {
+                Ok(false) => (),
+                Ok(true) => break,
+                Err(e) => {
+                    eprintln!(
+                        "Error when building index '{}' of collection '{}': {}",
+                        index.path(),
+                        collection.name(),
+                        e
+                    );
+                    index.fail_build(e.to_string());
+                    break;
+                }
+            }
+        });
+    }
+}
@@ -10,16 +10,20 @@ use std::{
 };
 
 use lmdb::{
-    put::Flags as PutFlags, traits::CreateCursor, Cursor, CursorIter, Database, DatabaseOptions,
-    LmdbResultExt, MaybeOwned, ReadTransaction, Unaligned, WriteTransaction,
+    put::{Flags as PutFlags, NOOVERWRITE},
+    ConstTransaction, Cursor, CursorIter, Database, LmdbResultExt, MaybeOwned, ReadTransaction,
+    WriteTransaction,
 };
-use ron::ser::to_string as to_db_name;
+use ron::{de::from_str as from_ron, ser::to_string as to_db_name};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use supercow::{ext::ConstDeref, Supercow};
 
 use super::{
-    DatabaseDef, Document, Enumerable, Filter, Index, IndexDef, IndexKind, KeyField, KeyFields,
-    KeyType, Modify, Order, OrderKind, Primary, RawDocument, Result, ResultWrap, Serial, Storage,
+    primary_db_options, primary_from_raw, primary_to_raw, project_doc, to_primary, Change,
+    Conflict, DatabaseDef, Document, Enumerable, Error, Expiry, Filter, FindOptions, Index,
+    IndexBuilder, IndexDef, IndexIterator, IndexKind, IndexOptions, KeyField, KeyFields, KeyType,
+    Modify, Order, OrderKind, Primary, PrimaryType, Projection, Projector, RawDocument, Result,
+    ResultWrap, Revision, Schema, Selection, Serial, SharedTxn, Storage, Watcher, Watchers,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -28,11 +32,14 @@ pub(crate) struct CollectionDef(
     pub Serial,
     /// Collection name
     pub String,
+    /// The type of primary key
+    #[serde(default, skip_serializing_if = "PrimaryType::is_default")]
+    pub PrimaryType,
 );
 
 impl CollectionDef {
-    pub fn new<S: AsRef<str>>(name: S) -> Self {
-        CollectionDef(0, name.as_ref().into())
+    pub fn new<S: AsRef<str>>(name: S, primary: PrimaryType) -> Self {
+        CollectionDef(0, name.as_ref().into(), primary)
     }
 }
 
@@ -42,11 +49,90 @@ impl Enumerable for CollectionDef {
     }
 }
 
+/// Which documents should be returned from update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Returning {
+    /// The documents before modification
+    #[serde(rename = "before")]
+    Before,
+    /// The documents after modification
+    #[serde(rename = "after")]
+    After,
+    /// The documents before and after modification
+    #[serde(rename = "both")]
+    Both,
+}
+
+impl Returning {
+    fn before(self) -> bool {
+        self != Returning::After
+    }
+
+    fn after(self) -> bool {
+        self != Returning::Before
+    }
+}
+
+/// The document which affected by update
+///
+/// The old and new documents are present according to `Returning` option.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Affected<T> {
+    /// The primary key/identifier of document
+    pub id: Primary,
+    /// The document before modification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<T>,
+    /// The document after modification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<T>,
+}
+
+impl Affected<RawDocument> {
+    fn new(old: &RawDocument, new: &RawDocument, returning: Returning) -> Result<Self> {
+        Ok(Affected {
+            id: new.req_id()?,
+            old: if returning.before() {
+                Some(old.clone())
+            } else {
+                None
+            },
+            new: if returning.after() {
+                Some(new.clone())
+            } else {
+                None
+            },
+        })
+    }
+
+    pub(crate) fn into_doc<T: DeserializeOwned + Document>(self) -> Result<Affected<T>> {
+        Ok(Affected {
+            id: self.id,
+            old: self.old.map(RawDocument::into_doc).transpose()?,
+            new: self.new.map(RawDocument::into_doc).transpose()?,
+        })
+    }
+}
+
 struct CollectionData {
     name: String,
+    primary: PrimaryType,
     indexes: RwLock<Vec<Index>>,
     storage: Storage,
     db: Database<'static>,
+    // Key of the id sequence in the main database
+    seq_key: String,
+    // Key of the revision sequence in the main database
+    rev_key: String,
+    // Key of the expiration in the main database
+    expiry_key: String,
+    expiry: RwLock<Option<Expiry>>,
+    // Key of the schema in the main database
+    schema_key: String,
+    schema: RwLock<Option<Schema>>,
+    // Subscribers of changes
+    watchers: Watchers,
     // Remove marker
     delete: AtomicBool,
 }
@@ -62,25 +148,40 @@ impl Collection {
         index_defs: Vec<IndexDef>,
     ) -> Result<Self> {
         let db_name = to_db_name(&DatabaseDef::Collection(def.clone())).wrap_err()?;
+        let seq_key = to_db_name(&DatabaseDef::Sequence(def.clone())).wrap_err()?;
+        let rev_key = to_db_name(&DatabaseDef::Revision(def.clone())).wrap_err()?;
+        let expiry_key = to_db_name(&DatabaseDef::Expiry(def.clone())).wrap_err()?;
+        let schema_key = to_db_name(&DatabaseDef::Schema(def.clone())).wrap_err()?;
 
-        let CollectionDef(_serial, name) = def;
+        let CollectionDef(_serial, name, primary) = def;
 
-        let db_opts = DatabaseOptions::create_map::<Unaligned<Primary>>();
+        let db_opts = primary_db_options(primary);
 
-        let db = Database::open(storage.clone(), Some(&db_name), &db_opts).wrap_err()?;
+        let db = Database::open(storage.env(), Some(&db_name), &db_opts).wrap_err()?;
 
         let indexes = RwLock::new(
             index_defs
                 .into_iter()
-                .map(|def| Index::new(storage.clone(), def))
+                .map(|def| Index::new(storage.clone(), def, primary))
                 .collect::<Result<Vec<_>>>()?,
         );
 
+        let expiry = RwLock::new(load_expiry(&storage, &expiry_key)?);
+        let schema = RwLock::new(load_schema(&storage, &schema_key)?);
+
         Ok(Collection(Some(Arc::new(CollectionData {
             name,
+            primary,
             indexes,
             storage,
             db,
+            seq_key,
+            rev_key,
+            expiry_key,
+            expiry,
+            schema_key,
+            schema,
+            watchers: Watchers::default(),
             delete: AtomicBool::new(false),
         }))))
     }
@@ -97,16 +198,73 @@ impl Collection {
         &self.handle().name
     }
 
+    pub(crate) fn storage(&self) -> &Storage {
+        &self.handle().storage
+    }
+
+    /// Get the type of primary key
+    pub fn primary_type(&self) -> PrimaryType {
+        self.handle().primary
+    }
+
+    /// Convert primary key/identifier into the type of collection
+    pub(crate) fn to_id(&self, id: &Primary) -> Result<Primary> {
+        to_primary(self.handle().primary, id)
+    }
+
+    pub(crate) fn id_to_raw(&self, id: &Primary) -> Result<Vec<u8>> {
+        primary_to_raw(self.handle().primary, id)
+    }
+
+    pub(crate) fn id_from_raw(&self, raw: &[u8]) -> Result<Primary> {
+        primary_from_raw(self.handle().primary, raw)
+    }
+
     /// Insert document into collection
     ///
-    /// The primary key/identifier of document will be selected by auto incrementing the id of last inserted document.
+    /// The primary key/identifier of document will be selected by auto incrementing the last allocated id.
+    /// The ids of deleted documents will not be reused unless the `reuse_ids` storage option is set.
+    ///
+    /// *Note*: The collections with string or binary primary keys requires `insert_with_id` instead.
     ///
     /// Primary key/identifier of new inserted document will be returned.
     ///
     pub fn insert<T: Serialize + Document>(&self, doc: T) -> Result<Primary> {
-        let id = self.new_id()?;
+        let doc = RawDocument::from_doc(&doc)?;
+
+        self.handle()
+            .storage
+            .write(|txn| self.insert_txn(txn, doc.clone()))
+    }
+
+    pub(crate) fn insert_txn(&self, txn: &WriteTransaction, doc: RawDocument) -> Result<Primary> {
+        let id = self.alloc_id_txn(txn)?;
 
-        self.put_raw(RawDocument::from_doc(&doc)?.with_id(id))?;
+        self.put_txn_with(txn, doc.with_id(id.clone()), false, None)?;
+
+        Ok(id)
+    }
+
+    /// Insert document with specified primary key/identifier into collection
+    ///
+    /// Unlike `put` it fails when the document with same primary key/identifier already exists.
+    ///
+    pub fn insert_with_id<T: Serialize + Document>(&self, doc: T) -> Result<Primary> {
+        let doc = RawDocument::from_doc(&doc)?;
+
+        self.handle()
+            .storage
+            .write(|txn| self.insert_with_id_txn(txn, doc.clone()))
+    }
+
+    pub(crate) fn insert_with_id_txn(
+        &self,
+        txn: &WriteTransaction,
+        doc: RawDocument,
+    ) -> Result<Primary> {
+        let id = self.to_id(&doc.req_id()?)?;
+
+        self.put_txn_with(txn, doc.with_id(id.clone()), false, None)?;
 
         Ok(id)
     }
@@ -124,49 +282,140 @@ impl Collection {
         filter: Option<Filter>,
         order: Order,
     ) -> Result<DocumentsIterator<T>> {
-        let handle = if let Some(handle) = &self.0 {
-            handle
-        } else {
-            unreachable!();
-        };
+        self.find_with(filter, order, FindOptions::default())
+    }
 
-        let txn = Arc::new(ReadTransaction::new(handle.storage.clone())?);
+    /// Find documents using optional filter, ordering and options
+    ///
+    /// The options allows select the range of found documents.
+    ///
+    pub fn find_with<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+        opts: FindOptions,
+    ) -> Result<DocumentsIterator<T>> {
+        let txn = SharedTxn::new(self.handle().storage.clone())?;
 
-        let ids = match (filter, order) {
-            (None, Order::Primary(order)) => {
-                PrimaryIterator::new(txn.clone(), self.clone(), order)?
-                    .collect::<Result<Vec<_>>>()?
-            }
+        let ids = txn.with(|txn| self.find_ids_txn(txn, filter, order, &opts))?;
+
+        DocumentsIterator::new(txn, self.clone(), ids)
+            .map(|iter| iter.project(opts.projection.as_ref()))
+    }
+
+    pub(crate) fn find_ids_txn(
+        &self,
+        txn: &ConstTransaction,
+        filter: Option<Filter>,
+        order: Order,
+        opts: &FindOptions,
+    ) -> Result<Vec<Primary>> {
+        let offset = opts.offset.unwrap_or(0);
+        let limit = opts.limit.unwrap_or(usize::MAX);
+        let after = opts.after.as_ref().map(|id| self.to_id(id)).transpose()?;
+        let filter = self.live_filter(filter)?;
+
+        Ok(match (filter, order) {
+            (None, Order::Primary(order)) => PrimaryIterator::new(txn, self.clone(), order, after)?
+                .skip(offset)
+                .take(limit)
+                .collect::<Result<Vec<_>>>()?,
 
             (None, Order::Field(field, order)) => self
-                .req_index(field)?
-                .query_iter(txn.clone(), order)?
+                .index_iter_txn(txn, field, order, after)?
+                .skip(offset)
+                .take(limit)
                 .collect::<Result<Vec<_>>>()?,
 
             (Some(filter), Order::Primary(order)) => {
-                let sel = filter.apply(&txn, &self)?;
+                let sel = filter.apply(txn, self)?;
 
                 if sel.inv {
-                    sel.filter(PrimaryIterator::new(txn.clone(), self.clone(), order)?)
+                    sel.filter(PrimaryIterator::new(txn, self.clone(), order, after)?)
+                        .skip(offset)
+                        .take(limit)
                         .collect::<Result<Vec<_>>>()?
                 } else {
-                    let mut ids = sel.ids.into_iter().collect::<Vec<_>>();
+                    let mut ids = sel
+                        .ids
+                        .into_iter()
+                        .filter(|id| match (&after, order) {
+                            (Some(after), OrderKind::Asc) => id > after,
+                            (Some(after), OrderKind::Desc) => id < after,
+                            (None, _) => true,
+                        })
+                        .collect::<Vec<_>>();
                     ids.sort_unstable_by(if order == OrderKind::Asc {
                         order_primary_asc
                     } else {
                         order_primary_desc
                     });
-                    ids
+                    ids.into_iter().skip(offset).take(limit).collect()
                 }
             }
 
             (Some(filter), Order::Field(field, order)) => filter
-                .apply(&txn, &self)?
-                .filter(self.req_index(field)?.query_iter(txn.clone(), order)?)
+                .apply(txn, self)?
+                .filter(self.index_iter_txn(txn, field, order, after)?)
+                .skip(offset)
+                .take(limit)
                 .collect::<Result<Vec<_>>>()?,
-        };
 
-        DocumentsIterator::new(handle.storage.clone(), self.clone(), ids)
+            (filter, Order::Near(field, point)) => {
+                self.near_ids_txn(txn, filter, &field, &point, opts)?
+            }
+        })
+    }
+
+    /// Select the documents ordered by rank
+    ///
+    /// The documents with same rank are ordered by primary key.
+    ///
+    pub(crate) fn select_ranked<R, I>(&self, ranked: I, opts: &FindOptions) -> Result<Vec<Primary>>
+    where
+        R: Ord,
+        I: IntoIterator<Item = (Primary, R)>,
+    {
+        let mut ranked = ranked.into_iter().collect::<Vec<_>>();
+
+        ranked.sort_unstable_by(|(a_id, a_rank), (b_id, b_rank)| {
+            a_rank.cmp(b_rank).then_with(|| a_id.cmp(b_id))
+        });
+
+        let mut ids = ranked.into_iter().map(|(id, _)| id);
+
+        if let Some(after) = &opts.after {
+            let after = self.to_id(after)?;
+            // skip the ranked ids up to the specified one
+            if !ids.any(|id| id == after) {
+                return Err(self.not_found(after));
+            }
+        }
+
+        Ok(ids
+            .skip(opts.offset.unwrap_or(0))
+            .take(opts.limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    fn index_iter_txn<'txn, P: AsRef<str>>(
+        &self,
+        txn: &'txn ConstTransaction<'txn>,
+        field: P,
+        order: OrderKind,
+        after: Option<Primary>,
+    ) -> Result<IndexIterator<'txn>> {
+        let index = self.req_index(field)?;
+
+        if let Some(id) = after {
+            if let Some(doc) = self.get_txn(txn, id.clone())? {
+                index.query_iter(txn, order, Some(&doc))
+            } else {
+                Err(self.not_found(id))
+            }
+        } else {
+            index.query_iter(txn, order, None)
+        }
     }
 
     /// Find documents using optional filter and ordering
@@ -185,19 +434,27 @@ impl Collection {
     pub fn find_ids(&self, filter: Option<Filter>) -> Result<HashSet<Primary>> {
         let handle = self.handle();
 
-        let txn = Arc::new(ReadTransaction::new(handle.storage.clone())?);
+        let txn = ReadTransaction::new(handle.storage.clone())?;
 
-        if let Some(filter) = filter {
-            let sel = filter.apply(&txn, &self)?;
+        self.find_ids_set_txn(&txn, filter)
+    }
+
+    pub(crate) fn find_ids_set_txn(
+        &self,
+        txn: &ConstTransaction,
+        filter: Option<Filter>,
+    ) -> Result<HashSet<Primary>> {
+        if let Some(filter) = self.live_filter(filter)? {
+            let sel = filter.apply(txn, self)?;
             if !sel.inv {
                 Ok(sel.ids)
             } else {
-                PrimaryIterator::new(txn, self.clone(), OrderKind::default())?
+                PrimaryIterator::new(txn, self.clone(), OrderKind::default(), None)?
                     .filter(move |res| if let Ok(id) = res { sel.has(id) } else { true })
                     .collect::<Result<HashSet<_>>>()
             }
         } else {
-            PrimaryIterator::new(txn, self.clone(), OrderKind::default())?
+            PrimaryIterator::new(txn, self.clone(), OrderKind::default(), None)?
                 .collect::<Result<HashSet<_>>>()
         }
     }
@@ -209,41 +466,205 @@ impl Collection {
     /// Returns the number of affected documents.
     ///
     pub fn update(&self, filter: Option<Filter>, modify: Modify) -> Result<usize> {
-        let handle = self.handle();
+        self.handle()
+            .storage
+            .write(|txn| self.update_txn(txn, filter.clone(), modify.clone()))
+    }
 
-        let found_ids = self.find_ids(filter)?;
+    pub(crate) fn update_txn(
+        &self,
+        txn: &WriteTransaction,
+        filter: Option<Filter>,
+        modify: Modify,
+    ) -> Result<usize> {
+        let found_ids = self.find_ids_set_txn(txn, filter)?;
 
         let mut count = 0;
-        {
-            let txn = WriteTransaction::new(handle.storage.clone())?;
-            let f = PutFlags::empty();
-            {
-                for id in found_ids {
-                    let (old_doc, new_doc) = {
-                        let mut access = txn.access();
-                        let old_doc =
-                            RawDocument::from_bin(access.get(&handle.db, &Unaligned::new(id))?)?
-                                .with_id(id);
-                        let new_doc = RawDocument::new(modify.apply(old_doc.clone().into_inner()))
-                            .with_id(id);
 
-                        access
-                            .put(&handle.db, &Unaligned::new(id), &new_doc.to_bin()?, f)
-                            .wrap_err()?;
+        self.update_ids_txn(txn, found_ids, &modify, |_old_doc, _new_doc| {
+            count += 1;
+            Ok(())
+        })?;
 
-                        (old_doc, new_doc)
-                    };
+        Ok(count)
+    }
 
-                    self.update_indexes(&txn, Some(&old_doc), Some(&new_doc))?;
+    /// Update documents using optional filter and modifier and get affected documents
+    ///
+    /// The documents are ordered by primary key/identifier.
+    ///
+    pub fn update_returning<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        modify: Modify,
+        returning: Returning,
+    ) -> Result<Vec<Affected<T>>> {
+        self.handle()
+            .storage
+            .write(|txn| {
+                self.update_returning_txn(
+                    txn,
+                    filter.clone(),
+                    Order::default(),
+                    None,
+                    modify.clone(),
+                    returning,
+                )
+            })?
+            .into_iter()
+            .map(Affected::into_doc)
+            .collect()
+    }
 
-                    count += 1;
-                }
-            }
+    /// Update the first found document using optional filter, ordering and modifier
+    ///
+    /// Returns the affected document or `None` when nothing found.
+    ///
+    pub fn find_one_and_update<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+        modify: Modify,
+        returning: Returning,
+    ) -> Result<Option<Affected<T>>> {
+        self.handle()
+            .storage
+            .write(|txn| {
+                self.update_returning_txn(
+                    txn,
+                    filter.clone(),
+                    order.clone(),
+                    Some(1),
+                    modify.clone(),
+                    returning,
+                )
+            })?
+            .into_iter()
+            .next()
+            .map(Affected::into_doc)
+            .transpose()
+    }
+
+    pub(crate) fn update_returning_txn(
+        &self,
+        txn: &WriteTransaction,
+        filter: Option<Filter>,
+        order: Order,
+        limit: Option<usize>,
+        modify: Modify,
+        returning: Returning,
+    ) -> Result<Vec<Affected<RawDocument>>> {
+        let opts = FindOptions {
+            limit,
+            ..FindOptions::default()
+        };
+        let found_ids = self.find_ids_txn(txn, filter, order, &opts)?;
+
+        let mut affected = Vec::with_capacity(found_ids.len());
 
-            txn.commit().wrap_err()?;
+        self.update_ids_txn(txn, found_ids, &modify, |old_doc, new_doc| {
+            affected.push(Affected::new(old_doc, new_doc, returning)?);
+            Ok(())
+        })?;
+
+        Ok(affected)
+    }
+
+    fn update_ids_txn<I, F>(
+        &self,
+        txn: &WriteTransaction,
+        ids: I,
+        modify: &Modify,
+        mut on_update: F,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = Primary>,
+        F: FnMut(&RawDocument, &RawDocument) -> Result<()>,
+    {
+        let handle = self.handle();
+
+        let f = PutFlags::empty();
+
+        for id in ids {
+            let raw_id = self.id_to_raw(&id)?;
+            let (old_doc, rev) = {
+                let access = txn.access();
+                RawDocument::from_bin_with_rev(
+                    access
+                        .get(&handle.db, raw_id.as_slice())
+                        .to_opt()?
+                        .ok_or_else(|| self.not_found(id.clone()))?,
+                )?
+            };
+            let old_doc = old_doc.with_id(id.clone());
+            let new_doc = RawDocument::new(modify.apply(old_doc.clone().into_inner())).with_id(id);
+
+            self.validate(&new_doc)?;
+
+            let rev = self.next_rev_txn(txn, Some(rev))?;
+
+            txn.access()
+                .put(
+                    &handle.db,
+                    raw_id.as_slice(),
+                    &new_doc.to_bin_with_rev(rev)?,
+                    f,
+                )
+                .wrap_err()?;
+
+            on_update(&old_doc, &new_doc)?;
+
+            self.document_changed(txn, Some(old_doc), Some(new_doc))?;
         }
 
-        Ok(count)
+        Ok(())
+    }
+
+    /// Update documents or insert new document when nothing found
+    ///
+    /// When the filter matches some documents it works like `update`.
+    /// Otherwise the modifier will be applied to the default document which will be inserted.
+    /// The default document without primary key/identifier gets new one like with `insert`.
+    ///
+    /// Returns the primary key/identifier of inserted document or `None` when documents was updated.
+    ///
+    pub fn upsert<T: Serialize + Document>(
+        &self,
+        filter: Option<Filter>,
+        modify: Modify,
+        default_doc: T,
+    ) -> Result<Option<Primary>> {
+        let doc = RawDocument::from_doc(&default_doc)?;
+
+        self.handle()
+            .storage
+            .write(|txn| self.upsert_txn(txn, filter.clone(), modify.clone(), doc.clone()))
+    }
+
+    pub(crate) fn upsert_txn(
+        &self,
+        txn: &WriteTransaction,
+        filter: Option<Filter>,
+        modify: Modify,
+        doc: RawDocument,
+    ) -> Result<Option<Primary>> {
+        if self.update_txn(txn, filter, modify.clone())? > 0 {
+            return Ok(None);
+        }
+
+        let id = doc.get_id().clone();
+        let doc = RawDocument::new(modify.apply(doc.into_inner()));
+        let doc = match id {
+            Some(id) => doc.with_id(id),
+            None => doc,
+        };
+
+        if doc.has_id() {
+            self.insert_with_id_txn(txn, doc)
+        } else {
+            self.insert_txn(txn, doc)
+        }
+        .map(Some)
     }
 
     /// Remove documents using optional filter
@@ -253,36 +674,118 @@ impl Collection {
     /// Returns the number of affected documents.
     ///
     pub fn remove(&self, filter: Option<Filter>) -> Result<usize> {
-        let handle = self.handle();
+        self.handle()
+            .storage
+            .write(|txn| self.remove_txn(txn, filter.clone()))
+    }
 
-        let found_ids = self.find_ids(filter)?;
+    pub(crate) fn remove_txn(
+        &self,
+        txn: &WriteTransaction,
+        filter: Option<Filter>,
+    ) -> Result<usize> {
+        let found_ids = self.find_ids_set_txn(txn, filter)?;
 
         let mut count = 0;
-        {
-            let txn = WriteTransaction::new(handle.storage.clone())?;
-            {
-                for id in found_ids {
-                    let old_doc = {
-                        let mut access = txn.access();
-                        let old_doc =
-                            RawDocument::from_bin(access.get(&handle.db, &Unaligned::new(id))?)?
-                                .with_id(id);
 
-                        access.del_key(&handle.db, &Unaligned::new(id)).wrap_err()?;
+        self.remove_ids_txn(txn, found_ids, |_old_doc| {
+            count += 1;
+        })?;
 
-                        old_doc
-                    };
+        Ok(count)
+    }
 
-                    self.update_indexes(&txn, Some(&old_doc), None)?;
+    /// Remove documents using optional filter and get removed documents
+    ///
+    /// The documents are ordered by primary key/identifier.
+    ///
+    pub fn remove_returning<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+    ) -> Result<Vec<T>> {
+        self.handle()
+            .storage
+            .write(|txn| self.remove_returning_txn(txn, filter.clone(), Order::default(), None))?
+            .into_iter()
+            .map(RawDocument::into_doc)
+            .collect()
+    }
 
-                    count += 1;
-                }
-            }
+    /// Delete the first found document using optional filter and ordering
+    ///
+    /// Returns the deleted document or `None` when nothing found.
+    ///
+    pub fn find_one_and_delete<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+    ) -> Result<Option<T>> {
+        self.handle()
+            .storage
+            .write(|txn| self.remove_returning_txn(txn, filter.clone(), order.clone(), Some(1)))?
+            .into_iter()
+            .next()
+            .map(RawDocument::into_doc)
+            .transpose()
+    }
+
+    pub(crate) fn remove_returning_txn(
+        &self,
+        txn: &WriteTransaction,
+        filter: Option<Filter>,
+        order: Order,
+        limit: Option<usize>,
+    ) -> Result<Vec<RawDocument>> {
+        let opts = FindOptions {
+            limit,
+            ..FindOptions::default()
+        };
+        let found_ids = self.find_ids_txn(txn, filter, order, &opts)?;
+
+        let mut removed = Vec::with_capacity(found_ids.len());
+
+        self.remove_ids_txn(txn, found_ids, |old_doc| {
+            removed.push(old_doc.clone());
+        })?;
+
+        Ok(removed)
+    }
+
+    pub(crate) fn remove_ids_txn<I, F>(
+        &self,
+        txn: &WriteTransaction,
+        ids: I,
+        mut on_remove: F,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = Primary>,
+        F: FnMut(&RawDocument),
+    {
+        let handle = self.handle();
+
+        for id in ids {
+            let raw_id = self.id_to_raw(&id)?;
+            let old_doc = {
+                let mut access = txn.access();
+                let old_doc = RawDocument::from_bin(
+                    access
+                        .get(&handle.db, raw_id.as_slice())
+                        .to_opt()?
+                        .ok_or_else(|| self.not_found(id.clone()))?,
+                )?
+                .with_id(id);
 
-            txn.commit().wrap_err()?;
+                access.del_key(&handle.db, raw_id.as_slice()).wrap_err()?;
+
+                old_doc
+            };
+
+            on_remove(&old_doc);
+
+            self.document_changed(txn, Some(old_doc), None)?;
         }
 
-        Ok(count)
+        Ok(())
     }
 
     /// Dump all documents which stored into the collection
@@ -299,42 +802,85 @@ impl Collection {
     where
         I: IntoIterator<Item = T>,
     {
-        self.purge()?;
-
         let handle = self.handle();
 
-        let txn = WriteTransaction::new(handle.storage.clone())?;
-        let f = PutFlags::empty();
-        let mut count = 0;
-
-        {
-            for doc in docs.into_iter() {
+        // the documents is kept to retry the loading when the storage is full
+        let docs = docs
+            .into_iter()
+            .map(|doc| {
                 let doc = RawDocument::from_doc(&doc)?;
+                let id = self.to_id(&doc.req_id()?)?;
+                Ok(doc.with_id(id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        handle.storage.write(|txn| {
+            self.purge_txn(txn)?;
+
+            let f = PutFlags::empty();
+            let mut count = 0;
+
+            for doc in &docs {
+                let doc = doc.clone();
                 let id = doc.req_id()?;
+                // the revisions of removed documents is never reused
+                let rev = self.next_rev_txn(txn, None)?;
+
+                self.validate(&doc)?;
 
                 {
                     let mut access = txn.access();
 
                     access
-                        .put(&handle.db, &Unaligned::new(id), &doc.to_bin()?, f)
+                        .put(
+                            &handle.db,
+                            self.id_to_raw(&id)?.as_slice(),
+                            &doc.to_bin_with_rev(rev)?,
+                            f,
+                        )
                         .wrap_err()?;
                 }
 
-                self.update_indexes(&txn, None, Some(&doc))?;
+                self.bump_seq_txn(txn, &id)?;
+
+                self.document_changed(txn, None, Some(doc))?;
 
                 count += 1;
             }
-        }
 
-        Ok(count)
+            Ok(count)
+        })
     }
 
     /// Remove all documents from the collection
     ///
     pub fn purge(&self) -> Result<()> {
+        self.handle().storage.write(|txn| self.purge_txn(txn))
+    }
+
+    pub(crate) fn purge_txn(&self, txn: &WriteTransaction) -> Result<()> {
         let handle = self.handle();
 
-        let txn = WriteTransaction::new(handle.storage.clone()).wrap_err()?;
+        if !handle.watchers.is_empty()? {
+            let access = txn.access();
+            let cursor = txn.cursor(self.clone()).wrap_err()?;
+
+            for res in CursorIter::new(
+                MaybeOwned::Owned(cursor),
+                &access,
+                |c, a| c.first(a),
+                Cursor::next::<[u8], [u8]>,
+            )
+            .wrap_err()?
+            {
+                let (key, val) = res.wrap_err()?;
+                let old_doc = RawDocument::from_bin(val)?.with_id(self.id_from_raw(key)?);
+                if let Some(change) = Change::new(Some(old_doc), None)? {
+                    handle.storage.add_change(self.clone(), change)?;
+                }
+            }
+        }
+
         let mut access = txn.access();
 
         let indexes = handle.indexes.read().wrap_err()?;
@@ -346,33 +892,68 @@ impl Collection {
     }
 
     /// Checks the collection contains document with specified primary key
-    pub fn has(&self, id: Primary) -> Result<bool> {
+    pub fn has(&self, id: impl Into<Primary>) -> Result<bool> {
         let handle = self.handle();
 
         let txn = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+
+        self.has_txn(&txn, id.into())
+    }
+
+    pub(crate) fn has_txn(&self, txn: &ConstTransaction, id: Primary) -> Result<bool> {
+        if self.get_expiry()?.is_some() {
+            // the document should be checked for expiration
+            return self.get_txn(txn, id).map(|doc| doc.is_some());
+        }
+
+        let handle = self.handle();
+        let id = self.id_to_raw(&id)?;
+
         let access = txn.access();
 
         access
-            .get::<Unaligned<Primary>, [u8]>(&handle.db, &Unaligned::new(id))
+            .get::<[u8], [u8]>(&handle.db, id.as_slice())
             .to_opt()
             .map(|res| res != None)
             .wrap_err()
     }
 
     /// Get document from collection using primary key/identifier
-    pub fn get<T: DeserializeOwned + Document>(&self, id: Primary) -> Result<Option<T>> {
+    pub fn get<T: DeserializeOwned + Document>(&self, id: impl Into<Primary>) -> Result<Option<T>> {
         let handle = self.handle();
 
         let txn = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+
+        self.get_txn(&txn, id.into())?
+            .map(RawDocument::into_doc)
+            .transpose()
+    }
+
+    pub(crate) fn get_txn(
+        &self,
+        txn: &ConstTransaction,
+        id: Primary,
+    ) -> Result<Option<RawDocument>> {
+        let handle = self.handle();
+        let id = self.to_id(&id)?;
+        let raw_id = self.id_to_raw(&id)?;
+
         let access = txn.access();
 
         Ok(
             match access
-                .get::<Unaligned<Primary>, [u8]>(&handle.db, &Unaligned::new(id))
+                .get::<[u8], [u8]>(&handle.db, raw_id.as_slice())
                 .to_opt()
                 .wrap_err()?
             {
-                Some(val) => Some(RawDocument::from_bin(val)?.with_id(id).into_doc()?),
+                Some(val) => {
+                    let doc = RawDocument::from_bin(val)?.with_id(id);
+                    if self.is_expired(&doc)? {
+                        None
+                    } else {
+                        Some(doc)
+                    }
+                }
                 None => None,
             },
         )
@@ -387,75 +968,217 @@ impl Collection {
     }
 
     fn put_raw(&self, doc: RawDocument) -> Result<()> {
-        let id = doc.req_id()?;
+        self.handle()
+            .storage
+            .write(|txn| self.put_txn(txn, doc.clone()))
+    }
+
+    pub(crate) fn put_txn(&self, txn: &WriteTransaction, doc: RawDocument) -> Result<()> {
+        self.put_txn_with(txn, doc, true, None).map(|_| ())
+    }
+
+    /// Replace document in the collection when it has expected revision
+    ///
+    /// The zero revision means that the document must not exists.
+    /// When the document has another revision the conflict error will be returned.
+    ///
+    /// *Note*: The document must have primary key/identifier.
+    ///
+    /// Returns the new revision of document.
+    ///
+    pub fn put_if_revision<T: Serialize + Document>(
+        &self,
+        doc: T,
+        rev: Revision,
+    ) -> Result<Revision> {
+        let doc = RawDocument::from_doc(&doc)?;
+        self.handle()
+            .storage
+            .write(|txn| self.put_if_revision_txn(txn, doc.clone(), rev))
+    }
+
+    pub(crate) fn put_if_revision_txn(
+        &self,
+        txn: &WriteTransaction,
+        doc: RawDocument,
+        rev: Revision,
+    ) -> Result<Revision> {
+        self.put_txn_with(txn, doc, true, Some(rev))
+    }
+
+    fn put_txn_with(
+        &self,
+        txn: &WriteTransaction,
+        doc: RawDocument,
+        overwrite: bool,
+        expected: Option<Revision>,
+    ) -> Result<Revision> {
+        let id = self.to_id(&doc.req_id()?)?;
+        let raw_id = self.id_to_raw(&id)?;
+        let doc = doc.with_id(id.clone());
+
+        self.validate(&doc)?;
 
         let handle = self.handle();
 
-        let txn = WriteTransaction::new(handle.storage.clone()).wrap_err()?;
+        let (old_doc, old_rev) = {
+            let access = txn.access();
+            if let Some(old_doc) = access.get(&handle.db, raw_id.as_slice()).to_opt()? {
+                let (old_doc, old_rev) = RawDocument::from_bin_with_rev(old_doc)?;
+                (Some(old_doc.with_id(id.clone())), Some(old_rev))
+            } else {
+                (None, None)
+            }
+        };
 
-        let old_doc = {
-            let mut access = txn.access();
-            let old_doc =
-                if let Some(old_doc) = access.get(&handle.db, &Unaligned::new(id)).to_opt()? {
-                    Some(RawDocument::from_bin(old_doc)?.with_id(id))
+        if old_doc.is_some() && !overwrite {
+            return Err(Error::DuplicateId {
+                collection: handle.name.clone(),
+                id,
+            });
+        }
+
+        if let Some(expected) = expected {
+            // the stored documents has non-zero revisions
+            if expected != old_rev.unwrap_or(0) {
+                return Err(Conflict {
+                    id,
+                    expected,
+                    actual: old_rev,
+                })
+                .wrap_err();
+            }
+        }
+
+        let rev = self.next_rev_txn(txn, old_rev)?;
+
+        txn.access()
+            .put(
+                &handle.db,
+                raw_id.as_slice(),
+                &doc.to_bin_with_rev(rev)?,
+                if overwrite {
+                    PutFlags::empty()
                 } else {
-                    None
-                };
+                    NOOVERWRITE
+                },
+            )
+            .wrap_err()?;
 
-            access
-                .put(
-                    &handle.db,
-                    &Unaligned::new(id),
-                    &doc.to_bin()?,
-                    PutFlags::empty(),
-                )
-                .wrap_err()?;
+        self.bump_seq_txn(txn, &id)?;
 
-            old_doc
-        };
+        self.document_changed(txn, old_doc, Some(doc))?;
 
-        self.update_indexes(
-            &txn,
-            if let Some(ref doc) = old_doc {
-                Some(&doc)
-            } else {
-                None
-            },
-            Some(&doc),
-        )?;
+        Ok(rev)
+    }
 
-        txn.commit().wrap_err()?;
+    /// Get the revision of document with specified primary key/identifier
+    ///
+    /// Returns `None` when the document does not exists.
+    ///
+    pub fn revision(&self, id: impl Into<Primary>) -> Result<Option<Revision>> {
+        let handle = self.handle();
 
-        Ok(())
+        let txn = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+
+        Ok(self.get_rev_txn(&txn, id.into())?.map(|(_doc, rev)| rev))
     }
 
-    /// Delete document with specified primary key/identifier from the collection
-    pub fn delete(&self, id: Primary) -> Result<bool> {
+    /// Get document with revision using primary key/identifier
+    pub fn get_with_revision<T: DeserializeOwned + Document>(
+        &self,
+        id: impl Into<Primary>,
+    ) -> Result<Option<(T, Revision)>> {
         let handle = self.handle();
 
-        let txn = WriteTransaction::new(handle.storage.clone()).wrap_err()?;
+        let txn = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+
+        self.get_rev_txn(&txn, id.into())?
+            .map(|(doc, rev)| doc.into_doc().map(|doc| (doc, rev)))
+            .transpose()
+    }
+
+    pub(crate) fn get_rev_txn(
+        &self,
+        txn: &ConstTransaction,
+        id: Primary,
+    ) -> Result<Option<(RawDocument, Revision)>> {
+        let handle = self.handle();
+        let id = self.to_id(&id)?;
+        let raw_id = self.id_to_raw(&id)?;
+
+        let access = txn.access();
+
+        Ok(
+            match access
+                .get::<[u8], [u8]>(&handle.db, raw_id.as_slice())
+                .to_opt()
+                .wrap_err()?
+            {
+                Some(val) => {
+                    let (doc, rev) = RawDocument::from_bin_with_rev(val)?;
+                    let doc = doc.with_id(id);
+                    if self.is_expired(&doc)? {
+                        None
+                    } else {
+                        Some((doc, rev))
+                    }
+                }
+                None => None,
+            },
+        )
+    }
+
+    /// Delete document with specified primary key/identifier from the collection
+    pub fn delete(&self, id: impl Into<Primary>) -> Result<bool> {
+        let id = id.into();
+        self.handle()
+            .storage
+            .write(|txn| self.delete_txn(txn, id.clone()))
+    }
+
+    pub(crate) fn delete_txn(&self, txn: &WriteTransaction, id: Primary) -> Result<bool> {
+        let handle = self.handle();
+        let id = self.to_id(&id)?;
+        let raw_id = self.id_to_raw(&id)?;
 
         let old_doc = {
             let mut access = txn.access();
 
             let old_doc =
-                if let Some(old_doc) = access.get(&handle.db, &Unaligned::new(id)).to_opt()? {
+                if let Some(old_doc) = access.get(&handle.db, raw_id.as_slice()).to_opt()? {
                     RawDocument::from_bin(old_doc)?.with_id(id)
                 } else {
                     // document not exists
                     return Ok(false);
                 };
 
-            access.del_key(&handle.db, &Unaligned::new(id)).wrap_err()?;
+            access.del_key(&handle.db, raw_id.as_slice()).wrap_err()?;
 
             old_doc
         };
 
-        let status = self.update_indexes(&txn, Some(&old_doc), None)?;
+        self.document_changed(txn, Some(old_doc), None)
+    }
+
+    /// Update indexes and notify subscribers when document changed
+    fn document_changed(
+        &self,
+        txn: &WriteTransaction,
+        old_doc: Option<RawDocument>,
+        new_doc: Option<RawDocument>,
+    ) -> Result<bool> {
+        let res = self.update_indexes(txn, old_doc.as_ref(), new_doc.as_ref())?;
+
+        let handle = self.handle();
 
-        txn.commit().wrap_err()?;
+        if !handle.watchers.is_empty()? {
+            if let Some(change) = Change::new(old_doc, new_doc)? {
+                handle.storage.add_change(self.clone(), change)?;
+            }
+        }
 
-        Ok(status)
+        Ok(res)
     }
 
     fn update_indexes(
@@ -466,39 +1189,207 @@ impl Collection {
     ) -> Result<bool> {
         let handle = self.handle();
 
+        let id = old_doc
+            .or(new_doc)
+            .ok_or("Either old_doc or new_doc or both must present")
+            .wrap_err()?
+            .req_id()?;
+
         {
             let indexes = handle.indexes.read().wrap_err()?;
             let mut access = txn.access();
 
             for index in indexes.iter() {
-                index.update_index(&mut access, old_doc, new_doc)?;
+                // the building index will get the rest of documents from builder
+                if index.is_built(&id) {
+                    index.update_index(&mut access, old_doc, new_doc)?;
+                }
             }
         }
 
         Ok(old_doc.is_some())
     }
 
+    /// Watch the changes of documents using optional filter
+    ///
+    /// The change will be received when the document passes the filter before or after it.
+    /// When none filter specified then all changes will be received.
+    ///
+    /// *Note*: The changes are sent only after the write transaction which made it is committed.
+    ///
+    pub fn watch(&self, filter: Option<Filter>) -> Result<Watcher> {
+        self.handle().watchers.add(filter)
+    }
+
+    pub(crate) fn notify(&self, change: &Change) -> Result<()> {
+        self.handle().watchers.notify(change)
+    }
+
     /// Get the last primary key/identifier of inserted document
-    pub fn last_id(&self) -> Result<Primary> {
+    ///
+    /// Returns `None` when the collection is empty.
+    ///
+    pub fn last_id(&self) -> Result<Option<Primary>> {
         let handle = self.handle();
 
         let txn = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+
+        self.last_id_txn(&txn)
+    }
+
+    pub(crate) fn last_id_txn(&self, txn: &ConstTransaction) -> Result<Option<Primary>> {
         let mut cursor = txn.cursor(self.clone()).wrap_err()?;
         let access = txn.access();
 
-        cursor
-            .last::<Unaligned<Primary>, [u8]>(&access)
+        cursor
+            .last::<[u8], [u8]>(&access)
+            .to_opt()
+            .wrap_err()?
+            .map(|(key, _val)| self.id_from_raw(key))
+            .transpose()
+    }
+
+    /// Get the last integer primary key/identifier or zero
+    fn last_int_id_txn(&self, txn: &ConstTransaction) -> Result<u64> {
+        Ok(self
+            .last_id_txn(txn)?
+            .and_then(|id| id.as_int())
+            .unwrap_or(0))
+    }
+
+    /// Check that the primary keys/identifiers can be generated
+    fn req_integer_ids(&self) -> Result<()> {
+        let primary = self.handle().primary;
+
+        if primary.is_integer() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Unable to generate {} primary key, use insert_with_id instead",
+                primary
+            ))
+            .wrap_err()
+        }
+    }
+
+    /// Get the new primary key/identifier
+    ///
+    /// *Note*: The id is not reserved, so the `insert` may select another one.
+    ///
+    pub fn new_id(&self) -> Result<Primary> {
+        self.req_integer_ids()?;
+
+        let handle = self.handle();
+
+        let txn = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+
+        let last_id = self.last_int_id_txn(&txn)?;
+
+        Ok(Primary::Int(
+            if handle.storage.reuse_ids() {
+                last_id
+            } else {
+                u64::max(last_id, self.seq_id_txn(&txn)?)
+            } + 1,
+        ))
+    }
+
+    /// Allocate new primary key/identifier inside write transaction
+    fn alloc_id_txn(&self, txn: &WriteTransaction) -> Result<Primary> {
+        self.req_integer_ids()?;
+
+        let last_id = self.last_int_id_txn(txn)?;
+
+        if self.handle().storage.reuse_ids() {
+            return Ok(Primary::Int(last_id + 1));
+        }
+
+        let id = u64::max(last_id, self.seq_id_txn(txn)?) + 1;
+
+        self.set_seq_txn(txn, id)?;
+
+        Ok(Primary::Int(id))
+    }
+
+    /// Get the last allocated primary key/identifier
+    fn seq_id_txn(&self, txn: &ConstTransaction) -> Result<u64> {
+        let handle = self.handle();
+        let access = txn.access();
+
+        Ok(
+            match access
+                .get::<str, [u8]>(handle.storage.main_db(), &handle.seq_key)
+                .to_opt()
+                .wrap_err()?
+            {
+                Some(raw) => self.id_from_raw(raw)?.as_int().unwrap_or(0),
+                None => 0,
+            },
+        )
+    }
+
+    fn set_seq_txn(&self, txn: &WriteTransaction, id: u64) -> Result<()> {
+        let handle = self.handle();
+        let id = self.id_to_raw(&Primary::Int(id))?;
+        let mut access = txn.access();
+
+        access
+            .put(
+                handle.storage.main_db(),
+                handle.seq_key.as_str(),
+                id.as_slice(),
+                PutFlags::empty(),
+            )
+            .wrap_err()
+    }
+
+    /// Move the sequence forward when the document with greater id was put
+    fn bump_seq_txn(&self, txn: &WriteTransaction, id: &Primary) -> Result<()> {
+        if let Some(id) = id.as_int() {
+            if !self.handle().storage.reuse_ids() && id > self.seq_id_txn(txn)? {
+                self.set_seq_txn(txn, id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocate the revision of changed document
+    ///
+    /// The revisions is allocated from the sequence of collection,
+    /// so it is never reused even when the document was removed and inserted again.
+    ///
+    fn next_rev_txn(&self, txn: &WriteTransaction, old_rev: Option<Revision>) -> Result<Revision> {
+        let handle = self.handle();
+        let mut access = txn.access();
+
+        let last_rev: Revision = match access
+            .get::<str, [u8]>(handle.storage.main_db(), &handle.rev_key)
             .to_opt()
-            .map(|res| res.map(|(key, _val)| key.get()).unwrap_or(0))
-            .wrap_err()
-    }
+            .wrap_err()?
+        {
+            Some(raw) => serde_cbor::from_slice(raw).wrap_err()?,
+            None => 0,
+        };
 
-    /// Get the new primary key/identifier
-    pub fn new_id(&self) -> Result<Primary> {
-        self.last_id().map(|id| id + 1)
+        // the documents which was stored before the sequence has no revision in it
+        let rev = Revision::max(last_rev, old_rev.unwrap_or(0)) + 1;
+
+        access
+            .put(
+                handle.storage.main_db(),
+                handle.rev_key.as_str(),
+                &serde_cbor::to_vec(&rev).wrap_err()?,
+                PutFlags::empty(),
+            )
+            .wrap_err()?;
+
+        Ok(rev)
     }
 
     /// Get indexes info from the collection
+    ///
+    /// The indexes which isn't ready yet has the state of building.
+    ///
     pub fn get_indexes(&self) -> Result<KeyFields> {
         let handle = self.handle();
 
@@ -515,8 +1406,7 @@ impl Collection {
         KeyField: From<T>,
     {
         for key_field in indexes.as_ref() {
-            let KeyField { path, kind, key } = KeyField::from(key_field.clone());
-            self.ensure_index(path, kind, key)?;
+            self.ensure_index_field(KeyField::from(key_field.clone()))?;
         }
         Ok(())
     }
@@ -535,18 +1425,36 @@ impl Collection {
         kind: IndexKind,
         key: KeyType,
     ) -> Result<bool> {
-        if let Some(index) = self.get_index(&path)? {
-            if index.kind() == kind && index.key() == key {
+        self.ensure_index_field(KeyField::new(path.as_ref()).with_type(key).with_kind(kind))
+    }
+
+    /// Ensure index for the collection using key field definition
+    ///
+    /// The key field may be compound.
+    pub fn ensure_index_field(&self, field: KeyField) -> Result<bool> {
+        self.ensure_index_with(field, IndexOptions::default())
+    }
+
+    /// Ensure index for the collection using key field definition and options
+    ///
+    /// The existing index with same definition is kept even it isn't ready yet.
+    ///
+    pub fn ensure_index_with(&self, field: KeyField, opts: IndexOptions) -> Result<bool> {
+        if let Some(index) = self.find_index(&field.path)? {
+            if index.is_field(&field) {
                 return Ok(false);
             } else {
-                self.drop_index(&path)?;
+                self.drop_index(&field.path)?;
             }
         }
 
-        self.create_index(&path, kind, key)
+        self.create_index_with(field, opts)
     }
 
     /// Checks the index for specified field exists for the collection
+    ///
+    /// *Note*: The index may be not ready yet.
+    ///
     pub fn has_index<P: AsRef<str>>(&self, path: P) -> Result<bool> {
         let path = path.as_ref();
 
@@ -564,14 +1472,36 @@ impl Collection {
         kind: IndexKind,
         key: KeyType,
     ) -> Result<bool> {
-        let path = path.as_ref();
+        self.create_index_field(KeyField::new(path.as_ref()).with_type(key).with_kind(kind))
+    }
+
+    /// Create index for the collection using key field definition
+    ///
+    /// The key field may be compound.
+    pub fn create_index_field(&self, field: KeyField) -> Result<bool> {
+        self.create_index_with(field, IndexOptions::default())
+    }
 
+    /// Create index for the collection using key field definition and options
+    ///
+    /// The background index is returned immediately and it is used by queries when it is ready.
+    ///
+    pub fn create_index_with(&self, field: KeyField, opts: IndexOptions) -> Result<bool> {
         let handle = self.handle();
 
+        if field.kind == IndexKind::FullText && field.is_compound() {
+            return Err("Full-text index cannot be compound").wrap_err();
+        }
+
+        if field.kind == IndexKind::Spatial && field.compound.len() > 1 {
+            return Err("Spatial index can have a pair of latitude and longitude fields only")
+                .wrap_err();
+        }
+
         {
             let indexes = handle.indexes.read().wrap_err()?;
             // search alive index
-            if indexes.iter().any(|index| index.path() == path) {
+            if indexes.iter().any(|index| index.path() == field.path) {
                 return Ok(false);
             }
         }
@@ -581,36 +1511,35 @@ impl Collection {
             handle.storage.clone(),
             handle
                 .storage
-                .enumerate(IndexDef::new(handle.name.clone(), path, kind, key)),
+                .enumerate(IndexDef::new(handle.name.clone(), field)),
+            handle.primary,
         )?;
 
-        {
-            // fulfill index
-            let txn = WriteTransaction::new(handle.storage.clone()).wrap_err()?;
-            {
+        if opts.background {
+            handle.storage.write(|txn| {
                 let mut access = txn.access();
-
-                let txn2 = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
-                let cursor2 = txn2.cursor(self.clone()).wrap_err()?;
-                let access2 = txn2.access();
-
-                for res in CursorIter::new(
-                    MaybeOwned::Owned(cursor2),
-                    &access2,
-                    |c, a| c.first(a),
-                    Cursor::next::<Unaligned<Primary>, [u8]>,
-                )
-                .wrap_err()?
-                {
-                    let (key, val) = res.wrap_err()?;
-                    let doc = RawDocument::from_bin(val)?.with_id(key.get());
-                    index.update_index(&mut access, None, Some(&doc))?;
-                }
-            }
-
-            txn.commit().wrap_err()?;
+                // the marker allows to restart the building when it was interrupted
+                access
+                    .put(
+                        handle.storage.main_db(),
+                        index.build_key(),
+                        "",
+                        PutFlags::empty(),
+                    )
+                    .wrap_err()?;
+                index.start_build(self.count_all_txn(txn)?);
+                Ok(())
+            })?;
+
+            handle.indexes.write().wrap_err()?.push(index.clone());
+
+            IndexBuilder::start(self.clone(), index, opts.batch_size());
+
+            return Ok(true);
         }
 
+        self.fill_index(&index)?;
+
         // add index to collection indexes
         let mut indexes = handle.indexes.write().wrap_err()?;
         indexes.push(index);
@@ -618,6 +1547,36 @@ impl Collection {
         Ok(true)
     }
 
+    /// Fulfill the index from scratch using single write transaction
+    pub(crate) fn fill_index(&self, index: &Index) -> Result<()> {
+        let handle = self.handle();
+
+        handle.storage.write(|txn| {
+            let mut access = txn.access();
+
+            index.purge(&mut access)?;
+
+            let txn2 = ReadTransaction::new(handle.storage.clone()).wrap_err()?;
+            let cursor2 = txn2.cursor(self.clone()).wrap_err()?;
+            let access2 = txn2.access();
+
+            for res in CursorIter::new(
+                MaybeOwned::Owned(cursor2),
+                &access2,
+                |c, a| c.first(a),
+                Cursor::next::<[u8], [u8]>,
+            )
+            .wrap_err()?
+            {
+                let (key, val) = res.wrap_err()?;
+                let doc = RawDocument::from_bin(val)?.with_id(self.id_from_raw(key)?);
+                index.update_index(&mut access, None, Some(&doc))?;
+            }
+
+            Ok(())
+        })
+    }
+
     /// Remove index from the collection
     pub fn drop_index<P: AsRef<str>>(&self, path: P) -> Result<bool> {
         let path = path.as_ref();
@@ -630,18 +1589,182 @@ impl Collection {
         };
 
         Ok(if let Some(pos) = found_pos {
-            let mut indexes = handle.indexes.write().wrap_err()?;
-            let index = indexes.remove(pos);
-            let txn = WriteTransaction::new(handle.storage.clone())?;
-            let mut access = txn.access();
-            index.to_delete(&mut access)?;
+            let index = handle.indexes.write().wrap_err()?.remove(pos);
+            handle
+                .storage
+                .write(|txn| self.delete_index_txn(txn, &index))?;
             true
         } else {
             false
         })
     }
 
+    /// Cancel building of index and remove it from the collection
+    ///
+    /// Returns `false` when the index is missing or it is ready.
+    ///
+    pub fn cancel_index<P: AsRef<str>>(&self, path: P) -> Result<bool> {
+        match self.find_index(&path)? {
+            Some(index) if !index.is_ready() => self.drop_index(path),
+            _ => Ok(false),
+        }
+    }
+
+    /// Restart the building of index from scratch
+    ///
+    /// Returns `false` when the index with same building marker is missing.
+    ///
+    pub(crate) fn resume_build(&self, path: &str, key: &str) -> Result<bool> {
+        let index = match self.find_index(path)? {
+            Some(index) if index.build_key() == key => index,
+            _ => return Ok(false),
+        };
+
+        self.handle().storage.write(|txn| {
+            index.purge(&mut txn.access())?;
+            index.start_build(self.count_all_txn(txn)?);
+            Ok(())
+        })?;
+
+        IndexBuilder::start(self.clone(), index, IndexOptions::default().batch_size());
+
+        Ok(true)
+    }
+
+    /// Delete the index database with the marker of building
+    fn delete_index_txn(&self, txn: &WriteTransaction, index: &Index) -> Result<()> {
+        index.to_delete(&mut txn.access())?;
+        self.finish_build_txn(txn, index)
+    }
+
+    /// Get the expiration of documents
+    pub fn get_expiry(&self) -> Result<Option<Expiry>> {
+        let expiry = self.handle().expiry.read().wrap_err()?;
+        Ok(expiry.clone())
+    }
+
+    /// Set the expiration of documents
+    ///
+    /// The expired documents are hidden from reads and removed by sweeping.
+    ///
+    /// The integer index for timestamp field will be created when the field has no index.
+    ///
+    pub fn set_expiry(&self, expiry: Expiry) -> Result<()> {
+        let handle = self.handle();
+
+        if !self.has_index(&expiry.field)? {
+            self.create_index(&expiry.field, IndexKind::Index, KeyType::Int)?;
+        }
+
+        let val = ron::ser::to_string(&expiry).wrap_err()?;
+
+        handle.storage.write(|txn| {
+            let mut access = txn.access();
+            access
+                .put(
+                    handle.storage.main_db(),
+                    handle.expiry_key.as_str(),
+                    val.as_str(),
+                    PutFlags::empty(),
+                )
+                .wrap_err()
+        })?;
+
+        *handle.expiry.write().wrap_err()? = Some(expiry);
+
+        Ok(())
+    }
+
+    /// Remove the expiration of documents
+    ///
+    /// Returns `false` when the collection has no expiration.
+    ///
+    pub fn drop_expiry(&self) -> Result<bool> {
+        let handle = self.handle();
+
+        if self.get_expiry()?.is_none() {
+            return Ok(false);
+        }
+
+        handle.storage.write(|txn| {
+            let mut access = txn.access();
+            access
+                .del_key(handle.storage.main_db(), handle.expiry_key.as_str())
+                .to_opt()
+                .wrap_err()
+        })?;
+
+        *handle.expiry.write().wrap_err()? = None;
+
+        Ok(true)
+    }
+
+    /// Get the schema of documents
+    pub fn get_schema(&self) -> Result<Option<Schema>> {
+        let schema = self.handle().schema.read().wrap_err()?;
+        Ok(schema.clone())
+    }
+
+    /// Set the schema of documents
+    ///
+    /// The documents are validated on `insert`, `put`, `update`, `upsert` and `load`,
+    /// the documents which does not match the schema are rejected with validation error.
+    ///
+    /// *Note*: The existing documents are not validated.
+    ///
+    pub fn set_schema(&self, schema: Schema) -> Result<()> {
+        let handle = self.handle();
+
+        let val = serde_json::to_string(&schema).wrap_err()?;
+
+        handle.storage.write(|txn| {
+            let mut access = txn.access();
+            access
+                .put(
+                    handle.storage.main_db(),
+                    handle.schema_key.as_str(),
+                    val.as_str(),
+                    PutFlags::empty(),
+                )
+                .wrap_err()
+        })?;
+
+        *handle.schema.write().wrap_err()? = Some(schema);
+
+        Ok(())
+    }
+
+    /// Remove the schema of documents
+    ///
+    /// Returns `false` when the collection has no schema.
+    ///
+    pub fn drop_schema(&self) -> Result<bool> {
+        let handle = self.handle();
+
+        if self.get_schema()?.is_none() {
+            return Ok(false);
+        }
+
+        handle.storage.write(|txn| {
+            let mut access = txn.access();
+            access
+                .del_key(handle.storage.main_db(), handle.schema_key.as_str())
+                .to_opt()
+                .wrap_err()
+        })?;
+
+        *handle.schema.write().wrap_err()? = None;
+
+        Ok(true)
+    }
+
+    /// Get the index for field which is ready to use
     pub(crate) fn get_index<P: AsRef<str>>(&self, path: P) -> Result<Option<Index>> {
+        Ok(self.find_index(path)?.filter(Index::is_ready))
+    }
+
+    /// Get the index for field even it isn't ready
+    fn find_index<P: AsRef<str>>(&self, path: P) -> Result<Option<Index>> {
         let path = path.as_ref();
 
         let handle = self.handle();
@@ -658,24 +1781,193 @@ impl Collection {
         if let Some(index) = self.get_index(&path)? {
             Ok(index)
         } else {
-            Err(format!("Missing index for field '{}'", path.as_ref())).wrap_err()
+            Err(self.missing_index(path))
         }
     }
 
-    pub(crate) fn to_delete(&self) -> Result<()> {
+    fn missing_index<P: AsRef<str>>(&self, path: P) -> Error {
+        Error::MissingIndex {
+            collection: self.name().into(),
+            field: path.as_ref().into(),
+        }
+    }
+
+    pub(crate) fn not_found(&self, id: Primary) -> Error {
+        Error::NotFound {
+            collection: self.name().into(),
+            id,
+        }
+    }
+
+    /// Get index for field or `None` when full scan should be used instead
+    ///
+    /// The index is used only when it is usable for operation.
+    ///
+    pub(crate) fn index_or_scan<P, F>(&self, path: P, usable: F) -> Result<Option<Index>>
+    where
+        P: AsRef<str>,
+        F: FnOnce(&Index) -> bool,
+    {
+        if let Some(index) = self.get_index(&path)?.filter(usable) {
+            Ok(Some(index))
+        } else if self.handle().storage.allow_full_scan() {
+            Ok(None)
+        } else {
+            Err(self.missing_index(path))
+        }
+    }
+
+    /// Select documents which passes the test by scanning collection
+    ///
+    /// When the set of ids is specified only that documents will be scanned.
+    ///
+    pub(crate) fn scan_txn<F>(
+        &self,
+        txn: &ConstTransaction,
+        ids: Option<&HashSet<Primary>>,
+        test: F,
+    ) -> Result<HashSet<Primary>>
+    where
+        F: Fn(&RawDocument) -> bool,
+    {
         let handle = self.handle();
 
-        let txn = WriteTransaction::new(handle.storage.clone()).wrap_err()?;
-        let mut access = txn.access();
+        let access = txn.access();
+        let mut out = HashSet::new();
+
+        if let Some(ids) = ids {
+            for id in ids {
+                if let Some(val) = access
+                    .get::<[u8], [u8]>(&handle.db, self.id_to_raw(id)?.as_slice())
+                    .to_opt()
+                    .wrap_err()?
+                {
+                    if test(&RawDocument::from_bin(val)?.with_id(id.clone())) {
+                        out.insert(id.clone());
+                    }
+                }
+            }
+        } else {
+            let cursor = txn.cursor(self.clone()).wrap_err()?;
+
+            for res in CursorIter::new(
+                MaybeOwned::Owned(cursor),
+                &access,
+                |c, a| c.first(a),
+                Cursor::next::<[u8], [u8]>,
+            )
+            .wrap_err()?
+            {
+                let (key, val) = res.wrap_err()?;
+                let id = self.id_from_raw(key)?;
+                if test(&RawDocument::from_bin(val)?.with_id(id.clone())) {
+                    out.insert(id);
+                }
+            }
+        }
 
-        let indexes = handle.indexes.read().wrap_err()?;
-        for index in indexes.iter() {
-            index.purge(&mut access)?;
-            index.to_delete(&mut access)?;
+        Ok(out)
+    }
+
+    pub(crate) fn read_txn(&self) -> Result<ReadTransaction<'static>> {
+        ReadTransaction::new(self.handle().storage.clone()).wrap_err()
+    }
+
+    pub(crate) fn shared_txn(&self) -> Result<SharedTxn> {
+        SharedTxn::new(self.handle().storage.clone())
+    }
+
+    /// Get the number of all documents in the collection
+    pub(crate) fn count_all_txn(&self, txn: &ConstTransaction) -> Result<usize> {
+        Ok(txn.db_stat(&self.handle().db).wrap_err()?.entries)
+    }
+
+    /// Visit the selected documents
+    ///
+    /// When the selection isn't specified all documents will be visited.
+    ///
+    pub(crate) fn visit_txn<F>(
+        &self,
+        txn: &ConstTransaction,
+        sel: Option<&Selection>,
+        mut visit: F,
+    ) -> Result<()>
+    where
+        F: FnMut(RawDocument) -> Result<()>,
+    {
+        let handle = self.handle();
+
+        let access = txn.access();
+
+        if let Some(Selection { ids, inv: false }) = sel {
+            for id in ids {
+                if let Some(val) = access
+                    .get::<[u8], [u8]>(&handle.db, self.id_to_raw(id)?.as_slice())
+                    .to_opt()
+                    .wrap_err()?
+                {
+                    visit(RawDocument::from_bin(val)?.with_id(id.clone()))?;
+                }
+            }
+        } else {
+            let cursor = txn.cursor(self.clone()).wrap_err()?;
+
+            for res in CursorIter::new(
+                MaybeOwned::Owned(cursor),
+                &access,
+                |c, a| c.first(a),
+                Cursor::next::<[u8], [u8]>,
+            )
+            .wrap_err()?
+            {
+                let (key, val) = res.wrap_err()?;
+                let id = self.id_from_raw(key)?;
+                if sel.map(|sel| sel.has(&id)).unwrap_or(true) {
+                    visit(RawDocument::from_bin(val)?.with_id(id))?;
+                }
+            }
         }
 
+        Ok(())
+    }
+
+    pub(crate) fn to_delete(&self) -> Result<()> {
+        let handle = self.handle();
+
+        handle.storage.write(|txn| {
+            let indexes = handle.indexes.read().wrap_err()?;
+            for index in indexes.iter() {
+                self.delete_index_txn(txn, index)?;
+            }
+
+            let mut access = txn.access();
+
+            access
+                .del_key(handle.storage.main_db(), handle.seq_key.as_str())
+                .to_opt()
+                .wrap_err()?;
+
+            access
+                .del_key(handle.storage.main_db(), handle.rev_key.as_str())
+                .to_opt()
+                .wrap_err()?;
+
+            access
+                .del_key(handle.storage.main_db(), handle.expiry_key.as_str())
+                .to_opt()
+                .wrap_err()?;
+
+            access
+                .del_key(handle.storage.main_db(), handle.schema_key.as_str())
+                .to_opt()
+                .wrap_err()?;
+
+            access.clear_db(&handle.db).wrap_err()
+        })?;
+
         handle.delete.store(true, AtomicOrdering::SeqCst);
-        access.clear_db(&handle.db).wrap_err()
+
+        Ok(())
     }
 }
 
@@ -725,50 +2017,101 @@ impl<'a> Into<Supercow<'a, Database<'a>>> for Collection {
     }
 }
 
-pub(crate) struct PrimaryIterator {
-    txn: Arc<ReadTransaction<'static>>,
-    cur: Cursor<'static, 'static>,
+/// Load the expiration of documents from the main database
+fn load_expiry(storage: &Storage, key: &str) -> Result<Option<Expiry>> {
+    let txn = ReadTransaction::new(storage.clone()).wrap_err()?;
+    let access = txn.access();
+
+    access
+        .get::<str, str>(storage.main_db(), key)
+        .to_opt()
+        .wrap_err()?
+        .map(|val| from_ron(val).wrap_err())
+        .transpose()
+}
+
+/// Load the schema of documents from the main database
+fn load_schema(storage: &Storage, key: &str) -> Result<Option<Schema>> {
+    let txn = ReadTransaction::new(storage.clone()).wrap_err()?;
+    let access = txn.access();
+
+    access
+        .get::<str, str>(storage.main_db(), key)
+        .to_opt()
+        .wrap_err()?
+        .map(|val| serde_json::from_str(val).wrap_err())
+        .transpose()
+}
+
+pub(crate) struct PrimaryIterator<'txn> {
+    txn: &'txn ConstTransaction<'txn>,
+    cur: Cursor<'txn, 'static>,
     order: OrderKind,
+    after: Option<Vec<u8>>,
+    primary: PrimaryType,
     init: bool,
 }
 
-impl PrimaryIterator {
+impl<'txn> PrimaryIterator<'txn> {
     pub(crate) fn new(
-        txn: Arc<ReadTransaction<'static>>,
+        txn: &'txn ConstTransaction<'txn>,
         coll: Collection,
         order: OrderKind,
+        after: Option<Primary>,
     ) -> Result<Self> {
+        let after = after.map(|id| coll.id_to_raw(&id)).transpose()?;
+        let primary = coll.primary_type();
         let cur = txn.cursor(coll)?;
 
         Ok(Self {
             txn,
             cur,
             order,
+            after,
+            primary,
             init: false,
         })
     }
 }
 
-impl Iterator for PrimaryIterator {
+impl<'txn> Iterator for PrimaryIterator<'txn> {
     type Item = Result<Primary>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let access = self.txn.access();
         match if self.init {
             match self.order {
-                OrderKind::Asc => self.cur.next::<Unaligned<Primary>, [u8]>(&access),
-                OrderKind::Desc => self.cur.prev::<Unaligned<Primary>, [u8]>(&access),
+                OrderKind::Asc => self.cur.next::<[u8], [u8]>(&access),
+                OrderKind::Desc => self.cur.prev::<[u8], [u8]>(&access),
+            }
+        } else if let Some(after) = self.after.take() {
+            self.init = true;
+            // seek to the first key which is greater than or equal to
+            match (
+                self.order,
+                self.cur
+                    .seek_range_k::<[u8], [u8]>(&access, after.as_slice())
+                    .to_opt(),
+            ) {
+                (OrderKind::Asc, Ok(Some((id, _)))) if id == after.as_slice() => {
+                    self.cur.next::<[u8], [u8]>(&access)
+                }
+                (OrderKind::Asc, Ok(Some(res))) => Ok(res),
+                (OrderKind::Desc, Ok(Some(_))) => self.cur.prev::<[u8], [u8]>(&access),
+                (OrderKind::Asc, Ok(None)) => return None,
+                (OrderKind::Desc, Ok(None)) => self.cur.last::<[u8], [u8]>(&access),
+                (_, Err(e)) => Err(e),
             }
         } else {
             self.init = true;
             match self.order {
-                OrderKind::Asc => self.cur.first::<Unaligned<Primary>, [u8]>(&access),
-                OrderKind::Desc => self.cur.last::<Unaligned<Primary>, [u8]>(&access),
+                OrderKind::Asc => self.cur.first::<[u8], [u8]>(&access),
+                OrderKind::Desc => self.cur.last::<[u8], [u8]>(&access),
             }
         }
         .to_opt()
         {
-            Ok(Some((id, _val))) => Some(Ok(id.get())),
+            Ok(Some((id, _val))) => Some(primary_from_raw(self.primary, id)),
             Ok(None) => None,
             Err(e) => Some(Err(e).wrap_err()),
         }
@@ -781,26 +2124,36 @@ impl Iterator for PrimaryIterator {
 ///
 /// The `DocumentsIterator::len()` method gets total number of found documents.
 ///
+/// The documents will be read using the same transaction which used to find it,
+/// so iterator sees the state of collection at the moment of query.
+///
 pub struct DocumentsIterator<T> {
-    storage: Storage,
+    txn: SharedTxn,
     coll: Collection,
     ids_iter: Box<dyn Iterator<Item = Primary> + Send>,
+    projector: Option<Projector>,
     phantom_doc: PhantomData<T>,
 }
 
 impl<T> DocumentsIterator<T> {
-    pub(crate) fn new<I>(storage: Storage, coll: Collection, ids_iter: I) -> Result<Self>
+    pub(crate) fn new<I>(txn: SharedTxn, coll: Collection, ids_iter: I) -> Result<Self>
     where
         I: IntoIterator<Item = Primary> + 'static,
         I::IntoIter: Send,
     {
         Ok(Self {
-            storage,
+            txn,
             coll,
             ids_iter: Box::new(ids_iter.into_iter()),
+            projector: None,
             phantom_doc: PhantomData,
         })
     }
+
+    pub(crate) fn project(mut self, projection: Option<&Projection>) -> Self {
+        self.projector = projection.map(Projection::compile);
+        self
+    }
 }
 
 impl<T> Iterator for DocumentsIterator<T>
@@ -810,18 +2163,21 @@ where
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let coll = &self.coll;
+        let projector = &self.projector;
         self.ids_iter.next().map(|id| {
-            let txn = ReadTransaction::new(self.storage.clone())?;
-            {
+            self.txn.with(|txn| {
                 let access = txn.access();
                 access
-                    .get(&self.coll, &Unaligned::new(id))
-                    .wrap_err()
+                    .get::<[u8], [u8]>(coll, coll.id_to_raw(&id)?.as_slice())
+                    .to_opt()
+                    .wrap_err()?
+                    .ok_or_else(|| coll.not_found(id.clone()))
                     .and_then(RawDocument::from_bin)
-                    .map(|doc| doc.with_id(id))
+                    .map(|doc| project_doc(projector.as_ref(), doc).with_id(id))
                     .and_then(RawDocument::into_doc)
                     .wrap_err()
-            }
+            })
         })
     }
 
@@ -832,12 +2188,10 @@ where
 
 impl<T> ExactSizeIterator for DocumentsIterator<T> where T: DeserializeOwned + Document {}
 
-#[allow(clippy::trivially_copy_pass_by_ref)]
 fn order_primary_asc(a: &Primary, b: &Primary) -> Ordering {
     a.cmp(b)
 }
 
-#[allow(clippy::trivially_copy_pass_by_ref)]
 fn order_primary_desc(a: &Primary, b: &Primary) -> Ordering {
     b.cmp(a)
 }
@@ -1,12 +1,16 @@
-use std::{
-    convert::TryInto,
-    ops::{Deref, DerefMut},
-};
+use std::ops::{Deref, DerefMut};
 
 use serde::{de::DeserializeOwned, Serialize};
 pub use serde_cbor::Value;
 
-use super::{Document, Primary, Result, ResultWrap};
+use super::{Document, Error, Primary, Result, ResultWrap};
+
+/// Document revision
+///
+/// The revision grows on each change of document.
+/// The revisions is allocated from the per-collection sequence, so it is never reused.
+///
+pub type Revision = u64;
 
 /// Raw document with id representation
 #[derive(Debug, Clone, PartialEq)]
@@ -48,9 +52,7 @@ impl RawDocument {
     /// Require the primary key/identifier of document
     #[inline]
     pub fn req_id(&self) -> Result<Primary> {
-        self.get_id()
-            .ok_or_else(|| "Missing document id")
-            .wrap_err()
+        self.get_id().clone().ok_or(Error::MissingId)
     }
 
     /// Unwrap document value
@@ -74,7 +76,33 @@ impl RawDocument {
     /// Since the internal representation does not contains primary identifier, it adds on reading documents from DB.
     ///
     pub fn from_bin(raw: &[u8]) -> Result<Self> {
-        serde_cbor::from_slice(raw).map(Self::new).wrap_err()
+        Self::from_bin_with_rev(raw).map(|(doc, _rev)| doc)
+    }
+
+    /// Convert document with revision to binary representation
+    ///
+    /// The revision is stored as CBOR unsigned integer before the document body.
+    ///
+    pub fn to_bin_with_rev(&self, rev: Revision) -> Result<Vec<u8>> {
+        let mut raw = serde_cbor::to_vec(&rev).wrap_err()?;
+        serde_cbor::to_writer(&mut raw, &self.1).wrap_err()?;
+        Ok(raw)
+    }
+
+    /// Restore document with revision from binary representation
+    ///
+    /// The documents which stored without revision has first revision.
+    ///
+    pub fn from_bin_with_rev(raw: &[u8]) -> Result<(Self, Revision)> {
+        let mut items = serde_cbor::Deserializer::from_slice(raw).into_iter::<Value>();
+        let item = items.next().ok_or("Missing document data").wrap_err()?;
+        Ok(match item.wrap_err()? {
+            Value::Integer(rev) if rev >= 0 => {
+                let doc = items.next().ok_or("Missing document data").wrap_err()?;
+                (Self::new(doc.wrap_err()?), rev as Revision)
+            }
+            doc => (Self::new(doc), 1),
+        })
     }
 
     /// Convert typed document to raw representation
@@ -97,8 +125,13 @@ impl RawDocument {
         let id = match id {
             None => None,
             Some(Value::Null) => None,
-            Some(Value::Integer(id)) => Some(id as u32),
-            _ => return Err("Document primary must be an integer").wrap_err(),
+            Some(Value::Integer(id)) if id >= 0 => Some(Primary::Int(id as u64)),
+            Some(Value::Text(id)) => Some(Primary::String(id)),
+            Some(Value::Bytes(id)) => Some(Primary::Binary(id)),
+            _ => {
+                return Err("Document primary must be an unsigned integer, string or binary")
+                    .wrap_err()
+            }
         };
 
         Ok(RawDocument(id, raw))
@@ -117,7 +150,11 @@ impl RawDocument {
             if let Some(id) = &id {
                 obj.insert(
                     Value::Text(T::primary_field().as_ref().into()),
-                    Value::Integer(u64::from(*id).try_into().unwrap()),
+                    match id {
+                        Primary::Int(id) => Value::Integer(i128::from(*id)),
+                        Primary::String(id) => Value::Text(id.clone()),
+                        Primary::Binary(id) => Value::Bytes(id.clone()),
+                    },
                 );
             }
         } else {
@@ -197,11 +234,32 @@ mod test {
         assert_eq!(res.to_bin().unwrap(), raw.to_bin().unwrap());
     }
 
+    #[test]
+    fn rev_doc() {
+        let src = User {
+            id: None,
+            name: "elen".into(),
+            email: None,
+        };
+        let raw = RawDocument::from_doc(&src).unwrap();
+
+        let bin = raw.to_bin_with_rev(3).unwrap();
+        assert_eq!(
+            RawDocument::from_bin_with_rev(&bin).unwrap(),
+            (raw.clone(), 3)
+        );
+        assert_eq!(RawDocument::from_bin(&bin).unwrap(), raw);
+
+        // stored without revision
+        let bin = raw.to_bin().unwrap();
+        assert_eq!(RawDocument::from_bin_with_rev(&bin).unwrap(), (raw, 1));
+    }
+
     #[test]
     #[ignore]
     fn duplicate_id() {
         let src = User {
-            id: Some(1),
+            id: Some(1.into()),
             name: "ivan".into(),
             email: None,
         };
@@ -7,9 +7,12 @@ use std::{
     error::Error as StdError,
 };
 
-use lmdb::error::Error as DbError;
+use lmdb::error::{Error as DbError, MAP_FULL};
 use ron::Error as RonError;
 use serde_cbor::error::Error as CborError;
+use serde_json::Error as JsonError;
+
+use super::{Invalid, KeyData, Primary, Revision};
 
 /// Database error type
 #[derive(Debug)]
@@ -18,9 +21,97 @@ pub enum Error {
     DbError(DbError),
     StrError(Utf8Error),
     DataError(CborError),
+    JsonError(JsonError),
     StorageError(String),
     IoError(IoError),
     SyncError(String),
+    ConflictError(Conflict),
+    ValidationError(Invalid),
+    /// The field has no index which is required for operation
+    MissingIndex {
+        collection: String,
+        field: String,
+    },
+    /// The key already exists in unique index
+    DuplicateKey {
+        collection: String,
+        field: String,
+        key: KeyData,
+    },
+    /// The document with same primary key/identifier already exists
+    DuplicateId {
+        collection: String,
+        id: Primary,
+    },
+    /// The document does not exists
+    NotFound {
+        collection: String,
+        id: Primary,
+    },
+    /// The document has no primary key/identifier
+    MissingId,
+    /// The storage is full (the max size of memory map is reached)
+    MapFull,
+}
+
+/// Revision conflict of document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The primary key/identifier of document
+    pub id: Primary,
+    /// The expected revision of document
+    pub expected: Revision,
+    /// The actual revision of document (`None` when document does not exists)
+    pub actual: Option<Revision>,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.actual {
+            Some(actual) => write!(
+                f,
+                "Document {} has revision {} but {} expected",
+                self.id, actual, self.expected
+            ),
+            None => write!(
+                f,
+                "Document {} does not exists but revision {} expected",
+                self.id, self.expected
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// Checks that the error is revision conflict
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Error::ConflictError(_))
+    }
+
+    /// Checks that the error is schema violation
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, Error::ValidationError(_))
+    }
+
+    /// Checks that the error is duplicate key of unique index or primary key/identifier
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self, Error::DuplicateKey { .. } | Error::DuplicateId { .. })
+    }
+
+    /// Checks that the error is missing document
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound { .. })
+    }
+
+    /// Checks that the error is missing index
+    pub fn is_missing_index(&self) -> bool {
+        matches!(self, Error::MissingIndex { .. })
+    }
+
+    /// Checks that the storage is full
+    pub fn is_map_full(&self) -> bool {
+        matches!(self, Error::MapFull)
+    }
 }
 
 impl Display for Error {
@@ -31,9 +122,38 @@ impl Display for Error {
             DbError(e) => write!(f, "Database error: {}", e),
             StrError(e) => write!(f, "String error: {}", e),
             DataError(e) => write!(f, "Data coding error: {}", e),
+            JsonError(e) => write!(f, "JSON coding error: {}", e),
             StorageError(s) => write!(f, "Storage error: {}", s),
             IoError(e) => write!(f, "I/O Error: {}", e),
             SyncError(s) => write!(f, "Sync error: {}", s),
+            ConflictError(e) => write!(f, "Conflict error: {}", e),
+            ValidationError(e) => write!(f, "Validation error: {}", e),
+            MissingIndex { collection, field } => write!(
+                f,
+                "Missing index for field '{}' of collection '{}'",
+                field, collection
+            ),
+            DuplicateKey {
+                collection,
+                field,
+                key,
+            } => write!(
+                f,
+                "Duplicate key {} of unique index for field '{}' of collection '{}'",
+                key, field, collection
+            ),
+            DuplicateId { collection, id } => write!(
+                f,
+                "Document with id {} already exists in collection '{}'",
+                id, collection
+            ),
+            NotFound { collection, id } => write!(
+                f,
+                "Document with id {} not found in collection '{}'",
+                id, collection
+            ),
+            MissingId => write!(f, "Missing document id"),
+            MapFull => write!(f, "Storage is full"),
         }
     }
 }
@@ -46,9 +166,14 @@ impl StdError for Error {
             DbError(e) => Some(e),
             StrError(e) => Some(e),
             DataError(e) => Some(e),
+            JsonError(e) => Some(e),
             StorageError(_) => None,
             IoError(e) => Some(e),
             SyncError(_) => None,
+            ConflictError(_) => None,
+            ValidationError(_) => None,
+            MissingIndex { .. } | DuplicateKey { .. } | DuplicateId { .. } | NotFound { .. } => None,
+            MissingId | MapFull => None,
         }
     }
 }
@@ -68,6 +193,12 @@ impl From<CborError> for Error {
     }
 }
 
+impl From<JsonError> for Error {
+    fn from(e: JsonError) -> Self {
+        Error::JsonError(e)
+    }
+}
+
 impl From<RonError> for Error {
     fn from(e: RonError) -> Self {
         Error::StorageError(format!("{}", e))
@@ -76,7 +207,10 @@ impl From<RonError> for Error {
 
 impl From<DbError> for Error {
     fn from(e: DbError) -> Self {
-        Error::DbError(e)
+        match e {
+            DbError::Code(MAP_FULL) => Error::MapFull,
+            e => Error::DbError(e),
+        }
     }
 }
 
@@ -101,6 +235,18 @@ impl From<Utf8Error> for Error {
     }
 }
 
+impl From<Conflict> for Error {
+    fn from(e: Conflict) -> Self {
+        Error::ConflictError(e)
+    }
+}
+
+impl From<Invalid> for Error {
+    fn from(e: Invalid) -> Self {
+        Error::ValidationError(e)
+    }
+}
+
 impl From<String> for Error {
     fn from(e: String) -> Self {
         Error::DocError(e)
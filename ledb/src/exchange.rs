@@ -0,0 +1,325 @@
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Collection, KeyFields, Order, PrimaryType, RawDocument, Result, ResultWrap, Storage, Value,
+};
+
+/// The number of documents which written in single transaction on import
+const BATCH_SIZE: usize = 1000;
+
+/// The format of exchange data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DataFormat {
+    /// [JSON Lines](http://jsonlines.org/)
+    ///
+    /// *Note*: Binary values will be represented as arrays of numbers.
+    ///
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    /// The sequence of [CBOR](https://cbor.io/) items
+    #[serde(rename = "cbor")]
+    Cbor,
+}
+
+/// The mode of import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ImportMode {
+    /// Add documents to collections
+    ///
+    /// The import fails when the document with same primary key already exists.
+    ///
+    #[default]
+    #[serde(rename = "append")]
+    Append,
+    /// Remove old documents from collections before adding new
+    #[serde(rename = "replace")]
+    Replace,
+    /// Add new and overwrite existing documents with same primary key
+    #[serde(rename = "upsert")]
+    Upsert,
+}
+
+/// Export options
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportOptions {
+    /// The format of data
+    #[serde(default)]
+    pub format: DataFormat,
+    /// Export selected collections only
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
+}
+
+impl ExportOptions {
+    pub fn format(mut self, format: DataFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn collection<S: Into<String>>(mut self, name: S) -> Self {
+        self.collections
+            .get_or_insert_with(Vec::new)
+            .push(name.into());
+        self
+    }
+}
+
+/// Import options
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// The format of data
+    #[serde(default)]
+    pub format: DataFormat,
+    /// The mode of import
+    #[serde(default)]
+    pub mode: ImportMode,
+    /// Import selected collections only
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
+}
+
+impl ImportOptions {
+    pub fn format(mut self, format: DataFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn mode(mut self, mode: ImportMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn collection<S: Into<String>>(mut self, name: S) -> Self {
+        self.collections
+            .get_or_insert_with(Vec::new)
+            .push(name.into());
+        self
+    }
+}
+
+/// Progress of export or import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    /// The name of collection
+    pub collection: String,
+    /// The number of processed documents of collection
+    pub documents: usize,
+}
+
+/// The collection header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CollectionInfo {
+    name: String,
+    #[serde(default)]
+    primary: PrimaryType,
+    #[serde(default)]
+    indexes: KeyFields,
+}
+
+/// The item of exchange data
+///
+/// The documents follows the header of collection which it belongs to.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Record {
+    #[serde(rename = "collection")]
+    Collection(CollectionInfo),
+    #[serde(rename = "document")]
+    Document(Value),
+}
+
+fn write_record<W: Write>(out: &mut W, format: DataFormat, record: &Record) -> Result<()> {
+    match format {
+        DataFormat::Json => {
+            serde_json::to_writer(&mut *out, record).wrap_err()?;
+            out.write_all(b"\n").wrap_err()
+        }
+        DataFormat::Cbor => serde_cbor::to_writer(out, record).wrap_err(),
+    }
+}
+
+fn read_records<'r, R: Read + 'r>(
+    inp: R,
+    format: DataFormat,
+) -> Box<dyn Iterator<Item = Result<Record>> + 'r> {
+    match format {
+        DataFormat::Json => Box::new(
+            serde_json::Deserializer::from_reader(inp)
+                .into_iter()
+                .map(|res| res.wrap_err()),
+        ),
+        DataFormat::Cbor => Box::new(
+            serde_cbor::Deserializer::from_reader(inp)
+                .into_iter()
+                .map(|res| res.wrap_err()),
+        ),
+    }
+}
+
+fn is_selected(collections: &Option<Vec<String>>, name: &str) -> bool {
+    collections
+        .as_ref()
+        .map(|names| names.iter().any(|selected| selected == name))
+        .unwrap_or(true)
+}
+
+impl Storage {
+    /// Export collections with documents and indexes
+    ///
+    /// All collections are read from the same snapshot, so the exported data is consistent.
+    /// The progress callback is called periodically for each collection.
+    ///
+    /// Returns the total number of exported documents.
+    ///
+    pub fn export<W, F>(&self, mut out: W, opts: &ExportOptions, mut progress: F) -> Result<usize>
+    where
+        W: Write,
+        F: FnMut(&Progress),
+    {
+        let snapshot = self.snapshot()?;
+        let mut total = 0;
+
+        for name in self.get_collections()? {
+            if !is_selected(&opts.collections, &name) {
+                continue;
+            }
+
+            let coll = self.collection(&name)?;
+
+            write_record(
+                &mut out,
+                opts.format,
+                &Record::Collection(CollectionInfo {
+                    name: name.clone(),
+                    primary: coll.primary_type(),
+                    indexes: coll.get_indexes()?,
+                }),
+            )?;
+
+            let mut state = Progress {
+                collection: name,
+                documents: 0,
+            };
+
+            for doc in snapshot
+                .collection(&state.collection)?
+                .find::<Value>(None, Order::default())?
+            {
+                write_record(&mut out, opts.format, &Record::Document(doc?))?;
+
+                state.documents += 1;
+                if state.documents.is_multiple_of(BATCH_SIZE) {
+                    progress(&state);
+                }
+            }
+
+            progress(&state);
+            total += state.documents;
+        }
+
+        out.flush().wrap_err()?;
+
+        Ok(total)
+    }
+
+    /// Import collections with documents and indexes
+    ///
+    /// The missing collections and indexes will be created.
+    /// The documents are written in batches, so the import may be stopped partially on error.
+    /// The progress callback is called after each batch.
+    ///
+    /// Returns the total number of imported documents.
+    ///
+    pub fn import<R, F>(&self, inp: R, opts: &ImportOptions, mut progress: F) -> Result<usize>
+    where
+        R: Read,
+        F: FnMut(&Progress),
+    {
+        let mut current: Option<(Collection, Progress)> = None;
+        let mut batch = Vec::new();
+        let mut total = 0;
+
+        for record in read_records(inp, opts.format) {
+            match record? {
+                Record::Collection(info) => {
+                    if let Some((coll, mut state)) = current.take() {
+                        let count = self.import_batch(&coll, &mut batch, opts.mode)?;
+                        state.documents += count;
+                        total += count;
+                        progress(&state);
+                    }
+
+                    if !is_selected(&opts.collections, &info.name) {
+                        continue;
+                    }
+
+                    let coll = self.collection_with(&info.name, info.primary)?;
+
+                    if opts.mode == ImportMode::Replace {
+                        coll.purge()?;
+                    }
+
+                    coll.set_indexes(&info.indexes)?;
+
+                    current = Some((
+                        coll,
+                        Progress {
+                            collection: info.name,
+                            documents: 0,
+                        },
+                    ));
+                }
+                Record::Document(doc) => {
+                    if let Some((coll, state)) = &mut current {
+                        batch.push(RawDocument::from_doc(&doc)?);
+
+                        if batch.len() == BATCH_SIZE {
+                            let count = self.import_batch(coll, &mut batch, opts.mode)?;
+                            state.documents += count;
+                            total += count;
+                            progress(state);
+                        }
+                    } else if opts.collections.is_none() {
+                        return Err("Missing collection of document").wrap_err();
+                    }
+                }
+            }
+        }
+
+        if let Some((coll, mut state)) = current {
+            let count = self.import_batch(&coll, &mut batch, opts.mode)?;
+            state.documents += count;
+            total += count;
+            progress(&state);
+        }
+
+        Ok(total)
+    }
+
+    fn import_batch(
+        &self,
+        coll: &Collection,
+        batch: &mut Vec<RawDocument>,
+        mode: ImportMode,
+    ) -> Result<usize> {
+        let count = batch.len();
+
+        self.write(|txn| {
+            for doc in batch.iter().cloned() {
+                if mode == ImportMode::Append {
+                    coll.insert_with_id_txn(txn, doc)?;
+                } else {
+                    coll.put_txn(txn, doc)?;
+                }
+            }
+            Ok(())
+        })?;
+
+        batch.clear();
+
+        Ok(count)
+    }
+}
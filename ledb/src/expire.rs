@@ -0,0 +1,147 @@
+use std::{
+    sync::{
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Weak,
+    },
+    thread::{spawn, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lmdb::WriteTransaction;
+use serde::{Deserialize, Serialize};
+
+use super::{Collection, Comp, Cond, Filter, KeyData, RawDocument, Result, StorageData};
+
+/// Expiration of documents
+///
+/// The document expires when `ttl` seconds passed since the timestamp in the `field`.
+/// The timestamp is a number of seconds since UNIX epoch.
+///
+/// With zero `ttl` the field keeps the time of expiration itself.
+///
+/// The documents without timestamp never expires.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expiry {
+    /// The field with timestamp
+    pub field: String,
+    /// The time to live in seconds
+    #[serde(default)]
+    pub ttl: u64,
+}
+
+impl Expiry {
+    pub fn new<F: ToString>(field: F, ttl: Duration) -> Self {
+        Self {
+            field: field.to_string(),
+            ttl: ttl.as_secs(),
+        }
+    }
+
+    /// Get the time to live
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl)
+    }
+
+    /// Get the filter which selects the documents expired to the moment
+    pub(crate) fn expired(&self) -> Filter {
+        let deadline = unix_time() as i64 - self.ttl as i64;
+        Filter::comp(self.field.clone(), Comp::Le(KeyData::Int(deadline)))
+    }
+}
+
+/// Get the current time in seconds since UNIX epoch
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+impl Collection {
+    /// Remove expired documents from the collection
+    ///
+    /// Returns the number of removed documents.
+    ///
+    pub fn sweep(&self) -> Result<usize> {
+        if self.get_expiry()?.is_none() {
+            return Ok(0);
+        }
+
+        self.storage().write(|txn| self.sweep_txn(txn))
+    }
+
+    pub(crate) fn sweep_txn(&self, txn: &WriteTransaction) -> Result<usize> {
+        let expiry = if let Some(expiry) = self.get_expiry()? {
+            expiry
+        } else {
+            return Ok(0);
+        };
+
+        let sel = expiry.expired().apply(txn, self)?;
+        let mut count = 0;
+
+        self.remove_ids_txn(txn, sel.ids, |_old_doc| {
+            count += 1;
+        })?;
+
+        Ok(count)
+    }
+
+    /// Restrict the filter to the documents which isn't expired yet
+    pub(crate) fn live_filter(&self, filter: Option<Filter>) -> Result<Option<Filter>> {
+        Ok(if let Some(expiry) = self.get_expiry()? {
+            let live = Filter::Cond(Cond::Not(Box::new(expiry.expired())));
+            Some(if let Some(filter) = filter {
+                Filter::Cond(Cond::And(vec![filter, live]))
+            } else {
+                live
+            })
+        } else {
+            filter
+        })
+    }
+
+    /// Checks that the document is expired
+    pub(crate) fn is_expired(&self, doc: &RawDocument) -> Result<bool> {
+        Ok(self
+            .get_expiry()?
+            .map(|expiry| expiry.expired().test(doc))
+            .unwrap_or(false))
+    }
+}
+
+/// The background thread which removes expired documents
+pub(crate) struct Sweeper {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Sweeper {
+    pub(crate) fn start(storage: Weak<StorageData>, interval: Duration) -> Self {
+        let (stop, wait) = channel();
+
+        let thread = spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = wait.recv_timeout(interval) {
+                let storage = if let Some(storage) = storage.upgrade() {
+                    storage
+                } else {
+                    break;
+                };
+                if let Err(e) = storage.sweep() {
+                    eprintln!("Error when sweeping expired documents: {}", e);
+                }
+            }
+        });
+
+        Sweeper { stop, thread }
+    }
+
+    pub(crate) fn stop(self) {
+        let Sweeper { stop, thread } = self;
+        drop(stop);
+        if thread.join().is_err() {
+            eprintln!("Error when stopping sweeper");
+        }
+    }
+}
@@ -1,9 +1,14 @@
-use std::iter::once;
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    iter::once,
+};
 
 use serde::{Serialize, Deserialize};
-use lmdb::{ReadTransaction};
+use lmdb::ConstTransaction;
+use ordered_float::OrderedFloat;
 
-use super::{Identifier, Result, KeyData, Selection, Collection};
+use super::{Identifier, Index, Result, KeyData, KeyType, Selection, Collection, Primary, Projection, RawDocument, Terms, TextQuery, WrappedRegex, GeoBox, GeoPoint, extract_field_values, extract_points, tokenize};
 
 /// Comparison operator of filter
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -32,6 +37,127 @@ pub enum Comp {
     /// Field exists (not null)
     #[serde(rename = "$has")]
     Has,
+    /// Starts with string
+    #[serde(rename = "$prefix")]
+    Prefix(String),
+    /// Matches regular expression
+    #[serde(rename = "$regex")]
+    Regex(WrappedRegex),
+    /// Equal ignoring case
+    #[serde(rename = "$ieq")]
+    IEq(String),
+    /// Starts with string ignoring case
+    #[serde(rename = "$iprefix")]
+    IPrefix(String),
+    /// Matches full-text query
+    #[serde(rename = "$match")]
+    Match(String),
+    /// Point inside the box
+    #[serde(rename = "$within")]
+    Within(GeoBox),
+    /// Point inside the circle with radius in meters
+    #[serde(rename = "$near")]
+    Near(GeoPoint, OrderedFloat<f64>),
+}
+
+impl Comp {
+    /// Checks the values of document field
+    fn test(&self, keys: &HashSet<KeyData>) -> bool {
+        use self::Comp::*;
+        use self::Ordering::*;
+        let any = |val: &KeyData, ords: &[Ordering]| keys.iter()
+            .any(|key| cmp_key(key, val).map(|ord| ords.contains(&ord)).unwrap_or(false));
+        match self {
+            Eq(val) => any(val, &[Equal]),
+            In(vals) => vals.iter().any(|val| any(val, &[Equal])),
+            Lt(val) => any(val, &[Less]),
+            Le(val) => any(val, &[Less, Equal]),
+            Gt(val) => any(val, &[Greater]),
+            Ge(val) => any(val, &[Greater, Equal]),
+            Bw(val1, inc1, val2, inc2) => keys.iter().any(|key| {
+                match (cmp_key(key, val1), cmp_key(key, val2)) {
+                    (Some(ord1), Some(ord2)) => (ord1 == Greater || *inc1 && ord1 == Equal) &&
+                        (ord2 == Less || *inc2 && ord2 == Equal),
+                    _ => false,
+                }
+            }),
+            Has => !keys.is_empty(),
+            Prefix(val) => any_str(keys, KeyType::String, |key| key.starts_with(val.as_str())),
+            Regex(val) => any_str(keys, KeyType::String, |key| val.is_match(key)),
+            IEq(val) => {
+                let val = val.to_lowercase();
+                any_str(keys, KeyType::Folded, |key| key == val)
+            }
+            IPrefix(val) => {
+                let val = val.to_lowercase();
+                any_str(keys, KeyType::Folded, |key| key.starts_with(val.as_str()))
+            }
+            Match(val) => {
+                let mut terms = Terms::new();
+                for key in keys {
+                    if let Some(KeyData::String(text)) = key.to_type(KeyType::String).as_ref().map(AsRef::as_ref) {
+                        terms.extend(tokenize(text).map(|term| (term, 1)));
+                    }
+                }
+                TextQuery::parse(val).score(&terms).is_some()
+            }
+            // the points is extracted from document separately
+            Within(..) | Near(..) => false,
+        }
+    }
+
+    /// Checks the points of document field
+    fn test_points(&self, points: &[GeoPoint]) -> bool {
+        use self::Comp::*;
+        match self {
+            Within(area) => points.iter().any(|point| area.contains(point)),
+            Near(center, OrderedFloat(radius)) => points.iter().any(|point| center.distance(point) <= *radius),
+            _ => false,
+        }
+    }
+
+    fn is_spatial(&self) -> bool {
+        matches!(self, Comp::Within(..) | Comp::Near(..))
+    }
+
+    /// Checks that the comparison can be done using index
+    ///
+    /// The string operations requires index with string keys,
+    /// the case-insensitive operations requires folded keys.
+    /// The case-sensitive operations cannot be done using folded keys, except of checking presence.
+    /// The full-text index is usable for full-text queries only,
+    /// the spatial index is usable for spatial queries only.
+    ///
+    fn is_indexed_by(&self, index: &Index) -> bool {
+        use self::Comp::*;
+        if index.is_fulltext() {
+            return matches!(self, Match(..));
+        }
+        if index.is_spatial() {
+            return self.is_spatial();
+        }
+        match (self, index.key()) {
+            (Prefix(..), KeyType::String) | (Regex(..), KeyType::String) => !index.is_compound(),
+            (IEq(..), KeyType::Folded) | (IPrefix(..), KeyType::Folded) => !index.is_compound(),
+            (Prefix(..), _) | (Regex(..), _) | (IEq(..), _) | (IPrefix(..), _) | (Match(..), _) => false,
+            (Within(..), _) | (Near(..), _) => false,
+            (Has, _) => true,
+            (_, key) => key != KeyType::Folded,
+        }
+    }
+}
+
+/// Checks that any of values converted to string passes the test
+fn any_str<F: Fn(&str) -> bool>(keys: &HashSet<KeyData>, typ: KeyType, test: F) -> bool {
+    keys.iter().any(|key| match key.to_type(typ).as_ref().map(AsRef::as_ref) {
+        Some(KeyData::String(key)) => test(key),
+        _ => false,
+    })
+}
+
+/// Compare document value with filter value using type of filter value
+fn cmp_key(key: &KeyData, val: &KeyData) -> Option<Ordering> {
+    key.to_type(val.get_type()).map(|key| key.as_ref().cmp(val))
 }
 
 /// Condition operator of filter
@@ -59,6 +185,19 @@ pub enum Filter {
     Comp(Identifier, Comp),
 }
 
+/// Restrict selection by the set of ids
+fn restrict(sel: Selection, within: Option<&HashSet<Primary>>) -> Selection {
+    if let Some(ids) = within {
+        Selection::new(if sel.inv {
+            ids.difference(&sel.ids).cloned().collect()
+        } else {
+            sel.ids.intersection(ids).cloned().collect()
+        }, false)
+    } else {
+        sel
+    }
+}
+
 impl Filter {
     pub fn cond(cond: Cond) -> Self {
         Filter::Cond(cond)
@@ -68,30 +207,85 @@ impl Filter {
         Filter::Comp(field.into(), comp)
     }
 
-    pub(crate) fn apply(&self, txn: &ReadTransaction<'static>, coll: &Collection) -> Result<Selection> {
+    pub(crate) fn apply(&self, txn: &ConstTransaction, coll: &Collection) -> Result<Selection> {
+        self.apply_within(txn, coll, None)
+    }
+
+    /// Checks that the document passes the filter
+    pub(crate) fn test(&self, doc: &RawDocument) -> bool {
+        match self {
+            Filter::Cond(Cond::Not(filter)) => !filter.test(doc),
+            Filter::Cond(Cond::And(filters)) => filters.iter().all(|filter| filter.test(doc)),
+            Filter::Cond(Cond::Or(filters)) => filters.iter().any(|filter| filter.test(doc)),
+            Filter::Comp(path, comp) if comp.is_spatial() => comp.test_points(&extract_points(doc, path)),
+            Filter::Comp(path, comp) => {
+                let mut keys = HashSet::new();
+                extract_field_values(doc, None, &path.split('.'), &mut keys);
+                comp.test(&keys)
+            },
+        }
+    }
+
+    /// Checks that filter can be applied using indexes only
+    fn is_indexed(&self, coll: &Collection) -> Result<bool> {
+        Ok(match self {
+            Filter::Cond(Cond::Not(filter)) => filter.is_indexed(coll)?,
+            Filter::Cond(Cond::And(filters)) | Filter::Cond(Cond::Or(filters)) => {
+                for filter in filters {
+                    if !filter.is_indexed(coll)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            },
+            Filter::Comp(path, comp) => coll
+                .get_index(path)?
+                .map(|index| comp.is_indexed_by(&index))
+                .unwrap_or(false),
+        })
+    }
+
+    /// Apply filter
+    ///
+    /// When the set of ids is specified the result is valid only within this set.
+    /// It used to reduce the number of documents which should be scanned for unindexed fields.
+    ///
+    fn apply_within(&self, txn: &ConstTransaction, coll: &Collection, within: Option<&HashSet<Primary>>) -> Result<Selection> {
         match self {
             Filter::Cond(cond) => {
                 use self::Cond::*;
                 Ok(match cond {
-                    Not(filter) => !filter.apply(txn, coll)?,
+                    Not(filter) => restrict(!filter.apply_within(txn, coll, within)?, within),
                     And(filters) => {
+                        // apply indexed filters first to scan less documents for unindexed
+                        let mut filters = filters.iter()
+                            .map(|filter| filter.is_indexed(coll).map(|indexed| (!indexed, filter)))
+                            .collect::<Result<Vec<_>>>()?;
+                        filters.sort_by_key(|(unindexed, _)| *unindexed);
+
                         let mut res = !Selection::default(); // universe
-                        for filter in filters {
-                            res = res & filter.apply(txn, coll)?;
+                        for (_, filter) in filters {
+                            let sel = filter.apply_within(txn, coll, if res.inv { within } else { Some(&res.ids) })?;
+                            res = res & sel;
                         }
                         res
                     },
                     Or(filters) => {
                         let mut res = Selection::default(); // empty
                         for filter in filters {
-                            res = res | filter.apply(txn, coll)?;
+                            res = res | filter.apply_within(txn, coll, within)?;
                         }
                         res
                     },
                 })
             },
             Filter::Comp(path, comp) => {
-                let index = coll.req_index(path)?;
+                let index = if let Some(index) = coll.index_or_scan(path, |index| comp.is_indexed_by(index))? {
+                    index
+                } else {
+                    // the field has no index so scan the documents
+                    return Ok(Selection::new(coll.scan_txn(txn, within, |doc| self.test(doc))?, false));
+                };
                 let access = txn.access();
                 use self::Comp::*;
                 Ok(match comp {
@@ -103,6 +297,15 @@ impl Filter {
                     Le(val) => Selection::new(index.query_range(&txn, &access, None, Some((val, true)))?, false),
                     Bw(val1, inc1, val2, inc2) => Selection::new(index.query_range(&txn, &access, Some((val1, *inc1)), Some((val2, *inc2)))?, false),
                     Has => Selection::new(index.query_range(&txn, &access, None, None)?, false),
+                    Prefix(val) | IPrefix(val) => Selection::new(index.query_prefix(txn, &access, val)?, false),
+                    Regex(val) => Selection::new(index.query_keys(txn, &access, |key| match key {
+                        KeyData::String(key) => val.is_match(key),
+                        _ => false,
+                    })?, false),
+                    IEq(val) => Selection::new(index.query_set(txn, &access, once(&KeyData::String(val.clone())))?, false),
+                    Match(val) => Selection::new(index.query_text(txn, &access, &TextQuery::parse(val))?.into_keys().collect(), false),
+                    Within(area) => Selection::new(index.query_within(txn, &access, area)?, false),
+                    Near(center, OrderedFloat(radius)) => Selection::new(index.query_near(txn, &access, center, *radius)?.into_keys().collect(), false),
                 })
             },
         }
@@ -137,12 +340,64 @@ pub enum Order {
     /// Order by specified indexed field
     #[serde(with = "order")]
     Field(Identifier, OrderKind),
+
+    /// Order by distance from the point to the points of field
+    ///
+    /// The nearest documents goes first, the documents without points are omitted.
+    ///
+    #[serde(with = "order_near")]
+    Near(Identifier, GeoPoint),
 }
 
 impl Default for Order {
     fn default() -> Self { Order::Primary(OrderKind::default()) }
 }
 
+/// The options of find query
+///
+/// The `after` option allows continue iteration next to the document with specified primary key/identifier,
+/// unlike the `offset` it stays valid when new documents was inserted.
+///
+/// The `projection` option allows get only the required fields of documents.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindOptions {
+    /// The number of documents to skip
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// The max number of documents to get
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// The primary key/identifier of document to start next to
+    #[serde(default)]
+    pub after: Option<Primary>,
+    /// The fields of documents to get
+    #[serde(default)]
+    pub projection: Option<Projection>,
+}
+
+impl FindOptions {
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn after(mut self, id: impl Into<Primary>) -> Self {
+        self.after = Some(id.into());
+        self
+    }
+
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+}
+
 impl Order {
     pub fn primary(kind: OrderKind) -> Self {
         Order::Primary(kind)
@@ -151,6 +406,10 @@ impl Order {
     pub fn field<F: Into<Identifier>>(field: F, kind: OrderKind) -> Self {
         Order::Field(field.into(), kind)
     }
+
+    pub fn near<F: Into<Identifier>, P: Into<GeoPoint>>(field: F, point: P) -> Self {
+        Order::Near(field.into(), point.into())
+    }
 }
 
 mod comp {
@@ -196,9 +455,36 @@ mod order {
     }
 }
 
+mod order_near {
+    use super::{Identifier, GeoPoint};
+    use std::collections::HashMap;
+    use serde::{Serializer, Deserializer, Serialize, Deserialize, de::{self}, ser::{SerializeMap}};
+
+    #[derive(Serialize, Deserialize)]
+    enum Near {
+        #[serde(rename="$near")]
+        Point(GeoPoint),
+    }
+
+    pub fn serialize<S: Serializer>(field: &Identifier, point: &GeoPoint, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&field, &Near::Point(*point))?;
+        map.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(Identifier, GeoPoint), D::Error> {
+        let map: HashMap<String, Near> = HashMap::deserialize(deserializer)?;
+        let mut it = map.into_iter();
+        match (it.next(), it.next()) {
+            (Some((field, Near::Point(point))), None) => Ok((field.into(), point)),
+            _ => Err(de::Error::custom("Not a near point"))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Filter, Comp, Cond, KeyData, Order, OrderKind};
+    use super::{Filter, Comp, Cond, KeyData, Order, OrderKind, GeoBox, GeoPoint, OrderedFloat};
     use serde_json::{from_str, to_string, Value, json};
 
     #[test]
@@ -223,6 +509,25 @@ mod test {
                     json!({ "name": { "$eq": "vlada" } }));
     }
 
+    #[test]
+    fn parse_comp_geo() {
+        test_parse!(Filter, json!({ "loc": { "$within": { "sw": { "lat": 1, "lon": 2 }, "ne": { "lat": 3.5, "lon": 4 } } } }),
+                    Filter::Comp("loc".into(),
+                                 Comp::Within(GeoBox::new((1.0, 2.0), (3.5, 4.0)))
+                    ));
+        test_parse!(Filter, json!({ "loc": { "$near": [{ "lat": 1.5, "lon": 2 }, 1000] } }),
+                    Filter::Comp("loc".into(),
+                                 Comp::Near(GeoPoint::new(1.5, 2.0), OrderedFloat(1000.0))
+                    ));
+    }
+
+    #[test]
+    fn build_comp_geo() {
+        test_build!(Filter::Comp("loc".into(),
+                                 Comp::Near(GeoPoint::new(1.5, 2.0), OrderedFloat(1000.5))),
+                    json!({ "loc": { "$near": [{ "lat": 1.5, "lon": 2.0 }, 1000.5] } }));
+    }
+
     #[test]
     fn parse_cond_not() {
         test_parse!(Filter, json!({ "$not": { "a":{ "$gt": 9 } } }),
@@ -303,4 +608,16 @@ mod test {
         test_build!(Order::Field("time".into(), OrderKind::Desc),
                    json!({ "time": "$desc" }));
     }
+
+    #[test]
+    fn parse_order_near() {
+        test_parse!(Order, json!({ "loc": { "$near": { "lat": 1, "lon": 2.5 } } }),
+                    Order::Near("loc".into(), GeoPoint::new(1.0, 2.5)));
+    }
+
+    #[test]
+    fn build_order_near() {
+        test_build!(Order::Near("loc".into(), GeoPoint::new(1.0, 2.5)),
+                    json!({ "loc": { "$near": { "lat": 1.0, "lon": 2.5 } } }));
+    }
 }
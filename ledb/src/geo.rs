@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+
+use lmdb::ConstTransaction;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    extract_field_values, Collection, Filter, FindOptions, Index, KeyData, KeyType, Primary,
+    Result, Selection, Value,
+};
+
+/// The mean radius of Earth in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The number of cells of quantized coordinate
+const CELLS: u64 = 1 << 32;
+
+/// The distances to found documents in meters
+pub(crate) type Distances = HashMap<Primary, f64>;
+
+/// Geographic point
+///
+/// The coordinates are in degrees.
+///
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GeoPoint {
+    /// Latitude in range -90..90
+    pub lat: f64,
+    /// Longitude in range -180..180
+    pub lon: f64,
+}
+
+impl PartialEq for GeoPoint {
+    fn eq(&self, other: &Self) -> bool {
+        OrderedFloat(self.lat) == OrderedFloat(other.lat)
+            && OrderedFloat(self.lon) == OrderedFloat(other.lon)
+    }
+}
+
+impl Eq for GeoPoint {}
+
+impl From<(f64, f64)> for GeoPoint {
+    fn from((lat, lon): (f64, f64)) -> Self {
+        GeoPoint::new(lat, lon)
+    }
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        GeoPoint { lat, lon }
+    }
+
+    /// Checks that the coordinates is in valid range
+    pub fn is_valid(&self) -> bool {
+        self.lat >= -90.0 && self.lat <= 90.0 && self.lon >= -180.0 && self.lon <= 180.0
+    }
+
+    /// Get the great-circle distance to other point in meters
+    pub fn distance(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    /// Get the bounding box of circle around the point
+    pub(crate) fn bounds(&self, radius: f64) -> GeoBox {
+        let angle = radius / EARTH_RADIUS;
+        let dlat = angle.to_degrees();
+        let (south, north) = (self.lat - dlat, self.lat + dlat);
+
+        if south <= -90.0 || north >= 90.0 {
+            // the circle covers the pole
+            return GeoBox::new((south.max(-90.0), -180.0), (north.min(90.0), 180.0));
+        }
+
+        let ratio = angle.sin() / self.lat.to_radians().cos();
+
+        if ratio >= 1.0 {
+            return GeoBox::new((south, -180.0), (north, 180.0));
+        }
+
+        let dlon = ratio.asin().to_degrees();
+
+        GeoBox::new(
+            (south, wrap_lon(self.lon - dlon)),
+            (north, wrap_lon(self.lon + dlon)),
+        )
+    }
+
+    /// Get the point from the value of document field
+    ///
+    /// The point is an object with `lat` and `lon` fields or GeoJSON point.
+    ///
+    pub(crate) fn from_val(val: &Value) -> Option<Self> {
+        let map = if let Value::Map(map) = val {
+            map
+        } else {
+            return None;
+        };
+        let get = |name: &str| map.get(&Value::Text(name.into()));
+
+        let point = if let (Some(lat), Some(lon)) = (get("lat"), get("lon")) {
+            GeoPoint::new(to_float(lat)?, to_float(lon)?)
+        } else if let (Some(Value::Text(typ)), Some(Value::Array(coords))) =
+            (get("type"), get("coordinates"))
+        {
+            if typ != "Point" || coords.len() < 2 {
+                return None;
+            }
+            // GeoJSON coordinates goes in order: longitude, latitude
+            GeoPoint::new(to_float(&coords[1])?, to_float(&coords[0])?)
+        } else {
+            return None;
+        };
+
+        Some(point).filter(GeoPoint::is_valid)
+    }
+
+    /// Get the code of point on Z-order curve
+    pub(crate) fn zorder(&self) -> u64 {
+        interleave(quantize_lat(self.lat), quantize_lon(self.lon))
+    }
+}
+
+/// Geographic bounding box
+///
+/// The box which crosses the antimeridian has the west longitude greater than the east.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoBox {
+    /// South-west corner
+    pub sw: GeoPoint,
+    /// North-east corner
+    pub ne: GeoPoint,
+}
+
+impl GeoBox {
+    pub fn new<A: Into<GeoPoint>, B: Into<GeoPoint>>(sw: A, ne: B) -> Self {
+        GeoBox {
+            sw: sw.into(),
+            ne: ne.into(),
+        }
+    }
+
+    /// Checks that the point is inside the box
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        point.lat >= self.sw.lat
+            && point.lat <= self.ne.lat
+            && if self.sw.lon <= self.ne.lon {
+                point.lon >= self.sw.lon && point.lon <= self.ne.lon
+            } else {
+                point.lon >= self.sw.lon || point.lon <= self.ne.lon
+            }
+    }
+
+    /// Get the ranges of Z-order codes which covers the box
+    ///
+    /// The ranges may cover some points outside of the box,
+    /// so the found points should be checked.
+    ///
+    pub(crate) fn zranges(&self) -> Vec<(u64, u64)> {
+        let lats = (quantize_lat(self.sw.lat), quantize_lat(self.ne.lat));
+        let (west, east) = (quantize_lon(self.sw.lon), quantize_lon(self.ne.lon));
+
+        let mut ranges = Vec::new();
+
+        if self.sw.lon <= self.ne.lon {
+            cover(lats, (west, east), &mut ranges);
+        } else {
+            cover(lats, (west, CELLS - 1), &mut ranges);
+            cover(lats, (0, east), &mut ranges);
+        }
+
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if last.1.checked_add(1) == Some(range.0) => last.1 = range.1,
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+impl<A: Into<GeoPoint>, B: Into<GeoPoint>> From<(A, B)> for GeoBox {
+    fn from((sw, ne): (A, B)) -> Self {
+        GeoBox::new(sw, ne)
+    }
+}
+
+fn to_float(val: &Value) -> Option<f64> {
+    match val {
+        Value::Float(val) => Some(*val),
+        Value::Integer(val) => Some(*val as f64),
+        _ => None,
+    }
+}
+
+fn wrap_lon(lon: f64) -> f64 {
+    if lon < -180.0 {
+        lon + 360.0
+    } else if lon > 180.0 {
+        lon - 360.0
+    } else {
+        lon
+    }
+}
+
+fn quantize(val: f64, range: f64) -> u64 {
+    // the float to integer conversion saturates so the out of range values are clamped
+    ((val / range + 0.5) * CELLS as f64).min((CELLS - 1) as f64) as u64
+}
+
+fn quantize_lat(lat: f64) -> u64 {
+    quantize(lat, 180.0)
+}
+
+fn quantize_lon(lon: f64) -> u64 {
+    quantize(lon, 360.0)
+}
+
+/// Interleave the bits of quantized latitude and longitude
+fn interleave(lat: u64, lon: u64) -> u64 {
+    spread(lat) | spread(lon) << 1
+}
+
+/// Spread the lower 32 bits to the even bits
+fn spread(val: u64) -> u64 {
+    let val = val & 0xffff_ffff;
+    let val = (val | val << 16) & 0x0000_ffff_0000_ffff;
+    let val = (val | val << 8) & 0x00ff_00ff_00ff_00ff;
+    let val = (val | val << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+    let val = (val | val << 2) & 0x3333_3333_3333_3333;
+    (val | val << 1) & 0x5555_5555_5555_5555
+}
+
+/// Cover the area of quantized coordinates by the cells of Z-order curve
+fn cover(lats: (u64, u64), lons: (u64, u64), out: &mut Vec<(u64, u64)>) {
+    if lats.0 > lats.1 || lons.0 > lons.1 {
+        return;
+    }
+    // the cells smaller than quarter of area gives too many ranges to scan
+    let extent = (lats.1 - lats.0).max(lons.1 - lons.0) + 1;
+    let depth = (34 - (63 - extent.leading_zeros())).min(32);
+
+    cover_cell(lats, lons, (0, 0), 0, depth, out);
+}
+
+fn cover_cell(
+    lats: (u64, u64),
+    lons: (u64, u64),
+    (lat, lon): (u64, u64),
+    level: u32,
+    depth: u32,
+    out: &mut Vec<(u64, u64)>,
+) {
+    let size = CELLS >> level;
+    let (lat_end, lon_end) = (lat + size - 1, lon + size - 1);
+
+    if lat > lats.1 || lat_end < lats.0 || lon > lons.1 || lon_end < lons.0 {
+        return;
+    }
+
+    if level == depth || lat >= lats.0 && lat_end <= lats.1 && lon >= lons.0 && lon_end <= lons.1 {
+        out.push((interleave(lat, lon), interleave(lat_end, lon_end)));
+        return;
+    }
+
+    let half = size >> 1;
+
+    // the quadrants in order of curve
+    for &cell in &[
+        (lat, lon),
+        (lat + half, lon),
+        (lat, lon + half),
+        (lat + half, lon + half),
+    ] {
+        cover_cell(lats, lons, cell, level + 1, depth, out);
+    }
+}
+
+/// Extract the points from the document field
+///
+/// The comma-separated paths are the latitude and longitude fields.
+///
+pub(crate) fn extract_points(doc: &Value, path: &str) -> Vec<GeoPoint> {
+    let mut points = Vec::new();
+
+    if let Some((lat, lon)) = path.split_once(',') {
+        let (mut lats, mut lons) = (Vec::new(), Vec::new());
+        extract_field_values(doc, Some(KeyType::Float), &lat.split('.'), &mut lats);
+        extract_field_values(doc, Some(KeyType::Float), &lon.split('.'), &mut lons);
+
+        points.extend(
+            lats.iter()
+                .zip(&lons)
+                .filter_map(|pair| match pair {
+                    (KeyData::Float(OrderedFloat(lat)), KeyData::Float(OrderedFloat(lon))) => {
+                        Some(GeoPoint::new(*lat, *lon))
+                    }
+                    _ => None,
+                })
+                .filter(GeoPoint::is_valid),
+        );
+    } else {
+        visit_field(doc, &path.split('.'), &mut |val| {
+            points.extend(GeoPoint::from_val(val))
+        });
+    }
+
+    points
+}
+
+fn visit_field<'i, I, F>(doc: &Value, path: &I, visit: &mut F)
+where
+    I: Iterator<Item = &'i str> + Clone,
+    F: FnMut(&Value),
+{
+    let mut sub_path = path.clone();
+    match (sub_path.next(), doc) {
+        (_, Value::Array(vals)) => vals.iter().for_each(|doc| visit_field(doc, path, visit)),
+        (None, doc) => visit(doc),
+        (Some("*"), Value::Map(vals)) => vals
+            .values()
+            .for_each(|doc| visit_field(doc, &sub_path, visit)),
+        (Some(name), Value::Map(vals)) => {
+            if let Some(doc) = vals.get(&Value::Text(name.into())) {
+                visit_field(doc, &sub_path, visit);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Keep the distance to the nearest point of document
+pub(crate) fn add_distance(dists: &mut Distances, id: Primary, dist: f64) {
+    let nearest = dists.entry(id).or_insert(dist);
+    if dist < *nearest {
+        *nearest = dist;
+    }
+}
+
+impl Collection {
+    /// Get the distances from the point to the documents
+    ///
+    /// When the document has several points the nearest is used.
+    /// The documents without points are omitted.
+    ///
+    pub(crate) fn distances_txn(
+        &self,
+        txn: &ConstTransaction,
+        path: &str,
+        center: &GeoPoint,
+        sel: Option<&Selection>,
+    ) -> Result<Distances> {
+        if let Some(index) = self.index_or_scan(path, Index::is_spatial)? {
+            let mut dists = index.query_near(txn, &txn.access(), center, f64::INFINITY)?;
+            if let Some(sel) = sel {
+                dists.retain(|id, _| sel.has(id));
+            }
+            return Ok(dists);
+        }
+
+        let mut dists = Distances::new();
+        self.visit_txn(txn, sel, |doc| {
+            for point in extract_points(&doc, path) {
+                add_distance(&mut dists, doc.req_id()?, center.distance(&point));
+            }
+            Ok(())
+        })?;
+        Ok(dists)
+    }
+
+    /// Find documents ordered by distance from the point
+    pub(crate) fn near_ids_txn(
+        &self,
+        txn: &ConstTransaction,
+        filter: Option<Filter>,
+        path: &str,
+        center: &GeoPoint,
+        opts: &FindOptions,
+    ) -> Result<Vec<Primary>> {
+        let sel = filter.map(|filter| filter.apply(txn, self)).transpose()?;
+        let dists = self.distances_txn(txn, path, center, sel.as_ref())?;
+
+        self.select_ranked(
+            dists.into_iter().map(|(id, dist)| (id, OrderedFloat(dist))),
+            opts,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_points, GeoBox, GeoPoint};
+    use serde_json::json;
+
+    #[test]
+    fn distance() {
+        let moscow = GeoPoint::new(55.7558, 37.6173);
+        let london = GeoPoint::new(51.5074, -0.1278);
+
+        assert!((moscow.distance(&london) - 2_500_000.0).abs() < 10_000.0);
+        assert_eq!(moscow.distance(&moscow), 0.0);
+    }
+
+    #[test]
+    fn zranges() {
+        let area = GeoBox::new((10.0, 20.0), (10.5, 20.5));
+        let ranges = area.zranges();
+
+        assert!(!ranges.is_empty());
+        for point in &[(10.0, 20.0), (10.25, 20.25), (10.5, 20.5)] {
+            let code = GeoPoint::from(*point).zorder();
+            assert!(ranges.iter().any(|(beg, end)| code >= *beg && code <= *end));
+        }
+        let code = GeoPoint::new(-10.0, 20.0).zorder();
+        assert!(!ranges.iter().any(|(beg, end)| code >= *beg && code <= *end));
+
+        // crossing the antimeridian
+        let area = GeoBox::new((-1.0, 179.0), (1.0, -179.0));
+        assert!(area.contains(&GeoPoint::new(0.0, 179.5)));
+        assert!(area.contains(&GeoPoint::new(0.0, -179.5)));
+        assert!(!area.contains(&GeoPoint::new(0.0, 0.0)));
+        for point in &[(0.0, 179.5), (0.0, -179.5)] {
+            let code = GeoPoint::from(*point).zorder();
+            assert!(area
+                .zranges()
+                .iter()
+                .any(|(beg, end)| code >= *beg && code <= *end));
+        }
+    }
+
+    #[test]
+    fn extract() {
+        let doc = serde_cbor::value::to_value(json!({
+            "a": { "lat": 1.5, "lon": 2 },
+            "b": [{ "type": "Point", "coordinates": [2.5, 1] }, { "lat": 91, "lon": 0 }],
+            "c": { "x": 1, "y": 2 },
+        }))
+        .unwrap();
+
+        assert_eq!(extract_points(&doc, "a"), vec![GeoPoint::new(1.5, 2.0)]);
+        assert_eq!(extract_points(&doc, "b"), vec![GeoPoint::new(1.0, 2.5)]);
+        assert_eq!(
+            extract_points(&doc, "c.x,c.y"),
+            vec![GeoPoint::new(1.0, 2.0)]
+        );
+        assert_eq!(extract_points(&doc, "c"), vec![]);
+    }
+}
@@ -1,27 +1,30 @@
 use std::{
+    borrow::Cow,
     collections::HashSet,
+    iter::once,
     ops::Deref,
     sync::{
         atomic::{AtomicBool, Ordering as AtomicOrdering},
-        Arc,
+        Arc, Mutex, MutexGuard, PoisonError,
     },
 };
 
-use crate::float::F64;
-
 use lmdb::{
+    error::KEYEXIST,
     put::{NODUPDATA, NOOVERWRITE},
-    traits::CreateCursor,
-    ConstAccessor, Cursor, CursorIter, Database, DatabaseOptions, LmdbResultExt, MaybeOwned,
-    ReadTransaction, Unaligned, WriteAccessor,
+    traits::LmdbOrdKey,
+    ConstAccessor, ConstTransaction, Cursor, Database, DatabaseOptions, LmdbResultExt, Unaligned,
+    WriteAccessor,
 };
 use ron::ser::to_string as to_db_name;
 use serde::{Deserialize, Serialize};
 use supercow::{ext::ConstDeref, Supercow};
 
 use super::{
-    DatabaseDef, Enumerable, IndexKind, KeyData, KeyField, KeyType, OrderKind, Primary,
-    RawDocument, Result, ResultWrap, Serial, Storage, Value,
+    add_distance, extract_points, extract_terms, primary_from_raw, primary_to_raw, DatabaseDef,
+    Distances, Enumerable, Error, GeoBox, GeoPoint, IndexBuild, IndexKind, KeyData, KeyField,
+    KeyType, OrderKind, Primary, PrimaryType, RawDocument, Result, ResultWrap, Scores, Serial,
+    Storage, TextQuery, Value,
 };
 
 /// The definition of index
@@ -35,16 +38,21 @@ pub(crate) struct IndexDef(
     pub String,
     pub IndexKind,
     pub KeyType,
+    /// Key types of the rest components of compound index
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub Vec<KeyType>,
 );
 
 impl IndexDef {
-    pub fn new<C: Into<String>, P: Into<String>>(
-        coll: C,
-        path: P,
-        kind: IndexKind,
-        key: KeyType,
-    ) -> Self {
-        IndexDef(0, coll.into(), path.into(), kind, key)
+    pub fn new<C: Into<String>>(coll: C, field: KeyField) -> Self {
+        let KeyField {
+            path,
+            key,
+            kind,
+            compound,
+            ..
+        } = field;
+        IndexDef(0, coll.into(), path, kind, key, compound)
     }
 }
 
@@ -54,11 +62,84 @@ impl Enumerable for IndexDef {
     }
 }
 
+/// The options of index database
+///
+/// The values of index is a primary keys of documents so it depends from primary key type.
+///
+fn index_db_options<K: LmdbOrdKey + ?Sized>(
+    kind: IndexKind,
+    primary: PrimaryType,
+) -> DatabaseOptions {
+    match (kind, primary) {
+        (IndexKind::Unique, _) => DatabaseOptions::create_map::<K>(),
+        // the values of full-text index is a primary keys with frequencies of words
+        (IndexKind::FullText, _) => DatabaseOptions::create_multimap_unsized::<K, [u8]>(),
+        // the values of spatial index is a primary keys with coordinates of points
+        (IndexKind::Spatial, _) => DatabaseOptions::create_multimap_unsized::<K, [u8]>(),
+        (IndexKind::Index, PrimaryType::Int) => {
+            DatabaseOptions::create_multimap::<K, Unaligned<u32>>()
+        }
+        (IndexKind::Index, PrimaryType::BigInt) => {
+            DatabaseOptions::create_multimap::<K, Unaligned<u64>>()
+        }
+        (IndexKind::Index, PrimaryType::String) => {
+            DatabaseOptions::create_multimap_unsized::<K, str>()
+        }
+        (IndexKind::Index, PrimaryType::Binary) => {
+            DatabaseOptions::create_multimap_unsized::<K, [u8]>()
+        }
+    }
+}
+
+/// The locked progress of building index
+///
+/// The readiness of index and the state of building is not locked,
+/// so the readers isn't blocked while the batch is indexing.
+///
+pub(crate) struct BuildProgress<'a> {
+    index: &'a Index,
+    last: MutexGuard<'a, Option<Primary>>,
+}
+
+impl BuildProgress<'_> {
+    /// Get the primary key of the last indexed document
+    pub(crate) fn last(&self) -> Option<Primary> {
+        self.last.clone()
+    }
+
+    /// Move the building forward by the batch of indexed documents
+    pub(crate) fn advance(mut self, last: Option<Primary>, count: usize, done: bool) {
+        if done {
+            *self.index.build() = None;
+            *self.last = None;
+            self.index.handle().ready.store(true, AtomicOrdering::SeqCst);
+        } else {
+            if let Some(build) = &mut *self.index.build() {
+                build.done += count;
+            }
+            if last.is_some() {
+                *self.last = last;
+            }
+        }
+    }
+}
+
 struct IndexData {
+    collection: String,
     path: String,
     kind: IndexKind,
     key: KeyType,
+    compound: Vec<KeyType>,
+    primary: PrimaryType,
     db: Database<'static>,
+    // Key of the building marker in the main database
+    build_key: String,
+    // The index is fulfilled and can be used by queries
+    ready: AtomicBool,
+    // The state of building when the index isn't ready yet
+    build: Mutex<Option<IndexBuild>>,
+    // The primary key of the last indexed document while building
+    last: Mutex<Option<Primary>>,
     // Remove marker
     delete: AtomicBool,
 }
@@ -68,41 +149,40 @@ struct IndexData {
 pub(crate) struct Index(Option<Arc<IndexData>>);
 
 impl Index {
-    pub(crate) fn new(storage: Storage, def: IndexDef) -> Result<Self> {
+    pub(crate) fn new(storage: Storage, def: IndexDef, primary: PrimaryType) -> Result<Self> {
         let db_name = to_db_name(&DatabaseDef::Index(def.clone())).wrap_err()?;
+        let build_key = to_db_name(&DatabaseDef::Build(def.clone())).wrap_err()?;
 
-        let IndexDef(_serial, _coll, path, kind, key) = def;
+        let IndexDef(_serial, collection, path, kind, key, compound) = def;
 
-        let db_opts = match (kind, key) {
-            (IndexKind::Unique, KeyType::Int) => DatabaseOptions::create_map::<Unaligned<i64>>(),
-            (IndexKind::Unique, KeyType::Float) => DatabaseOptions::create_map::<Unaligned<F64>>(),
-            (IndexKind::Unique, KeyType::String) => DatabaseOptions::create_map::<str>(),
-            (IndexKind::Unique, KeyType::Binary) => DatabaseOptions::create_map::<[u8]>(),
-            (IndexKind::Unique, KeyType::Bool) => DatabaseOptions::create_map::<u8>(),
-            (IndexKind::Index, KeyType::Int) => {
-                DatabaseOptions::create_multimap::<Unaligned<i64>, Unaligned<Primary>>()
-            }
-            (IndexKind::Index, KeyType::Float) => {
-                DatabaseOptions::create_multimap::<Unaligned<F64>, Unaligned<Primary>>()
-            }
-            (IndexKind::Index, KeyType::String) => {
-                DatabaseOptions::create_multimap::<str, Unaligned<Primary>>()
-            }
-            (IndexKind::Index, KeyType::Binary) => {
-                DatabaseOptions::create_multimap::<[u8], Unaligned<Primary>>()
-            }
-            (IndexKind::Index, KeyType::Bool) => {
-                DatabaseOptions::create_multimap::<u8, Unaligned<Primary>>()
-            }
+        let db_opts = match key {
+            // compound keys are order-preserving binaries
+            _ if !compound.is_empty() => index_db_options::<[u8]>(kind, primary),
+            // full-text keys are words
+            _ if kind == IndexKind::FullText => index_db_options::<str>(kind, primary),
+            // spatial keys are big-endian codes of points on Z-order curve
+            _ if kind == IndexKind::Spatial => index_db_options::<[u8]>(kind, primary),
+            // numbers are order-preserving big-endian binaries
+            KeyType::Int | KeyType::Float => index_db_options::<[u8]>(kind, primary),
+            KeyType::String | KeyType::Folded => index_db_options::<str>(kind, primary),
+            KeyType::Binary => index_db_options::<[u8]>(kind, primary),
+            KeyType::Bool => index_db_options::<u8>(kind, primary),
         };
 
-        let db = Database::open(storage, Some(&db_name), &db_opts).wrap_err()?;
+        let db = Database::open(storage.env(), Some(&db_name), &db_opts).wrap_err()?;
 
         Ok(Index(Some(Arc::new(IndexData {
+            collection,
             path,
             kind,
             key,
+            compound,
+            primary,
             db,
+            build_key,
+            ready: AtomicBool::new(true),
+            build: Mutex::new(None),
+            last: Mutex::new(None),
             delete: AtomicBool::new(false),
         }))))
     }
@@ -130,9 +210,136 @@ impl Index {
     pub fn field(&self) -> KeyField {
         let handle = self.handle();
 
-        KeyField::new(handle.path.clone())
-            .with_type(handle.key)
-            .with_kind(handle.kind)
+        KeyField {
+            path: handle.path.clone(),
+            key: handle.key,
+            kind: handle.kind,
+            compound: handle.compound.clone(),
+            build: self.build().clone(),
+        }
+    }
+
+    /// Checks that the index has same definition as key field
+    pub fn is_field(&self, field: &KeyField) -> bool {
+        let handle = self.handle();
+
+        handle.path == field.path
+            && handle.key == field.key
+            && handle.kind == field.kind
+            && handle.compound == field.compound
+    }
+
+    pub fn is_compound(&self) -> bool {
+        !self.handle().compound.is_empty()
+    }
+
+    pub fn is_fulltext(&self) -> bool {
+        self.handle().kind == IndexKind::FullText
+    }
+
+    pub fn is_spatial(&self) -> bool {
+        self.handle().kind == IndexKind::Spatial
+    }
+
+    /// The keys of index is a plain values of field
+    pub fn is_plain(&self) -> bool {
+        !self.is_compound() && matches!(self.kind(), IndexKind::Index | IndexKind::Unique)
+    }
+
+    fn build(&self) -> MutexGuard<'_, Option<IndexBuild>> {
+        self.handle()
+            .build
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The index is fulfilled and can be used by queries
+    pub fn is_ready(&self) -> bool {
+        self.handle().ready.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Get the key of building marker in the main database
+    pub(crate) fn build_key(&self) -> &str {
+        &self.handle().build_key
+    }
+
+    /// Mark the index as building from scratch
+    pub(crate) fn start_build(&self, total: usize) {
+        let mut last = self.lock_last();
+        self.handle().ready.store(false, AtomicOrdering::SeqCst);
+        *self.build() = Some(IndexBuild {
+            done: 0,
+            total,
+            error: None,
+        });
+        *last = None;
+    }
+
+    fn lock_last(&self) -> MutexGuard<'_, Option<Primary>> {
+        self.handle()
+            .last
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the progress of building
+    ///
+    /// The writers which checks `is_built()` waits until the lock is released,
+    /// so the progress may be advanced after the batch of documents is committed.
+    ///
+    pub(crate) fn lock_build(&self) -> BuildProgress<'_> {
+        BuildProgress {
+            index: self,
+            last: self.lock_last(),
+        }
+    }
+
+    /// Checks that the document is already indexed
+    ///
+    /// The building index contains the documents up to the last indexed only,
+    /// the rest of documents will be indexed by the builder later.
+    ///
+    pub(crate) fn is_built(&self, id: &Primary) -> bool {
+        let last = self.lock_last();
+        if self.is_ready() {
+            return true;
+        }
+        match &*last {
+            Some(last) => id <= last,
+            None => false,
+        }
+    }
+
+    /// Stop building because of error
+    pub(crate) fn fail_build(&self, error: String) {
+        if let Some(build) = &mut *self.build() {
+            build.error = Some(error);
+        }
+    }
+
+    /// Checks that the index was removed
+    pub(crate) fn is_deleted(&self) -> bool {
+        self.handle().delete.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Get the key types of all index components
+    fn key_types(&self) -> Vec<KeyType> {
+        let handle = self.handle();
+        let mut keys = vec![handle.key];
+        keys.extend(&handle.compound);
+        keys
+    }
+
+    /// Convert key data to the index key
+    ///
+    /// The key of compound index is a tuple or its prefix.
+    ///
+    fn to_key<'a>(&self, key: &'a KeyData) -> Option<Cow<'a, KeyData>> {
+        if self.is_compound() {
+            key.to_tuple(&self.key_types()).map(Cow::Owned)
+        } else {
+            key.to_type(self.key())
+        }
     }
 
     pub(crate) fn update_index(
@@ -145,7 +352,15 @@ impl Index {
             .or_else(|| new_doc)
             .ok_or_else(|| "Either old_doc or new_doc or both must present")
             .wrap_err()?;
-        let id = doc.req_id()?;
+
+        let handle = self.handle();
+        let id = primary_to_raw(handle.primary, &doc.req_id()?)?;
+
+        match handle.kind {
+            IndexKind::FullText => return self.update_terms(access, &id, old_doc, new_doc),
+            IndexKind::Spatial => return self.update_points(access, &id, old_doc, new_doc),
+            _ => (),
+        }
 
         let old_keys = old_doc.map(|doc| self.extract(doc)).unwrap_or_default();
         let new_keys = new_doc.map(|doc| self.extract(doc)).unwrap_or_default();
@@ -155,24 +370,115 @@ impl Index {
             new_keys.difference(&old_keys),
         );
 
-        let handle = self.handle();
-
         //println!("Update index {} --{:?} ++{:?}", &handle.path, &old_keys, &new_keys);
 
         for key in old_keys {
             access
-                .del_item(&handle.db, key.as_raw(), &Unaligned::new(id))
+                .del_item(&handle.db, &*key.as_raw(), id.as_slice())
                 .wrap_err()?;
         }
 
         let f = match handle.kind {
             IndexKind::Unique => NOOVERWRITE,
-            IndexKind::Index => NODUPDATA,
+            IndexKind::Index | IndexKind::FullText | IndexKind::Spatial => NODUPDATA,
         };
 
         for key in new_keys {
+            match access.put(&handle.db, &*key.as_raw(), id.as_slice(), f) {
+                Err(lmdb::Error::Code(KEYEXIST)) => {
+                    return Err(Error::DuplicateKey {
+                        collection: handle.collection.clone(),
+                        field: handle.path.clone(),
+                        key: key.clone(),
+                    })
+                }
+                res => res.wrap_err()?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the words of full-text index
+    ///
+    /// The word is stored with primary key and frequency of word in the document,
+    /// so the changing of frequency replaces the stored value.
+    ///
+    fn update_terms(
+        &self,
+        access: &mut WriteAccessor,
+        id: &[u8],
+        old_doc: Option<&RawDocument>,
+        new_doc: Option<&RawDocument>,
+    ) -> Result<()> {
+        let handle = self.handle();
+
+        let old_terms = old_doc
+            .map(|doc| extract_terms(doc, &handle.path))
+            .unwrap_or_default();
+        let new_terms = new_doc
+            .map(|doc| extract_terms(doc, &handle.path))
+            .unwrap_or_default();
+
+        for (term, freq) in &old_terms {
+            if new_terms.get(term) != Some(freq) {
+                access
+                    .del_item(&handle.db, term.as_bytes(), &*posting_to_raw(id, *freq))
+                    .wrap_err()?;
+            }
+        }
+
+        for (term, freq) in &new_terms {
+            if old_terms.get(term) != Some(freq) {
+                access
+                    .put(
+                        &handle.db,
+                        term.as_bytes(),
+                        &*posting_to_raw(id, *freq),
+                        NODUPDATA,
+                    )
+                    .wrap_err()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the points of spatial index
+    ///
+    /// The point is stored by the code on Z-order curve with primary key and exact coordinates.
+    ///
+    fn update_points(
+        &self,
+        access: &mut WriteAccessor,
+        id: &[u8],
+        old_doc: Option<&RawDocument>,
+        new_doc: Option<&RawDocument>,
+    ) -> Result<()> {
+        let handle = self.handle();
+
+        let locations = |doc: Option<&RawDocument>| {
+            doc.map(|doc| {
+                extract_points(doc, &handle.path)
+                    .iter()
+                    .map(|point| (point.zorder().to_be_bytes(), location_to_raw(id, point)))
+                    .collect::<HashSet<_>>()
+            })
+            .unwrap_or_default()
+        };
+
+        let old_locations = locations(old_doc);
+        let new_locations = locations(new_doc);
+
+        for (key, val) in old_locations.difference(&new_locations) {
             access
-                .put(&handle.db, key.as_raw(), &Unaligned::new(id), f)
+                .del_item(&handle.db, &key[..], val.as_slice())
+                .wrap_err()?;
+        }
+
+        for (key, val) in new_locations.difference(&old_locations) {
+            access
+                .put(&handle.db, &key[..], val.as_slice(), NODUPDATA)
                 .wrap_err()?;
         }
 
@@ -182,60 +488,74 @@ impl Index {
     fn extract(&self, doc: &RawDocument) -> HashSet<KeyData> {
         let mut keys = HashSet::new();
         let handle = self.handle();
-        let path = handle.path.split('.');
-        extract_field_values(&*doc, handle.key, &path, &mut keys);
+        if handle.compound.is_empty() {
+            let path = handle.path.split('.');
+            extract_field_values(doc, Some(handle.key), &path, &mut keys);
+        } else {
+            // the compound keys is a cartesian product of the component values
+            let mut tuples = vec![Vec::new()];
+            for (path, key) in handle.path.split(',').zip(self.key_types()) {
+                let mut vals = HashSet::new();
+                extract_field_values(doc, Some(key), &path.split('.'), &mut vals);
+                tuples = tuples
+                    .into_iter()
+                    .flat_map(|tuple| {
+                        vals.iter().map(move |val| {
+                            let mut tuple = tuple.clone();
+                            tuple.push(val.clone());
+                            tuple
+                        })
+                    })
+                    .collect();
+            }
+            keys.extend(tuples.into_iter().map(KeyData::Tuple));
+        }
         keys
     }
 
     pub(crate) fn query_set<'a, I: Iterator<Item = &'a KeyData>>(
         &self,
-        txn: &ReadTransaction,
+        txn: &ConstTransaction,
         access: &ConstAccessor,
         keys: I,
     ) -> Result<HashSet<Primary>> {
         let mut out = HashSet::new();
         let handle = self.handle();
 
+        if self.is_compound() {
+            for key in keys {
+                if let Some(key) = self.to_key(key) {
+                    let key = key.as_raw();
+                    out.extend(self.query_prefix_range(
+                        txn,
+                        access,
+                        Some((&key, true)),
+                        Some((&key, true)),
+                    )?);
+                }
+            }
+            return Ok(out);
+        }
+
         for key in keys {
             if let Some(key) = key.to_type(handle.key) {
                 let mut cursor = txn.cursor(self.clone()).wrap_err()?;
 
-                match handle.kind {
-                    IndexKind::Unique => match cursor
-                        .seek_k_both::<[u8], Unaligned<Primary>>(&access, key.as_raw())
-                        .to_opt()
-                    {
-                        Ok(Some((_key, id))) => {
-                            out.insert(id.get());
-                        }
-                        Err(e) => return Err(e).wrap_err(),
-                        _ => (),
-                    },
-                    IndexKind::Index => {
-                        match cursor
-                            .seek_k::<[u8], Unaligned<Primary>>(&access, key.as_raw())
+                let mut item = cursor
+                    .seek_k::<[u8], [u8]>(access, &*key.as_raw())
+                    .to_opt()
+                    .wrap_err()?;
+
+                while let Some(id) = item {
+                    out.insert(primary_from_raw(handle.primary, id)?);
+                    item = match handle.kind {
+                        IndexKind::Unique => None,
+                        IndexKind::Index | IndexKind::FullText | IndexKind::Spatial => cursor
+                            .next_dup::<[u8], [u8]>(access)
                             .to_opt()
-                        {
-                            Ok(Some(..)) => (),
-                            Ok(None) => continue,
-                            Err(e) => return Err(e).wrap_err(),
-                        }
-
-                        for res in CursorIter::new(
-                            MaybeOwned::Owned(cursor),
-                            &access,
-                            |c, a| c.get_multiple::<[Unaligned<Primary>]>(&a),
-                            Cursor::next_multiple::<[Unaligned<Primary>]>,
-                        )
-                        .wrap_err()?
-                        {
-                            if let Some(ids) = res.to_opt().wrap_err()? {
-                                for id in ids {
-                                    out.insert(id.get());
-                                }
-                            }
-                        }
-                    }
+                            .wrap_err()?
+                            .map(|(_key, id)| id),
+                    };
                 }
             }
         }
@@ -245,121 +565,465 @@ impl Index {
 
     pub(crate) fn query_range(
         &self,
-        txn: &ReadTransaction,
+        txn: &ConstTransaction,
         access: &ConstAccessor,
         beg: Option<(&KeyData, bool)>,
         end: Option<(&KeyData, bool)>,
     ) -> Result<HashSet<Primary>> {
+        if self.is_compound() {
+            let beg = beg.and_then(|(key, inc)| {
+                self.to_key(key).map(|key| (key.as_raw().into_owned(), inc))
+            });
+            let end = end.and_then(|(key, inc)| {
+                self.to_key(key).map(|key| (key.as_raw().into_owned(), inc))
+            });
+            return self.query_prefix_range(
+                txn,
+                access,
+                beg.as_ref().map(|(key, inc)| (key.as_slice(), *inc)),
+                end.as_ref().map(|(key, inc)| (key.as_slice(), *inc)),
+            );
+        }
+
         let mut out = HashSet::new();
         let handle = self.handle();
 
         let beg = beg.and_then(|(key, inc)| key.to_type(handle.key).map(|key| (key, inc)));
         let end = end.and_then(|(key, inc)| key.to_type(handle.key).map(|key| (key, inc)));
-        let cursor = txn.cursor(self.clone()).wrap_err()?;
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
 
-        match handle.kind {
-            IndexKind::Unique => {
-                for item in CursorIter::new(
-                    MaybeOwned::Owned(cursor),
-                    access,
-                    |c, a| match beg {
-                        Some((beg_key, beg_inc)) => {
-                            let p = c.seek_range_k(a, beg_key.as_raw())?;
-                            if beg_inc {
-                                Ok(p)
-                            } else {
-                                c.next(a)
-                            }
-                        }
-                        _ => c.first(a),
-                    },
-                    Cursor::next::<[u8], Unaligned<Primary>>,
-                )
-                .wrap_err()?
-                {
-                    match (item, &end) {
-                        (Ok((key, id)), Some((end_key, end_inc))) => {
-                            let key = KeyData::from_raw(end_key.get_type(), key)?;
-                            #[allow(clippy::op_ref)]
-                            {
-                                if &key < end_key || *end_inc && &key <= end_key {
-                                    out.insert(id.get());
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        (Ok((_, id)), _) => {
-                            out.insert(id.get());
-                        }
-                        (Err(e), _) => return Err(e).wrap_err(),
-                    }
+        let mut item = match &beg {
+            Some((beg_key, _)) => cursor.seek_range_k::<[u8], [u8]>(access, &*beg_key.as_raw()),
+            _ => cursor.first::<[u8], [u8]>(access),
+        }
+        .to_opt()
+        .wrap_err()?;
+
+        while let Some((key, id)) = item {
+            let key = KeyData::from_raw(handle.key, key)?;
+            if let Some((beg_key, false)) = &beg {
+                if key == **beg_key {
+                    item = cursor.next(access).to_opt().wrap_err()?;
+                    continue;
+                }
+            }
+            if let Some((end_key, end_inc)) = &end {
+                if key > **end_key || !end_inc && key == **end_key {
+                    break;
                 }
             }
-            IndexKind::Index => {
-                for item in CursorIter::new(
-                    MaybeOwned::Owned(cursor),
-                    access,
-                    |c, a| {
-                        let key = match beg {
-                            Some((beg_key, beg_inc)) => {
-                                let p = c.seek_range_k::<[u8], [u8]>(a, beg_key.as_raw())?.0;
-                                if beg_inc {
-                                    p
-                                } else {
-                                    c.next::<[u8], [u8]>(a)?.0
-                                }
-                            }
-                            _ => c.first::<[u8], [u8]>(a)?.0,
-                        };
-                        c.get_multiple::<[Unaligned<Primary>]>(a)
-                            .map(|val| (key, val))
-                    },
-                    |c, a| {
-                        if let Some(ids) = c.next_multiple(a).to_opt()? {
-                            c.get_current::<[u8], [u8]>(a).map(|(key, _val)| (key, ids))
-                        } else {
-                            let key = c.next::<[u8], Unaligned<Primary>>(a)?.0;
-                            c.get_multiple(a).map(|ids| (key, ids))
-                        }
-                    },
-                )
+            out.insert(primary_from_raw(handle.primary, id)?);
+            item = cursor.next(access).to_opt().wrap_err()?;
+        }
+
+        Ok(out)
+    }
+
+    /// Query the string keys which starts with prefix
+    pub(crate) fn query_prefix(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        prefix: &str,
+    ) -> Result<HashSet<Primary>> {
+        if prefix.is_empty() {
+            return self.query_range(txn, access, None, None);
+        }
+
+        let handle = self.handle();
+        let prefix = KeyData::String(prefix.into());
+        let prefix = match prefix.to_type(handle.key) {
+            Some(prefix) => prefix,
+            None => return Ok(HashSet::new()),
+        };
+        let prefix = prefix.as_raw();
+
+        let mut out = HashSet::new();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        let mut item = cursor
+            .seek_range_k::<[u8], [u8]>(access, &*prefix)
+            .to_opt()
+            .wrap_err()?;
+
+        while let Some((key, id)) = item {
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            out.insert(primary_from_raw(handle.primary, id)?);
+            item = cursor.next(access).to_opt().wrap_err()?;
+        }
+
+        Ok(out)
+    }
+
+    /// Query the keys which passes the test
+    ///
+    /// All keys of index will be checked.
+    ///
+    pub(crate) fn query_keys<F: Fn(&KeyData) -> bool>(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        test: F,
+    ) -> Result<HashSet<Primary>> {
+        let mut out = HashSet::new();
+        let handle = self.handle();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        let mut item = cursor.first::<[u8], [u8]>(access).to_opt().wrap_err()?;
+        // the duplicated keys goes in row so it tested once
+        let mut last: Option<(Vec<u8>, bool)> = None;
+
+        while let Some((key, id)) = item {
+            let passed = match &last {
+                Some((last_key, passed)) if last_key.as_slice() == key => *passed,
+                _ => {
+                    let passed = test(&KeyData::from_raw(handle.key, key)?);
+                    last = Some((key.into(), passed));
+                    passed
+                }
+            };
+            if passed {
+                out.insert(primary_from_raw(handle.primary, id)?);
+            }
+            item = cursor.next(access).to_opt().wrap_err()?;
+        }
+
+        Ok(out)
+    }
+
+    /// Query the documents which matches full-text query
+    ///
+    /// The scores of found documents is the total frequency of matched words.
+    ///
+    pub(crate) fn query_text(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        query: &TextQuery,
+    ) -> Result<Scores> {
+        let mut out = Scores::new();
+
+        for terms in query.alternatives() {
+            let mut found: Option<Scores> = None;
+
+            for term in terms {
+                let postings = self.query_term(txn, access, term)?;
+                found = Some(match found {
+                    None => postings,
+                    Some(found) => found
+                        .into_iter()
+                        .filter_map(|(id, score)| postings.get(&id).map(|freq| (id, score + freq)))
+                        .collect(),
+                });
+            }
+
+            for (id, score) in found.unwrap_or_default() {
+                *out.entry(id).or_default() += score;
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Get the documents which contains word with frequencies
+    fn query_term(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        term: &str,
+    ) -> Result<Scores> {
+        let mut out = Scores::new();
+        let handle = self.handle();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        let mut item = cursor
+            .seek_k::<[u8], [u8]>(access, term.as_bytes())
+            .to_opt()
+            .wrap_err()?;
+
+        while let Some(raw) = item {
+            let (id, freq) = posting_from_raw(raw)?;
+            out.insert(primary_from_raw(handle.primary, id)?, freq);
+            item = cursor
+                .next_dup::<[u8], [u8]>(access)
+                .to_opt()
                 .wrap_err()?
-                {
-                    match (item, &end) {
-                        (Ok((key, ids)), Some((end_key, end_inc))) => {
-                            let key = KeyData::from_raw(end_key.get_type(), key)?;
-                            #[allow(clippy::op_ref)]
-                            {
-                                if &key < end_key || *end_inc && &key <= end_key {
-                                    for id in ids {
-                                        out.insert(id.get());
-                                    }
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        (Ok((_, ids)), _) => {
-                            for id in ids {
-                                out.insert(id.get());
-                            }
-                        }
-                        (Err(e), _) => return Err(e).wrap_err(),
-                    }
+                .map(|(_key, raw)| raw);
+        }
+
+        Ok(out)
+    }
+
+    /// Query the documents which has points inside the box
+    pub(crate) fn query_within(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        area: &GeoBox,
+    ) -> Result<HashSet<Primary>> {
+        let mut out = HashSet::new();
+        self.visit_area(txn, access, area, |id, _point| {
+            out.insert(id);
+        })?;
+        Ok(out)
+    }
+
+    /// Query the documents which has points inside the circle
+    ///
+    /// The distance to the nearest point of each found document is returned.
+    ///
+    pub(crate) fn query_near(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        center: &GeoPoint,
+        radius: f64,
+    ) -> Result<Distances> {
+        let mut out = Distances::new();
+        self.visit_area(txn, access, &center.bounds(radius), |id, point| {
+            let dist = center.distance(&point);
+            if dist <= radius {
+                add_distance(&mut out, id, dist);
+            }
+        })?;
+        Ok(out)
+    }
+
+    /// Visit the points of spatial index inside the box
+    fn visit_area<F: FnMut(Primary, GeoPoint)>(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        area: &GeoBox,
+        mut visit: F,
+    ) -> Result<()> {
+        let handle = self.handle();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        for (beg, end) in area.zranges() {
+            let end = end.to_be_bytes();
+            let mut item = cursor
+                .seek_range_k::<[u8], [u8]>(access, &beg.to_be_bytes()[..])
+                .to_opt()
+                .wrap_err()?;
+
+            while let Some((key, raw)) = item {
+                if key > &end[..] {
+                    break;
+                }
+                let (id, point) = location_from_raw(raw)?;
+                // the ranges of curve covers the points outside of box too
+                if area.contains(&point) {
+                    visit(primary_from_raw(handle.primary, id)?, point);
+                }
+                item = cursor.next(access).to_opt().wrap_err()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Query the range of binary keys
+    ///
+    /// The bounds are treated as prefixes, so the inclusive bounds matches all the keys
+    /// which starts with it and the exclusive bounds excludes all of such keys.
+    ///
+    fn query_prefix_range(
+        &self,
+        txn: &ConstTransaction,
+        access: &ConstAccessor,
+        beg: Option<(&[u8], bool)>,
+        end: Option<(&[u8], bool)>,
+    ) -> Result<HashSet<Primary>> {
+        let mut out = HashSet::new();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        let mut item = match beg {
+            Some((beg_key, _)) => cursor.seek_range_k::<[u8], [u8]>(access, beg_key),
+            _ => cursor.first::<[u8], [u8]>(access),
+        }
+        .to_opt()
+        .wrap_err()?;
+
+        while let Some((key, id)) = item {
+            if let Some((beg_key, false)) = beg {
+                if key.starts_with(beg_key) {
+                    item = cursor.next(access).to_opt().wrap_err()?;
+                    continue;
                 }
             }
+            match end {
+                Some((end_key, true)) if key > end_key && !key.starts_with(end_key) => break,
+                Some((end_key, false)) if key >= end_key => break,
+                _ => (),
+            }
+            out.insert(primary_from_raw(self.handle().primary, id)?);
+            item = cursor.next(access).to_opt().wrap_err()?;
         }
 
         Ok(out)
     }
 
-    pub(crate) fn query_iter(
+    /// Iterate over indexed documents in order of keys
+    ///
+    /// When the document is specified the iteration starts next to it.
+    ///
+    pub(crate) fn query_iter<'txn>(
+        &self,
+        txn: &'txn ConstTransaction<'txn>,
+        order: OrderKind,
+        after: Option<&RawDocument>,
+    ) -> Result<IndexIterator<'txn>> {
+        if self.is_fulltext() {
+            return Err("Full-text index cannot be used for ordering").wrap_err();
+        }
+        if self.is_spatial() {
+            return Err("Spatial index cannot be used for ordering by field").wrap_err();
+        }
+
+        let after = if let Some(doc) = after {
+            let keys = self.extract(doc);
+            // the document may have several keys so use the last of it
+            let key = if order == OrderKind::Asc {
+                keys.into_iter().max()
+            } else {
+                keys.into_iter().min()
+            };
+            if let Some(key) = key {
+                Some((key, doc.req_id()?))
+            } else {
+                return Err(format!(
+                    "Document has no value of field '{}' to start from",
+                    self.path()
+                ))
+                .wrap_err();
+            }
+        } else {
+            None
+        };
+
+        IndexIterator::new(txn, self.clone(), order, after)
+    }
+
+    /// Iterate over the keys of index with the identifiers of documents
+    ///
+    /// When the `unique` is set only the first document of each key will be visited.
+    /// The visitor returns `false` to stop iteration.
+    ///
+    /// *Note*: The keys of compound indexes cannot be decoded so it isn't supported.
+    ///
+    pub(crate) fn visit_keys<F>(
         &self,
-        txn: Arc<ReadTransaction<'static>>,
+        txn: &ConstTransaction,
         order: OrderKind,
-    ) -> Result<IndexIterator> {
-        IndexIterator::new(txn, self.clone(), order)
+        unique: bool,
+        mut visit: F,
+    ) -> Result<()>
+    where
+        F: FnMut(KeyData, Primary) -> Result<bool>,
+    {
+        if self.is_compound() {
+            return Err("Compound index keys cannot be visited").wrap_err();
+        }
+        if self.is_fulltext() {
+            return Err("Full-text index keys cannot be visited").wrap_err();
+        }
+        if self.is_spatial() {
+            return Err("Spatial index keys cannot be visited").wrap_err();
+        }
+
+        let handle = self.handle();
+        let access = txn.access();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        let mut item = match order {
+            OrderKind::Asc => cursor.first::<[u8], [u8]>(&access),
+            OrderKind::Desc => cursor.last::<[u8], [u8]>(&access),
+        }
+        .to_opt()
+        .wrap_err()?;
+
+        while let Some((key, id)) = item {
+            if !visit(
+                KeyData::from_raw(handle.key, key)?,
+                primary_from_raw(handle.primary, id)?,
+            )? {
+                break;
+            }
+            item = match (order, unique) {
+                (OrderKind::Asc, false) => cursor.next::<[u8], [u8]>(&access),
+                (OrderKind::Asc, true) => cursor.next_nodup::<[u8], [u8]>(&access),
+                (OrderKind::Desc, false) => cursor.prev::<[u8], [u8]>(&access),
+                (OrderKind::Desc, true) => cursor.prev_nodup::<[u8], [u8]>(&access),
+            }
+            .to_opt()
+            .wrap_err()?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the raw entries of index which the document should have
+    ///
+    /// The entry is a pair of key and value like it stored in the database.
+    ///
+    pub(crate) fn entries(&self, doc: &RawDocument) -> Result<HashSet<(Vec<u8>, Vec<u8>)>> {
+        let handle = self.handle();
+        let id = primary_to_raw(handle.primary, &doc.req_id()?)?;
+
+        Ok(match handle.kind {
+            IndexKind::FullText => extract_terms(doc, &handle.path)
+                .into_iter()
+                .map(|(term, freq)| (term.into_bytes(), posting_to_raw(&id, freq)))
+                .collect(),
+            IndexKind::Spatial => extract_points(doc, &handle.path)
+                .iter()
+                .map(|point| {
+                    (
+                        point.zorder().to_be_bytes().to_vec(),
+                        location_to_raw(&id, point),
+                    )
+                })
+                .collect(),
+            IndexKind::Index | IndexKind::Unique => self
+                .extract(doc)
+                .iter()
+                .map(|key| (key.as_raw().into_owned(), id.clone()))
+                .collect(),
+        })
+    }
+
+    /// Get the primary key/identifier of document from the value of entry
+    pub(crate) fn entry_id(&self, val: &[u8]) -> Result<Primary> {
+        let handle = self.handle();
+
+        let id = match handle.kind {
+            IndexKind::FullText => posting_from_raw(val)?.0,
+            IndexKind::Spatial => location_from_raw(val)?.0,
+            IndexKind::Index | IndexKind::Unique => val,
+        };
+
+        primary_from_raw(handle.primary, id)
+    }
+
+    /// Visit all raw entries of index
+    pub(crate) fn visit_entries<F>(&self, txn: &ConstTransaction, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<()>,
+    {
+        let access = txn.access();
+        let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+        let mut item = cursor.first::<[u8], [u8]>(&access).to_opt().wrap_err()?;
+
+        while let Some((key, val)) = item {
+            visit(key, val)?;
+            item = cursor.next::<[u8], [u8]>(&access).to_opt().wrap_err()?;
+        }
+
+        Ok(())
     }
 
     pub(crate) fn purge(&self, access: &mut WriteAccessor) -> Result<()> {
@@ -421,12 +1085,20 @@ impl<'a> Into<Supercow<'a, Database<'a>>> for Index {
     }
 }
 
-fn extract_field_values<'a, 'i: 'a, I: Iterator<Item = &'i str> + Clone>(
+/// Extract the values of document field using path
+///
+/// When the key type is specified the values will be converted to it.
+/// The values are collected into a set to get unique keys or into a vector to get all of it.
+///
+pub(crate) fn extract_field_values<'a, 'i: 'a, I, K>(
     doc: &'a Value,
-    typ: KeyType,
+    typ: Option<KeyType>,
     path: &'a I,
-    keys: &mut HashSet<KeyData>,
-) {
+    keys: &mut K,
+) where
+    I: Iterator<Item = &'i str> + Clone,
+    K: Extend<KeyData>,
+{
     let mut sub_path = path.clone();
     if let Some(name) = sub_path.next() {
         use Value::*;
@@ -449,7 +1121,7 @@ fn extract_field_values<'a, 'i: 'a, I: Iterator<Item = &'i str> + Clone>(
     }
 }
 
-fn extract_field_primitives(doc: &Value, typ: KeyType, keys: &mut HashSet<KeyData>) {
+fn extract_field_primitives<K: Extend<KeyData>>(doc: &Value, typ: Option<KeyType>, keys: &mut K) {
     use serde_cbor::Value::*;
     match (typ, doc) {
         (_, Array(val)) => val
@@ -458,56 +1130,136 @@ fn extract_field_primitives(doc: &Value, typ: KeyType, keys: &mut HashSet<KeyDat
         (_, Map(val)) => val
             .iter()
             .for_each(|(key, _doc)| extract_field_primitives(key, typ, keys)),
-        (typ, val) => {
+        (Some(typ), val) => {
             if let Some(val) = KeyData::from_val(&val) {
                 if let Some(val) = val.to_type(typ) {
-                    keys.insert(val.into_owned());
+                    keys.extend(once(val.into_owned()));
                 }
             }
         }
+        (None, val) => {
+            if let Some(val) = KeyData::from_val(val) {
+                keys.extend(once(val));
+            }
+        }
     }
 }
 
-pub(crate) struct IndexIterator {
-    txn: Arc<ReadTransaction<'static>>,
-    cur: Cursor<'static, 'static>,
+/// Convert the primary key and frequency of word into the value of full-text index
+fn posting_to_raw(id: &[u8], freq: u32) -> Vec<u8> {
+    let mut raw = id.to_vec();
+    raw.extend_from_slice(&freq.to_be_bytes());
+    raw
+}
+
+/// Restore the primary key and frequency of word from the value of full-text index
+fn posting_from_raw(raw: &[u8]) -> Result<(&[u8], u32)> {
+    if raw.len() < 4 {
+        return Err("Full-text index value must be at least 4 bytes length").wrap_err();
+    }
+    let (id, freq) = raw.split_at(raw.len() - 4);
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(freq);
+    Ok((id, u32::from_be_bytes(buf)))
+}
+
+/// Convert the primary key and coordinates of point into the value of spatial index
+fn location_to_raw(id: &[u8], point: &GeoPoint) -> Vec<u8> {
+    let mut raw = id.to_vec();
+    raw.extend_from_slice(&point.lat.to_be_bytes());
+    raw.extend_from_slice(&point.lon.to_be_bytes());
+    raw
+}
+
+/// Restore the primary key and coordinates of point from the value of spatial index
+fn location_from_raw(raw: &[u8]) -> Result<(&[u8], GeoPoint)> {
+    if raw.len() < 16 {
+        return Err("Spatial index value must be at least 16 bytes length").wrap_err();
+    }
+    let (id, coords) = raw.split_at(raw.len() - 16);
+    let mut lat = [0u8; 8];
+    let mut lon = [0u8; 8];
+    lat.copy_from_slice(&coords[..8]);
+    lon.copy_from_slice(&coords[8..]);
+    Ok((
+        id,
+        GeoPoint::new(f64::from_be_bytes(lat), f64::from_be_bytes(lon)),
+    ))
+}
+
+pub(crate) struct IndexIterator<'txn> {
+    txn: &'txn ConstTransaction<'txn>,
+    cur: Cursor<'txn, 'static>,
     order: OrderKind,
+    after: Option<(KeyData, Primary)>,
+    kind: IndexKind,
+    primary: PrimaryType,
     init: bool,
 }
 
-impl IndexIterator {
-    pub fn new(txn: Arc<ReadTransaction<'static>>, coll: Index, order: OrderKind) -> Result<Self> {
+impl<'txn> IndexIterator<'txn> {
+    pub fn new(
+        txn: &'txn ConstTransaction<'txn>,
+        coll: Index,
+        order: OrderKind,
+        after: Option<(KeyData, Primary)>,
+    ) -> Result<Self> {
+        let kind = coll.kind();
+        let primary = coll.handle().primary;
         let cur = txn.cursor(coll)?;
 
         Ok(Self {
             txn,
             cur,
             order,
+            after,
+            kind,
+            primary,
             init: false,
         })
     }
 }
 
-impl Iterator for IndexIterator {
+impl<'txn> Iterator for IndexIterator<'txn> {
     type Item = Result<Primary>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let access = self.txn.access();
         match if self.init {
             match self.order {
-                OrderKind::Asc => self.cur.next::<[u8], Unaligned<Primary>>(&access),
-                OrderKind::Desc => self.cur.prev::<[u8], Unaligned<Primary>>(&access),
+                OrderKind::Asc => self.cur.next::<[u8], [u8]>(&access),
+                OrderKind::Desc => self.cur.prev::<[u8], [u8]>(&access),
+            }
+        } else if let Some((key, id)) = self.after.take() {
+            self.init = true;
+            if let Err(e) = match self.kind {
+                IndexKind::Unique => self
+                    .cur
+                    .seek_k::<[u8], [u8]>(&access, &*key.as_raw())
+                    .map(|_| ()),
+                IndexKind::Index | IndexKind::FullText | IndexKind::Spatial => {
+                    match primary_to_raw(self.primary, &id) {
+                        Ok(id) => self.cur.seek_kv(&*key.as_raw(), id.as_slice()),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            } {
+                return Some(Err(e).wrap_err());
+            }
+            match self.order {
+                OrderKind::Asc => self.cur.next::<[u8], [u8]>(&access),
+                OrderKind::Desc => self.cur.prev::<[u8], [u8]>(&access),
             }
         } else {
             self.init = true;
             match self.order {
-                OrderKind::Asc => self.cur.first::<[u8], Unaligned<Primary>>(&access),
-                OrderKind::Desc => self.cur.last::<[u8], Unaligned<Primary>>(&access),
+                OrderKind::Asc => self.cur.first::<[u8], [u8]>(&access),
+                OrderKind::Desc => self.cur.last::<[u8], [u8]>(&access),
             }
         }
         .to_opt()
         {
-            Ok(Some((_key, id))) => Some(Ok(id.get())),
+            Ok(Some((_key, id))) => Some(primary_from_raw(self.primary, id)),
             Ok(None) => None,
             Err(e) => Some(Err(e).wrap_err()),
         }
@@ -5,12 +5,22 @@
 ## Features
 
 * Processing documents which implements `Serialize` and `Deserialize` traits from [serde](https://serde.rs/).
-* Identifying documents using auto-incrementing integer primary keys.
+* Identifying documents using auto-incrementing integer primary keys or string and binary keys.
 * Indexing any fields of documents using unique or duplicated keys.
 * Searching and ordering documents using indexed fields or primary key.
-* Selecting documents using complex filters with fields comparing and logical operations.
+* Selecting documents using complex filters with fields comparing, string matching and logical operations.
+* Searching documents by words using full-text indexes.
+* Searching and ordering documents by geographic locations using spatial indexes.
+* Getting only required fields of found documents using projections.
+* Aggregating documents using counting, grouping and accumulating of field values.
 * Updating documents using rich set of modifiers.
+* Upserting documents and optimistic locking using document revisions.
+* Validating documents on write using optional schemas of collections.
 * Storing documents into independent storages so called collections.
+* Running multiple operations across collections atomically using write transactions.
+* Watching the changes of documents in collections.
+* Expiring documents using time to live with sweeping on demand or in background.
+* Exporting and importing collections using JSON Lines or CBOR sequences.
 * Flexible `query!` macro which helps write clear and readable queries.
 * Using [LMDB](https://en.wikipedia.org/wiki/Lightning_Memory-Mapped_Database) as backend for document storage and indexing engine.
 
@@ -94,6 +104,26 @@ fn main() {
 }
 ```
 
+## Primary keys
+
+By default the documents identified by auto-incrementing 32-bit integer keys.
+The collection may be created with another type of primary key instead:
+
+| Primary type | Json name | Description                        |
+| ------------ | --------- | ---------------------------------- |
+| Int          | "int"     | 32-bit integer (default)           |
+| BigInt       | "bigint"  | 64-bit integer                     |
+| String       | "string"  | UTF-8 string                       |
+| Binary       | "binary"  | Binary data, like UUID             |
+
+```ignore
+let users = storage.collection_with("user", PrimaryType::String)?;
+// the string and binary keys cannot be generated so the id is required
+users.insert_with_id(json!({ "$": "alice", "name": "Alice" }))?;
+```
+
+The type of collection primary keys is persistent and cannot be changed.
+
 ## Field names
 
 Field name is a sequence of dot-separated identifiers which represents nesting of value in document.
@@ -154,17 +184,102 @@ query!(
 | ------------- | ---------  | -----------                  |
 | Index         | "index"    | The values can be duplicated |
 | Unique        | "unique"   | Each value is unique         |
+| FullText      | "fulltext" | Words of strings for search  |
+| Spatial       | "spatial"  | Geographic points            |
 
 Unique index guarantee that each value can be stored once, any duplicates disalowed.
 
-The operation will fail in two cases:
+The operation will fail with `Error::DuplicateKey` in two cases:
 
 1. When you try to insert new document which duplicate unique field
 2. When you try to ensure unique index for field which have duplicates
 
 Unique fields is pretty fit for sorting.
 
-*TODO: Full-text index kind for searching*
+### Compound indexes
+
+The index can be created over several fields using comma-separated field paths.
+The key types of the rest components is passed separately:
+
+```ignore
+collection.ensure_index_field(
+    KeyField::new("status")
+        .with_type(KeyType::String)
+        .with_component("created", KeyType::Int)
+)
+```
+
+The compound index can be queried by the tuple of values like `{"status,created": {"$eq": ["done", 1234]}}` or by the prefix of it like `{"status,created": {"$eq": "done"}}`.
+The ranges and ordering by compound index follows the order of components.
+
+### Full-text indexes
+
+The full-text index stores the lowercased words of string values with the number of occurences in each document.
+It can be used by `$match` filter or by `Collection::search()` which ranks the found documents by the total number of matched words:
+
+```ignore
+query!(index for collection text string fulltext)?;
+
+let found = collection.search::<Value, _>("text", "lmdb | rust", FindOptions::default())?;
+```
+
+The full-text query consists of words which all must be found in document, the alternatives of words can be separated by `|`.
+The full-text index cannot be compound and cannot be used for ordering.
+
+### Spatial indexes
+
+The spatial index stores the geographic points using the codes on [Z-order curve](https://en.wikipedia.org/wiki/Z-order_curve).
+The indexed field may be an object like `{"lat": 55.75, "lon": 37.61}`, a GeoJSON point like `{"type": "Point", "coordinates": [37.61, 55.75]}`
+or a pair of latitude and longitude fields:
+
+```ignore
+collection.ensure_index_field(
+    KeyField::new("lat")
+        .with_kind(IndexKind::Spatial)
+        .with_component("lon", KeyType::Float)
+)
+```
+
+The spatial index can be used by `$within` and `$near` filters and for ordering by distance.
+
+### Background building
+
+The index is fulfilled synchronously inside single write transaction by default, so the writers are blocked until it is done.
+The `IndexOptions::background()` makes the index to be fulfilled by separate thread in batches:
+
+```ignore
+collection.ensure_index_with(KeyField::new("title"), IndexOptions::background().with_batch(500))?;
+
+for field in collection.get_indexes()?.iter() {
+    if let Some(build) = &field.build {
+        println!("{}: {} of {} documents indexed", field.path, build.done, build.total);
+    }
+}
+```
+
+The building index is not used by queries until it is ready.
+The `Collection::cancel_index()` stops the building and removes the index.
+The building which was interrupted by closing the storage restarts from scratch when the storage opens next time.
+
+### Integrity verification
+
+The `Collection::verify()` checks that the indexes agrees with documents.
+It reports the entries which refers to missing documents or keys (dangling), the keys of documents which is missing in index
+and the documents which shares the keys of unique index (duplicates).
+The `Collection::repair()` rebuilds the damaged indexes from documents:
+
+```ignore
+let integrity = collection.verify()?;
+
+if !integrity.is_ok() {
+    for path in integrity.damaged() {
+        println!("Index '{}' is damaged", path);
+    }
+    collection.repair()?;
+}
+```
+
+The unique index which keys is shared by several documents cannot be repaired until the documents will be fixed.
 
 ### Key types
 
@@ -174,6 +289,7 @@ Unique fields is pretty fit for sorting.
 | Float         | "float"    | 64-bit floating point numbers |
 | Bool          | "bool"     | boolean values                |
 | String        | "string"   | UTF-8 strings                 |
+| Folded        | "folded"   | lowercased UTF-8 strings      |
 | Binary        | "binary"   | raw binary data               |
 
 ## Filters
@@ -193,8 +309,22 @@ Unique fields is pretty fit for sorting.
 | Bw(a, true, b, false)  | {"$bw": [a, true, b, false]}  | field in> a..b    | Between incl a excl b |
 | Bw(a, false, b, true)  | {"$bw": [a, false, b, true]}  | field <in a..b    | Between excl a incl b |
 | Has                    | "$has"                        | field ?           | Has value (not null)  |
+| Prefix(string)         | {"$prefix": string}           | field ^= str      | Starts with string    |
+| Regex(regex)           | {"$regex": regex}             | field =~ regex    | Matches regex         |
+| IEq(string)            | {"$ieq": string}              | field ~= str      | Equal ignoring case   |
+| IPrefix(string)        | {"$iprefix": string}          | field ~^= str     | Starts ignoring case  |
+| Match(query)           | {"$match": query}             | field match query | Matches words         |
+| Within(box)            | {"$within": box}              | field within box  | Point inside box      |
+| Near(point, radius)    | {"$near": [point, radius]}    | field near pt, r  | Point inside circle   |
+
+The points of filters is an objects like `{"lat": 55.75, "lon": 37.61}`, the radius is in meters.
+The box is an object with south-west and north-east corners like `{"sw": point, "ne": point}`.
+The box which crosses the antimeridian has the west longitude greater than the east.
 
-**NOTE: To be able to use particular field of document in filters you need create index for it first.**
+The string operations uses the index with `string` keys, but the case-insensitive operations requires the index with `folded` keys.
+The folded index stores strings in lower case, so the other comparisons cannot be done using it except of `$has`.
+
+**NOTE: The filters by fields which has no index requires scanning of all documents in collection, so create indexes for fields which used in queries frequently. The full scans can be disallowed by the `no_full_scan` storage option.**
 
 Some examples:
 
@@ -208,6 +338,12 @@ query!(@filter field in 123..456)   // [123 ... 456]
 query!(@filter field <in> 123..456) // (123 ... 456)
 query!(@filter field <in 123..456)  // (123 ... 456]
 query!(@filter field in> 123..456)  // [123 ... 456)
+query!(@filter field ^= "abc")      // abc...
+query!(@filter field ~= "Abc")      // abc, ABC, aBc...
+query!(@filter field =~ "^a.+c$")
+query!(@filter field match "abc def | xyz")
+query!(@filter field within ((55.0, 37.0), (56.0, 38.0)))
+query!(@filter field near (55.75, 37.61), 1000)
 ```
 
 ### Logical operations
@@ -241,6 +377,7 @@ query!(@filter field <= 123 || field > 456)
 | Primary(Desc)      | "$desc"            | <, desc             | Descending ordering by primary key |
 | Field(field, Asc)  | {"field": "$asc"}  | field >, field asc  | Ascending ordering by field        |
 | Field(field, Desc) | {"field": "$desc"} | field <, field desc | Descending ordering by field       |
+| Near(field, point) | {"field": {"$near": point}} | field near point | Ordering by distance to point |
 
 Examples:
 
@@ -260,8 +397,23 @@ query!(@order by field asc)
 // descending ordering by other.field
 query!(@order by other.field <)
 query!(@order by other.field desc)
+
+// ordering by distance from point to location
+query!(@order by location near (55.75, 37.61))
 ```
 
+## Fields projection
+
+The projection allows get only the required fields of found documents using `FindOptions::projection()`.
+The fields are specified by paths like the indexed fields, so the `*` can be used for any field of object.
+
+| Internal Repr        | JSON Repr                       | Description                        |
+| -------------        | ---------------                 | -----------                        |
+| Include(fields)      | {"$include": ["field", "a.b"]}  | Get only specified fields          |
+| Exclude(fields)      | {"$exclude": ["field", "a.*.b"]}| Get all fields except specified    |
+
+The primary key/identifier of document is always kept.
+
 ## Modifiers
 
 | Internal Repr              | JSON Repr                       | Query (where)         | Description                |
@@ -333,6 +485,189 @@ query!(@modify obj ~= { a: true, b: "abc", c: 123 })
 query!(@modify obj ~= extra)
 ```
 
+## Export and import
+
+The `Storage::export()` writes the documents and indexes of all or selected collections into a stream
+and the `Storage::import()` reads it back, so it can be used for fixtures and migration between versions.
+
+```ignore
+let opts = ExportOptions::default().format(DataFormat::Cbor).collection("post");
+storage.export(File::create("post.cbor")?, &opts, |state| {
+    println!("Exported {} documents of {}", state.documents, state.collection);
+})?;
+
+let opts = ImportOptions::default().format(DataFormat::Cbor).mode(ImportMode::Upsert);
+storage.import(File::open("post.cbor")?, &opts, |_| ())?;
+```
+
+## Watching changes
+
+The `Collection::watch()` subscribes to the changes of documents which passes optional filter.
+The changes made by `insert`, `put`, `update`, `remove`, `delete`, `load` and `purge` are received after commit of write transaction.
+
+```ignore
+let watcher = collection.watch(query!(@filter title == "Foo"))?;
+
+for change in watcher {
+    match change.kind {
+        ChangeKind::Insert => println!("Inserted {}: {:?}", change.id, change.new_doc::<MyDoc>()?),
+        ChangeKind::Update => println!("Updated {}: {:?}", change.id, change.new_doc::<MyDoc>()?),
+        ChangeKind::Delete => println!("Deleted {}", change.id),
+    }
+}
+```
+
+## Aggregation
+
+The `Collection::aggregate()` applies the `Aggregation` to the documents which passes optional filter.
+The aggregations is serializable like filters and modifiers:
+
+```json
+"$count"
+{"$distinct": "field"}
+{"$sum": "field"}
+{"$avg": "field"}
+{"$min": "field"}
+{"$max": "field"}
+{"$group": {"by": "field", "fields": {"name": "$count", "other_name": {"$sum": "other_field"}}}}
+```
+
+The `distinct`, `min` and `max` are served directly from index when the field is indexed and its values have the type of index keys.
+The documents which has several values of field (like arrays) are counted for each value.
+
+```ignore
+let total = collection.count(query!(@filter title == "Foo"))?;
+let tags = collection.distinct(None, "tag")?;
+let newest = collection.max(None, "timestamp")?;
+
+let groups = collection.aggregate(None, &Aggregation::group("tag").with("posts", Accumulator::Count))?;
+```
+
+## Upserts and revisions
+
+The `Collection::upsert()` updates the documents which passes filter or inserts the default document when nothing found.
+The modifier is applied to the default document too.
+
+Each document has revision which grows on each change of it.
+The revisions is allocated from the per-collection sequence,
+so the document which was removed and inserted again never gets the revision it had before.
+The `Collection::put_if_revision()` replaces the document only when it has expected revision,
+otherwise the conflict error will be returned. The zero revision means that the document must not exists.
+
+```ignore
+collection.upsert(query!(@filter title == "Foo"), query!(@modify tag += ["new"]), MyDoc { title: "Foo".into(), ..Default::default() })?;
+
+let (mut doc, rev) = collection.get_with_revision::<MyDoc>(1)?.unwrap();
+doc.title = "Bar".into();
+
+match collection.put_if_revision(doc, rev) {
+    Ok(rev) => println!("New revision {}", rev),
+    Err(error) if error.is_conflict() => println!("Document was changed concurrently"),
+    Err(error) => return Err(error),
+}
+```
+
+## Returning affected documents
+
+The `Collection::update_returning()` and `Collection::remove_returning()` works like `update` and `remove` but returns the affected documents instead of the number of it.
+The `Returning` option selects which documents should be returned from update: before modification, after it or both.
+
+The `Collection::find_one_and_update()` and `Collection::find_one_and_delete()` operates on the first found document in a given order.
+
+```ignore
+let affected = collection.update_returning::<MyDoc>(query!(@filter title == "Foo"), query!(@modify title = "Bar"), Returning::After)?;
+
+let oldest = collection.find_one_and_delete::<MyDoc>(None, query!(@order by timestamp >))?;
+```
+
+## Validating documents
+
+The `Collection::set_schema()` configures the `Schema` which is used to validate documents on write.
+The schema is a subset of [JSON Schema](https://json-schema.org/) with types, required fields, enumerations, length of strings and ranges of numbers:
+
+```json
+{
+  "type": "object",
+  "required": ["name"],
+  "properties": {
+    "name": {"type": "string", "minLength": 1, "maxLength": 64},
+    "role": {"enum": ["admin", "user"]},
+    "age": {"type": "integer", "minimum": 0},
+    "tags": {"type": "array", "items": {"type": "string"}}
+  }
+}
+```
+
+The documents are validated by `insert`, `put`, `update` (after applying modifier), `upsert` and `load`.
+The invalid documents are rejected with validation error which lists the failing paths.
+
+```ignore
+collection.set_schema(Schema::object().required("name", Schema::of(SchemaType::String).length(Some(1), None)))?;
+
+match collection.insert(&json!({ "name": "" })) {
+    Err(Error::ValidationError(invalid)) => for violation in invalid.violations {
+        println!("{}: {}", violation.path, violation.message);
+    },
+    _ => (),
+}
+```
+
+## Expiring documents
+
+The `Collection::set_expiry()` configures the time to live of documents using the field with timestamp in seconds since UNIX epoch.
+The integer index will be created for the timestamp field when it has no index.
+The expiration is stored in the storage like indexes.
+
+The expired documents are hidden from `get`, `has`, `find`, `search` and `aggregate` immediately,
+but it stays in the collection until sweeping.
+The `Collection::sweep()` and `Storage::sweep()` removes the expired documents on demand,
+the `Storage::start_sweeper()` runs the background thread which does it periodically.
+
+```ignore
+collection.set_expiry(Expiry::new("updated", Duration::from_secs(3600)))?;
+
+let removed = storage.sweep()?;
+
+storage.start_sweeper(Duration::from_secs(60))?;
+```
+
+## Growing storage
+
+The size of memory map is limited by the `map_size` storage option, the writes fails with `Error::MapFull` when it is reached.
+The `map_grow_step` option allows the memory map to grow by specified step each time the storage is full,
+the optional `map_max_size` limits the size which the memory map may grow to.
+The failed writes of collections are retried after growing.
+
+The memory map is resized when no transactions is active, so the storage cannot grow while the documents iterators, snapshots or backups is alive, the writes fails with `Error::MapFull` instead.
+The `Storage::get_info()` reports the used size against the maximum size.
+
+```ignore
+let storage = Storage::new("my_db", serde_json::from_value(json!({
+    "map_size": 16 << 20,
+    "map_grow_step": 64 << 20,
+    "map_max_size": 1 << 30,
+}))?)?;
+
+let info = storage.get_info()?;
+println!("Used {} of {:?} bytes", info.used_size, info.max_size);
+```
+
+## Statistics
+
+The `Storage::get_stats()` reports the page usage of whole storage.
+The `Collection::stats()` reports the number and sizes of documents with the page usage of collection,
+and the number of entries and distinct keys with the page usage of each index:
+
+```ignore
+let stats = collection.stats()?;
+
+println!("{} documents takes {} bytes", stats.documents, stats.total_size);
+
+for index in &stats.indexes {
+    println!("Index '{}' has {} keys in {} leaf pages", index.path, index.keys, index.pages.leaf_pages);
+}
+```
+
 */
 
 pub use ledb_types as types;
@@ -344,63 +679,104 @@ pub use ledb_derive::Document;
 #[macro_use]
 mod test;
 
+mod aggregate;
+mod builder;
 mod collection;
 mod document;
 mod enumerate;
 mod error;
+mod exchange;
+mod expire;
 mod filter;
-mod float;
+mod geo;
 mod index;
 mod modify;
 mod pool;
+mod primary;
+mod projection;
+mod resize;
+mod schema;
 mod selection;
+mod snapshot;
+mod stats;
 mod storage;
+mod text;
+mod transaction;
 mod value;
+mod verify;
+mod watch;
 
 #[macro_use]
 mod macros;
 
-pub use ledb_types::{Document, Identifier, IndexKind, KeyField, KeyFields, KeyType, Primary};
+pub use ledb_types::{
+    Document, Identifier, IndexBuild, IndexKind, KeyField, KeyFields, KeyType, Primary,
+    PrimaryType,
+};
+pub use ordered_float::OrderedFloat;
 
 #[cfg(feature = "ledb-derive")]
 #[doc(hidden)]
 pub use ledb_derive::*;
 
-pub use collection::{Collection, DocumentsIterator};
-pub use document::{to_value, RawDocument, Value};
-pub use error::{Error, Result, ResultWrap};
-pub use filter::{Comp, Cond, Filter, Order, OrderKind};
+pub use aggregate::{Accumulator, Aggregated, Aggregation, Group};
+pub use builder::IndexOptions;
+pub use collection::{Affected, Collection, DocumentsIterator, Returning};
+pub use document::{to_value, RawDocument, Revision, Value};
+pub use error::{Conflict, Error, Result, ResultWrap};
+pub use exchange::{DataFormat, ExportOptions, ImportMode, ImportOptions, Progress};
+pub use expire::Expiry;
+pub use filter::{Comp, Cond, Filter, FindOptions, Order, OrderKind};
+pub use geo::{GeoBox, GeoPoint};
 pub use macros::*;
 pub use modify::{Action, Modify, WrappedRegex};
+pub use projection::Projection;
+pub use schema::{Invalid, Schema, SchemaType, Violation};
+pub use snapshot::{Snapshot, SnapshotCollection};
+pub use stats::{CollectionStats, IndexStats};
 pub use storage::{Info, Options, Stats, Storage};
+pub use transaction::{Transaction, TransactionCollection};
 pub use value::KeyData;
+pub use verify::{IndexIntegrity, Integrity};
+pub use watch::{Change, ChangeKind, Watcher};
 
-use collection::CollectionDef;
+use builder::IndexBuilder;
+use collection::{CollectionDef, PrimaryIterator};
 use enumerate::{Enumerable, Serial, SerialGenerator};
-use index::{Index, IndexDef};
+use expire::Sweeper;
+use geo::{add_distance, extract_points, Distances};
+use index::{extract_field_values, Index, IndexDef, IndexIterator};
 use pool::Pool;
+use primary::{primary_db_options, primary_from_raw, primary_to_raw, to_primary};
+use projection::{project_doc, Projector};
+use resize::Activity;
 use selection::Selection;
-use storage::{DatabaseDef, StorageData};
+use snapshot::SharedTxn;
+use storage::{DatabaseDef, Held, StorageData};
+use text::{extract_terms, tokenize, Scores, Terms, TextQuery};
+use watch::{notify_changes, Changes, Watchers};
 
 #[cfg(test)]
 mod tests {
     use serde::{Deserialize, Serialize};
     use serde_json::json;
+    use std::{
+        fs::remove_dir_all,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
 
     use super::{
-        test::test_db, Collection, Document, Identifier, IndexKind, KeyFields, KeyType, Primary,
-        Result, Value,
+        test::test_db, Accumulator, Aggregated, Aggregation, ChangeKind, Collection, Comp,
+        DataFormat, Document, Error, Expiry, ExportOptions, Filter, FindOptions, GeoBox, GeoPoint,
+        Identifier, ImportMode, ImportOptions, IndexKind, IndexOptions, Invalid, KeyData, KeyField,
+        KeyFields, KeyType, Options, Order, OrderKind, Primary, PrimaryType, Projection, Result,
+        Returning, Schema, SchemaType, Storage, Value,
     };
 
     fn get_id(val: Value) -> Option<Primary> {
         if let Value::Map(map) = val {
-            map.get(&Value::Text("$".into())).and_then(|val| {
-                if let Value::Integer(id) = val {
-                    Some(*id as u32)
-                } else {
-                    None
-                }
-            })
+            map.get(&Value::Text("$".into()))
+                .and_then(|val| serde_cbor::value::from_value(val.clone()).ok())
         } else {
             None
         }
@@ -408,7 +784,7 @@ mod tests {
 
     macro_rules! assert_found {
         ($res:expr $(,$exp:expr)*) => {
-            let ids: Vec<Primary> = vec![$($exp),*];
+            let ids: Vec<Primary> = vec![$(Primary::from($exp)),*];
             assert_eq!($res.unwrap().map(|doc: Result<Value>| get_id(doc.unwrap()).unwrap()).collect::<Vec<_>>(), ids)
         }
     }
@@ -483,6 +859,218 @@ mod tests {
         );
     }
 
+    #[test]
+    fn grow_map_size() {
+        let path = "test_db/grow_map_size";
+        let _ = remove_dir_all(path);
+
+        let s = Storage::new(
+            path,
+            serde_json::from_value(json!({
+                "map_size": 1 << 20,
+                "map_grow_step": 1 << 20,
+                "map_max_size": 4 << 20,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let c = s.collection("test").unwrap();
+
+        let info = s.get_info().unwrap();
+        assert_eq!(info.map_size, 1 << 20);
+        assert_eq!(info.max_size, Some(4 << 20));
+
+        let text = "x".repeat(3000);
+
+        // the storage grows until the maximum size is reached
+        let (count, e) = (0..2000)
+            .map(|_| c.insert(json!({ "s": text })))
+            .enumerate()
+            .find_map(|(count, res)| res.err().map(|e| (count, e)))
+            .unwrap();
+        assert!(e.is_map_full());
+        assert!(count > 512);
+        assert_eq!(c.count_all_txn(&c.read_txn().unwrap()).unwrap(), count);
+
+        let info = s.get_info().unwrap();
+        assert_eq!(info.map_size, 4 << 20);
+        assert!(info.used_size > 2 << 20);
+        assert!(info.used_size <= 4 << 20);
+    }
+
+    #[test]
+    fn grow_map_size_with_iterator() {
+        let path = "test_db/grow_map_size_with_iterator";
+        let _ = remove_dir_all(path);
+
+        let s = Storage::new(
+            path,
+            serde_json::from_value(json!({
+                "map_size": 1 << 20,
+                "map_grow_step": 1 << 20,
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let c = s.collection("test").unwrap();
+
+        let text = "x".repeat(3000);
+        c.insert(json!({ "s": text })).unwrap();
+
+        // the storage cannot grow while the iterator is alive
+        let mut docs = c.find::<Value>(None, Order::default()).unwrap();
+        let start = Instant::now();
+        let e = (0..1000)
+            .find_map(|_| c.insert(json!({ "s": text })).err())
+            .unwrap();
+        assert!(e.is_map_full());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(s.get_info().unwrap().map_size, 1 << 20);
+        assert!(docs.next().unwrap().is_ok());
+
+        drop(docs);
+        while s.get_info().unwrap().map_size == 1 << 20 {
+            c.insert(json!({ "s": text })).unwrap();
+        }
+        assert_eq!(s.get_info().unwrap().map_size, 2 << 20);
+    }
+
+    #[test]
+    fn insert_no_reuse_ids() {
+        let s = test_db("insert_no_reuse_ids").unwrap();
+        let c = s.collection("test").unwrap();
+
+        assert_eq!(c.insert(Doc::default()).unwrap(), 1);
+        assert_eq!(c.insert(Doc::default()).unwrap(), 2);
+        assert!(c.delete(2).unwrap());
+        assert_eq!(c.new_id().unwrap(), 3);
+        assert_eq!(c.insert(Doc::default()).unwrap(), 3);
+
+        c.purge().unwrap();
+        assert_eq!(c.insert(Doc::default()).unwrap(), 4);
+
+        let s = Storage::new(
+            "test_db/insert_no_reuse_ids",
+            serde_json::from_value(json!({ "reuse_ids": true })).unwrap(),
+        )
+        .unwrap();
+        let c = s.collection("test").unwrap();
+
+        assert!(c.delete(4).unwrap());
+        assert_eq!(c.insert(Doc::default()).unwrap(), 1);
+    }
+
+    #[test]
+    fn insert_with_id() {
+        let s = test_db("insert_with_id").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_data(&c).unwrap();
+
+        assert!(c
+            .insert_with_id(json!({ "$": 2, "s": "new" }))
+            .is_err());
+        assert_eq!(&c.get::<Doc>(2).unwrap().unwrap().s, "def");
+
+        assert_eq!(c.insert_with_id(json!({ "$": 10, "s": "new" })).unwrap(), 10);
+        assert!(c.delete(10).unwrap());
+        assert_eq!(c.insert(Doc::default()).unwrap(), 11);
+    }
+
+    #[test]
+    fn insert_concurrent() {
+        let s = test_db("insert_concurrent").unwrap();
+        let c = s.collection("test").unwrap();
+
+        let threads = (0..4)
+            .map(|_| {
+                let c = c.clone();
+                std::thread::spawn(move || {
+                    (0..50)
+                        .map(|_| c.insert(Doc::default()).unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut ids = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+
+        assert_eq!(ids.len(), 200);
+        assert_eq!(query!(find Value in c).unwrap().len(), 200);
+    }
+
+    #[test]
+    fn string_primary() {
+        let s = test_db("string_primary").unwrap();
+        let c = s.collection_with("test", PrimaryType::String).unwrap();
+
+        c.ensure_index("s", IndexKind::Index, KeyType::String)
+            .unwrap();
+
+        assert!(c.insert(json!({ "s": "abc" })).is_err());
+        assert_eq!(c.insert_with_id(json!({ "$": "b", "s": "abc" })).unwrap(), "b");
+        assert_eq!(c.insert_with_id(json!({ "$": "a", "s": "def" })).unwrap(), "a");
+        assert_eq!(c.insert_with_id(json!({ "$": "c", "s": "abc" })).unwrap(), "c");
+        assert!(c.insert_with_id(json!({ "$": "", "s": "abc" })).is_err());
+
+        assert_eq!(
+            c.get::<Value>("a").unwrap().map(get_id),
+            Some(Some("a".into()))
+        );
+        assert_found!(query!(find in c order >), "a", "b", "c");
+        assert_found!(query!(find in c where s == "abc" order <), "c", "b");
+
+        assert!(c.delete("b").unwrap());
+        assert_found!(query!(find in c where s == "abc"), "c");
+
+        assert!(s.collection_with("test", PrimaryType::Int).is_err());
+        assert_eq!(
+            s.collection("test").unwrap().primary_type(),
+            PrimaryType::String
+        );
+    }
+
+    #[test]
+    fn bigint_primary() {
+        let s = test_db("bigint_primary").unwrap();
+        let c = s.collection_with("test", PrimaryType::BigInt).unwrap();
+
+        let id = u64::from(u32::MAX) + 10;
+
+        assert_eq!(c.insert_with_id(json!({ "$": id, "s": "abc" })).unwrap(), id);
+        assert_eq!(c.insert(json!({ "s": "def" })).unwrap(), id + 1);
+        assert_found!(query!(find in c order <), id + 1, id);
+
+        let c = s.collection("int").unwrap();
+
+        assert!(c.insert_with_id(json!({ "$": id, "s": "abc" })).is_err());
+    }
+
+    #[test]
+    fn binary_primary() {
+        let s = test_db("binary_primary").unwrap();
+        let c = s.collection_with("test", PrimaryType::Binary).unwrap();
+
+        c.ensure_index("s", IndexKind::Unique, KeyType::String)
+            .unwrap();
+
+        // the hex strings are accepted as binary ids
+        assert_eq!(
+            c.insert_with_id(json!({ "$": "0a0b0c", "s": "abc" })).unwrap(),
+            Primary::Binary(vec![10, 11, 12])
+        );
+        assert_eq!(
+            c.get::<Value>(vec![10u8, 11, 12]).unwrap().map(get_id),
+            Some(Some(Primary::Binary(vec![10, 11, 12])))
+        );
+        assert_found!(query!(find in c where s == "abc"), vec![10u8, 11, 12]);
+    }
+
     #[test]
     fn get_by_primary() {
         let s = test_db("get").unwrap();
@@ -553,6 +1141,61 @@ mod tests {
         assert!(mk_index(&c).is_err());
     }
 
+    #[test]
+    fn typed_errors() {
+        let s = test_db("typed_errors").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        let e = query!(insert into c { "s": "abc" }).unwrap_err();
+        assert!(e.is_duplicate());
+        assert!(matches!(
+            &e,
+            Error::DuplicateKey { collection, field, key }
+                if collection == "test" && field == "s" && *key == KeyData::String("abc".into())
+        ));
+        assert_eq!(
+            e.to_string(),
+            "Duplicate key \"abc\" of unique index for field 's' of collection 'test'"
+        );
+
+        let e = c.insert_with_id(json!({ "$": 2, "s": "new" })).unwrap_err();
+        assert!(e.is_duplicate());
+        assert!(matches!(e, Error::DuplicateId { id: Primary::Int(2), .. }));
+
+        assert!(matches!(
+            c.insert_with_id(json!({ "s": "new" })),
+            Err(Error::MissingId)
+        ));
+
+        let e = c
+            .find_with::<Value>(
+                None,
+                Order::Field("s".into(), OrderKind::Asc),
+                FindOptions::default().after(100),
+            )
+            .err()
+            .unwrap();
+        assert!(e.is_not_found());
+        assert_eq!(e.to_string(), "Document with id 100 not found in collection 'test'");
+
+        let s = Storage::new(
+            "test_db/typed_errors",
+            serde_json::from_value(json!({ "no_full_scan": true })).unwrap(),
+        )
+        .unwrap();
+        let c = s.collection("test").unwrap();
+
+        let e = query!(find Value in c where x == 1).err().unwrap();
+        assert!(e.is_missing_index());
+        assert!(matches!(
+            &e,
+            Error::MissingIndex { collection, field } if collection == "test" && field == "x"
+        ));
+    }
+
     #[test]
     fn order_by_primary() {
         let s = test_db("order_by_primary").unwrap();
@@ -760,58 +1403,1502 @@ mod tests {
     }
 
     #[test]
-    fn remove_eq_str() {
-        let s = test_db("remove_eq_str").unwrap();
+    fn find_with_offset_limit() {
+        let s = test_db("find_with_offset_limit").unwrap();
         let c = s.collection("test").unwrap();
 
         mk_index(&c).unwrap();
         fill_data(&c).unwrap();
 
-        assert_found!(query!(find in c where s == "def"), 2);
-        assert_eq!(query!(remove from c where s == "def").unwrap(), 1);
-        assert_found!(query!(find in c where s == "def"));
-        assert_found!(query!(find in c where s == "abc"), 1);
+        let opts = FindOptions::default().offset(2).limit(3);
+
+        assert_found!(c.find_with(None, Order::default(), opts.clone()), 3, 4, 5);
+        assert_found!(
+            c.find_with(None, Order::Primary(OrderKind::Desc), opts.clone()),
+            5,
+            4,
+            3
+        );
+        assert_found!(
+            c.find_with(None, Order::Field("s".into(), OrderKind::Asc), opts.clone()),
+            5,
+            6,
+            1
+        );
+        assert_found!(
+            c.find_with(query!(@filter b == false), Order::default(), opts.clone()),
+            5
+        );
+        assert_found!(
+            c.find_with(query!(@filter !b == true), Order::default(), opts.clone()),
+            5,
+            7
+        );
+        assert_found!(
+            c.find_with(
+                query!(@filter i > 1),
+                Order::Field("s".into(), OrderKind::Desc),
+                FindOptions::default().limit(2)
+            ),
+            4,
+            2
+        );
     }
 
     #[test]
-    fn remove_ge_int() {
-        let s = test_db("remove_ge_int").unwrap();
+    fn find_with_after() {
+        let s = test_db("find_with_after").unwrap();
         let c = s.collection("test").unwrap();
 
         mk_index(&c).unwrap();
         fill_data(&c).unwrap();
 
-        assert_found!(query!(find in c where i >= 4), 3, 4, 6);
-        assert_eq!(query!(remove from c where i >= 4).unwrap(), 3);
-        assert_found!(query!(find in c where i >= 4));
-        assert_found!(query!(find in c where i >= 2), 2, 5);
+        let opts = FindOptions::default().after(3).limit(2);
+
+        assert_found!(c.find_with(None, Order::default(), opts.clone()), 4, 5);
+        assert_found!(
+            c.find_with(None, Order::Primary(OrderKind::Desc), opts.clone()),
+            2,
+            1
+        );
+        assert_found!(
+            c.find_with(None, Order::Field("s".into(), OrderKind::Asc), opts.clone()),
+            5,
+            6
+        );
+        assert_found!(
+            c.find_with(None, Order::Field("s".into(), OrderKind::Desc), opts.clone()),
+            7
+        );
+        assert_found!(
+            c.find_with(query!(@filter b == true), Order::default(), opts.clone()),
+            4,
+            6
+        );
+        assert_found!(
+            c.find_with(query!(@filter !b == true), Order::default(), opts.clone()),
+            5,
+            7
+        );
+        // not affected by inserting new documents
+        c.insert(json!({ "s": "000" })).unwrap();
+        assert_found!(
+            c.find_with(None, Order::Field("s".into(), OrderKind::Asc), opts.clone()),
+            5,
+            6
+        );
+        // documents with several values
+        assert_found!(
+            c.find_with(
+                None,
+                Order::Field("i".into(), OrderKind::Asc),
+                FindOptions::default().after(2)
+            ),
+            3,
+            5,
+            3,
+            6,
+            3,
+            4,
+            4,
+            6
+        );
+        // document without values
+        assert!(c
+            .find_with::<Value>(
+                None,
+                Order::Field("i".into(), OrderKind::Asc),
+                FindOptions::default().after(7)
+            )
+            .is_err());
     }
 
     #[test]
-    fn update_set_eq_str() {
-        let s = test_db("update_set_eq_str").unwrap();
+    fn find_with_projection() {
+        let s = test_db("find_with_projection").unwrap();
         let c = s.collection("test").unwrap();
 
         mk_index(&c).unwrap();
         fill_data(&c).unwrap();
 
-        assert_found!(query!(find in c where s == "def"), 2);
+        let opts = FindOptions::default().projection(Projection::include(vec!["s", "n.i"]));
+
         assert_eq!(
-            query!(update in c modify s = "klm" where s == "def").unwrap(),
-            1
+            c.find_with::<serde_json::Value>(query!(@filter s == "def"), Order::default(), opts)
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap(),
+            vec![json!({ "$": 2, "s": "def", "n": { "i": 1 } })]
         );
-        assert_found!(query!(find in c where s == "def"));
-        assert_found!(query!(find in c where s == "abc"), 1);
-        assert_found!(query!(find in c where s == "klm"), 2);
-    }
 
-    // derive
-    #[derive(Debug, Clone, Serialize, Deserialize, Document)]
-    struct RootDoc {
-        #[document(primary)]
-        id: Option<Primary>,
+        let opts = FindOptions::default().projection(Projection::exclude(vec!["i", "n.a"]));
 
-        #[document(unique)]
+        assert_eq!(
+            c.find_with::<serde_json::Value>(query!(@filter s == "def"), Order::default(), opts)
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap(),
+            vec![json!({ "$": 2, "s": "def", "b": false, "n": { "i": 1 } })]
+        );
+    }
+
+    #[test]
+    fn find_strings() {
+        let s = test_db("find_strings").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        // using index
+        assert_found!(query!(find in c where s ^= "ab"), 1);
+        assert_found!(query!(find in c where s =~ "^[0-9]+$"), 3, 5, 6);
+        // using scan
+        assert_found!(query!(find in c where s ~= "ABC"), 1);
+        assert_found!(query!(find in c where s ~^= "X"), 4);
+        assert_found!(query!(find in c where n.a =~ "4$"), 4, 5);
+
+        let c = s.collection("folded").unwrap();
+
+        query!(index for c name folded).unwrap();
+        query!(insert into c { "name": "Foo" }).unwrap();
+        query!(insert into c { "name": "foobar" }).unwrap();
+        query!(insert into c { "name": "BAR" }).unwrap();
+
+        // using index
+        assert_found!(query!(find in c where name ~= "foo"), 1);
+        assert_found!(query!(find in c where name ~^= "FOO"), 1, 2);
+        assert_found!(query!(find in c order by name), 3, 1, 2);
+        // using scan
+        assert_found!(query!(find in c where name == "foo"));
+        assert_found!(query!(find in c where name ^= "foo"), 2);
+
+        // the folded keys cannot be used as values
+        assert_eq!(
+            c.distinct(None, "name").unwrap(),
+            vec!["BAR".into(), "Foo".into(), "foobar".into()]
+        );
+        assert_eq!(c.min(None, "name").unwrap(), Some("BAR".into()));
+        assert_eq!(c.max(None, "name").unwrap(), Some("foobar".into()));
+    }
+
+    #[test]
+    fn fulltext_search() {
+        let s = test_db("fulltext_search").unwrap();
+
+        for indexed in &[true, false] {
+            let c = s.collection(if *indexed { "indexed" } else { "scanned" }).unwrap();
+
+            if *indexed {
+                query!(index for c text string fulltext).unwrap();
+            }
+
+            query!(insert into c { "text": "Rust is fast. Rust is safe." }).unwrap();
+            query!(insert into c { "text": ["LMDB is fast", "and compact"] }).unwrap();
+            query!(insert into c { "text": "Embedded database in Rust using LMDB" }).unwrap();
+
+            assert_found!(query!(find in c where text match "rust"), 1, 3);
+            assert_found!(query!(find in c where text match "RUST lmdb"), 3);
+            assert_found!(query!(find in c where text match "safe | compact"), 1, 2);
+            assert_found!(query!(find in c where text match "fast" && !(text match "rust")), 2);
+
+            // ranked by frequency of words
+            assert_found!(c.search("text", "rust | lmdb", FindOptions::default()), 1, 3, 2);
+            assert_found!(c.search("text", "rust | lmdb", FindOptions::default().after(1)), 3, 2);
+            assert_found!(c.search("text", "missing", FindOptions::default()));
+
+            query!(update in c modify text = "Rust is not slow" where text match "compact").unwrap();
+            assert_found!(query!(find in c where text match "lmdb"), 3);
+            assert_found!(query!(find in c where text match "slow"), 2);
+            assert_found!(c.search("text", "rust", FindOptions::default()), 1, 2, 3);
+
+            query!(remove from c where text match "safe").unwrap();
+            assert_found!(query!(find in c where text match "rust"), 2, 3);
+
+            if *indexed {
+                // ordering is not available using full-text index
+                assert!(query!(find Value in c order by text).is_err());
+            }
+        }
+
+        let c = s.collection("compound").unwrap();
+        assert!(c
+            .ensure_index_field(
+                KeyField::new("a")
+                    .with_kind(IndexKind::FullText)
+                    .with_component("b", KeyType::String)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn spatial_search() {
+        let s = test_db("spatial_search").unwrap();
+
+        for indexed in &[true, false] {
+            let c = s.collection(if *indexed { "indexed" } else { "scanned" }).unwrap();
+
+            if *indexed {
+                query!(index for c loc float spatial).unwrap();
+            }
+
+            // Moscow
+            query!(insert into c { "loc": { "lat": 55.7558, "lon": 37.6173 } }).unwrap();
+            // Saint Petersburg
+            query!(insert into c { "loc": { "lat": 59.9343, "lon": 30.3351 } }).unwrap();
+            // London
+            query!(insert into c { "loc": { "type": "Point", "coordinates": [-0.1278, 51.5074] } }).unwrap();
+            // Tokyo
+            query!(insert into c { "loc": { "lat": 35.6762, "lon": 139.6503 } }).unwrap();
+            query!(insert into c { "name": "nowhere" }).unwrap();
+            // Anadyr
+            query!(insert into c { "loc": { "lat": 64.7337, "lon": 177.4968 } }).unwrap();
+            // Kazan and Nome
+            query!(insert into c { "loc": [{ "lat": 55.7963, "lon": 49.1088 }, { "lat": 64.5011, "lon": -165.4064 }] }).unwrap();
+
+            assert_found!(query!(find in c where loc within ((50.0, 25.0), (60.0, 40.0))), 1, 2);
+            // crossing the antimeridian
+            assert_found!(query!(find in c where loc within ((60.0, 170.0), (70.0, -160.0))), 6, 7);
+            assert_found!(query!(find in c where loc near (55.7558, 37.6173), 650_000), 1, 2);
+            assert_found!(query!(find in c where loc near (55.7558, 37.6173), 800_000), 1, 2, 7);
+
+            assert_found!(query!(find in c order by loc near (55.7558, 37.6173)), 1, 2, 7, 3, 6, 4);
+            assert_found!(query!(find in c where loc near (55.7558, 37.6173), 800_000 order by loc near (51.5, 0.0)), 2, 1, 7);
+            assert_found!(
+                c.find_with(
+                    None,
+                    Order::near("loc", (51.5, 0.0)),
+                    FindOptions::default().after(2).limit(2)
+                ),
+                1, 7
+            );
+            assert!(c
+                .find_with::<Value>(None, Order::near("loc", (51.5, 0.0)), FindOptions::default().after(100))
+                .err()
+                .unwrap()
+                .is_not_found());
+
+            query!(update in c modify loc = json!({ "lat": 35.0, "lon": 139.0 }) where loc within ((59.0, 30.0), (60.0, 31.0))).unwrap();
+            assert_found!(query!(find in c where loc near (55.7558, 37.6173), 800_000), 1, 7);
+            assert_found!(query!(find in c where loc near (35.5, 139.5), 100_000), 2, 4);
+
+            query!(remove from c where loc within ((35.0, 139.0), (36.0, 140.0))).unwrap();
+            assert_found!(query!(find in c where loc?), 1, 3, 6, 7);
+            assert_found!(query!(find in c order by loc near (35.5, 139.5)), 6, 7, 1, 3);
+        }
+
+        let c = s.collection("pair").unwrap();
+        c.ensure_index_field(
+            KeyField::new("lat")
+                .with_type(KeyType::Float)
+                .with_kind(IndexKind::Spatial)
+                .with_component("lon", KeyType::Float),
+        )
+        .unwrap();
+
+        query!(insert into c { "lat": 55.7558, "lon": 37.6173 }).unwrap();
+        query!(insert into c { "lat": 59.9343, "lon": 30.3351 }).unwrap();
+        query!(insert into c { "lat": 35.6762 }).unwrap();
+
+        assert_found!(c.find(Some(Filter::comp("lat,lon", Comp::Within(GeoBox::new((55.0, 37.0), (56.0, 38.0))))), Order::default()), 1);
+        assert_found!(c.find(None, Order::near("lat,lon", (60.0, 30.0))), 2, 1);
+    }
+
+    #[test]
+    fn schema_validation() {
+        let s = test_db("schema_validation").unwrap();
+        let c = s.collection("users").unwrap();
+
+        // the existing documents are not validated
+        query!(insert into c { "name": "" }).unwrap();
+
+        c.set_schema(
+            Schema::object()
+                .required("name", Schema::of(SchemaType::String).length(Some(1), None))
+                .field("age", Schema::of(SchemaType::Integer).range(Some(0.0), None)),
+        )
+        .unwrap();
+        assert!(c.get_schema().unwrap().is_some());
+
+        query!(insert into c { "name": "alice", "age": 30 }).unwrap();
+
+        match query!(insert into c { "age": -1 }) {
+            Err(Error::ValidationError(Invalid { violations })) => assert_eq!(
+                violations.iter().map(|v| v.path.as_str()).collect::<Vec<_>>(),
+                vec!["name", "age"]
+            ),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert!(query!(update in c modify age = -5 where name == "alice").unwrap_err().is_invalid());
+        assert_found!(query!(find in c where age == 30), 2);
+
+        assert!(c.put(json!({ "$": 2, "name": 1 })).unwrap_err().is_invalid());
+        assert!(c.load(vec![json!({ "$": 1, "name": "bob" }), json!({ "$": 3 })]).unwrap_err().is_invalid());
+        assert_found!(query!(find in c), 1, 2);
+
+        assert!(c.drop_schema().unwrap());
+        assert!(!c.drop_schema().unwrap());
+        query!(insert into c { "age": -1 }).unwrap();
+    }
+
+    #[test]
+    fn expire_documents() {
+        let s = test_db("expire_documents").unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        let c = s.collection("sessions").unwrap();
+
+        query!(insert into c { "user": "alice", "seen": now - 7200 }).unwrap();
+        query!(insert into c { "user": "bob", "seen": now }).unwrap();
+        query!(insert into c { "user": "carol" }).unwrap();
+        query!(insert into c { "user": "dave", "seen": now - 3700 }).unwrap();
+
+        assert_eq!(c.sweep().unwrap(), 0);
+
+        c.set_expiry(Expiry::new("seen", Duration::from_secs(3600))).unwrap();
+        assert!(c.has_index("seen").unwrap());
+        assert_eq!(c.get_expiry().unwrap(), Some(Expiry::new("seen", Duration::from_secs(3600))));
+
+        assert_eq!(c.get::<Value>(1).unwrap(), None);
+        assert!(c.get::<Value>(2).unwrap().is_some());
+        assert!(!c.has(4).unwrap());
+        assert!(c.has(3).unwrap());
+        assert_found!(query!(find in c), 2, 3);
+        assert_found!(query!(find in c where user == "alice"));
+        assert_eq!(c.count(None).unwrap(), 2);
+
+        query!(update in c modify seen = now - 3600 where user == "bob").unwrap();
+        assert_found!(query!(find in c), 3);
+
+        assert_eq!(c.sweep().unwrap(), 3);
+        assert!(c.drop_expiry().unwrap());
+        assert!(!c.drop_expiry().unwrap());
+        assert_found!(query!(find in c), 3);
+
+        // the field keeps the time of expiration itself
+        let c = s.collection("cache").unwrap();
+        c.set_expiry(Expiry::new("expires", Duration::from_secs(0))).unwrap();
+
+        query!(insert into c { "expires": now - 1 }).unwrap();
+        query!(insert into c { "expires": now + 3600 }).unwrap();
+        assert_found!(query!(find in c), 2);
+
+        s.start_sweeper(Duration::from_millis(10)).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(s.stop_sweeper().unwrap());
+        assert!(!s.stop_sweeper().unwrap());
+
+        c.drop_expiry().unwrap();
+        assert_found!(query!(find in c), 2);
+    }
+
+    #[test]
+    fn find_unindexed() {
+        let s = test_db("find_unindexed").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_data(&c).unwrap();
+
+        assert_found!(query!(find in c where s == "def"), 2);
+        assert_found!(query!(find in c where b == true), 3, 4, 6);
+        assert_found!(query!(find in c where i of [1, 3]), 2, 3, 4, 6);
+        assert_found!(query!(find in c where i in 3..4), 3, 4, 6);
+        assert_found!(query!(find in c where i <in 3..4), 3, 4);
+        assert_found!(query!(find in c where n.i < 2), 2, 6);
+        assert_found!(query!(find in c where n.a ?), 2, 4, 5, 6);
+        assert_found!(query!(find in c where n.a == "t2" || n.a == "t1"), 2, 4, 5, 6);
+    }
+
+    #[test]
+    fn find_partially_indexed() {
+        let s = test_db("find_partially_indexed").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_data(&c).unwrap();
+        query!(index for c b bool).unwrap();
+
+        assert_found!(query!(find in c where b == true && n.i > 0), 3, 4);
+        assert_found!(query!(find in c where n.i > 0 && !b == true), 2, 5);
+        assert_found!(query!(find in c where b == true || n.i == 1), 2, 3, 4, 6);
+        assert_found!(query!(find in c where b == true && !(i == 2 || i == 3)), 4);
+    }
+
+    #[test]
+    fn find_strict() {
+        let s = test_db("find_strict").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_data(&c).unwrap();
+        query!(index for c b bool).unwrap();
+
+        let s = Storage::new(
+            "test_db/find_strict",
+            serde_json::from_value(json!({ "no_full_scan": true })).unwrap(),
+        )
+        .unwrap();
+        let c = s.collection("test").unwrap();
+
+        assert_found!(query!(find in c where b == false), 1, 2, 5);
+        assert!(query!(find Value in c where s == "def").is_err());
+        assert!(query!(find Value in c where b == true && n.i > 0).is_err());
+    }
+
+    /// Turn the storage back into first format with native-endian integer keys
+    fn downgrade_format(path: &str, nums: &[i64]) {
+        use super::DatabaseDef;
+        use lmdb::{
+            db, open, put, Database, DatabaseOptions, EnvBuilder, Unaligned, WriteTransaction,
+        };
+        use ron::{de::from_str as from_ron, ser::to_string as to_db_name};
+
+        let mut bld = EnvBuilder::new().unwrap();
+        bld.set_maxdbs(128).unwrap();
+        let env = unsafe { bld.open(path, open::Flags::empty(), 0o600) }.unwrap();
+        let main = Database::open(&env, None, &DatabaseOptions::defaults()).unwrap();
+
+        let name = {
+            let txn = WriteTransaction::new(&env).unwrap();
+            let name = {
+                let mut access = txn.access();
+                access
+                    .del_key(&main, to_db_name(&DatabaseDef::Format).unwrap().as_str())
+                    .unwrap();
+                let mut cursor = txn.cursor(&main).unwrap();
+                let mut name = None;
+                let mut item = cursor.first::<str, [u8]>(&access);
+                while let Ok((key, _val)) = item {
+                    if let Ok(DatabaseDef::Index(..)) = from_ron(key) {
+                        name = Some(key.to_string());
+                    }
+                    item = cursor.next::<str, [u8]>(&access);
+                }
+                name.unwrap()
+            };
+            txn.commit().unwrap();
+            name
+        };
+
+        Database::open(&env, Some(&name), &DatabaseOptions::new(db::Flags::empty()))
+            .unwrap()
+            .delete()
+            .unwrap();
+
+        let db = Database::open(
+            &env,
+            Some(&name),
+            &DatabaseOptions::create_multimap::<Unaligned<i64>, Unaligned<u32>>(),
+        )
+        .unwrap();
+
+        let txn = WriteTransaction::new(&env).unwrap();
+        {
+            let mut access = txn.access();
+            for (id, n) in nums.iter().enumerate() {
+                access
+                    .put(
+                        &db,
+                        &Unaligned::new(*n),
+                        &Unaligned::new(id as u32 + 1),
+                        put::NODUPDATA,
+                    )
+                    .unwrap();
+            }
+        }
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn upgrade_format() {
+        use super::DatabaseDef;
+        use lmdb::ReadTransaction;
+        use ron::ser::to_string as to_db_name;
+
+        let path = "test_db/upgrade_format";
+        let nums = [3i64, -2, 0, -10, 5, 1];
+
+        {
+            let s = test_db("upgrade_format").unwrap();
+            let c = s.collection("test").unwrap();
+            for n in &nums {
+                c.insert(json!({ "n": n })).unwrap();
+            }
+            query!(index for c n int).unwrap();
+        }
+
+        downgrade_format(path, &nums);
+
+        let s = Storage::new(path, Options::default()).unwrap();
+        let c = s.collection("test").unwrap();
+
+        assert_found!(
+            c.find_with(None, Order::Field("n".into(), OrderKind::Asc), FindOptions::default()),
+            4,
+            2,
+            3,
+            6,
+            1,
+            5
+        );
+        assert_found!(query!(find in c where n in -5..2), 2, 3, 6);
+        assert_found!(query!(find in c where n < 0), 2, 4);
+        assert_found!(query!(find in c where n == -10), 4);
+
+        c.insert(json!({ "n": -7 })).unwrap();
+        assert_found!(query!(find in c where n < 0), 2, 4, 7);
+
+        let txn = ReadTransaction::new(s.clone()).unwrap();
+        assert_eq!(
+            txn.access()
+                .get::<str, str>(s.main_db(), &to_db_name(&DatabaseDef::Format).unwrap())
+                .unwrap(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn upgrade_format_interrupted() {
+        use super::storage::drop_index_dbs;
+        use lmdb::{open, Database, DatabaseOptions, EnvBuilder};
+        use std::sync::Arc;
+
+        let path = "test_db/upgrade_format_interrupted";
+        let nums = [3i64, -2, 0, -10, 5, 1];
+
+        {
+            let s = test_db("upgrade_format_interrupted").unwrap();
+            let c = s.collection("test").unwrap();
+            for n in &nums {
+                c.insert(json!({ "n": n })).unwrap();
+            }
+            query!(index for c n int).unwrap();
+        }
+
+        downgrade_format(path, &nums);
+
+        // the upgrading is interrupted after the indexes was dropped
+        {
+            let mut bld = EnvBuilder::new().unwrap();
+            bld.set_maxdbs(128).unwrap();
+            let env = Arc::new(unsafe { bld.open(path, open::Flags::empty(), 0o600) }.unwrap());
+            let main = Database::open(env.clone(), None, &DatabaseOptions::defaults()).unwrap();
+
+            assert_eq!(drop_index_dbs(&env, &main).unwrap().len(), 1);
+        }
+
+        let s = Storage::new(path, Options::default()).unwrap();
+        let c = s.collection("test").unwrap();
+
+        assert!(c.has_index("n").unwrap());
+        assert_found!(query!(find in c where n in -5..2), 2, 3, 6);
+        assert_found!(query!(find in c where n < 0), 2, 4);
+    }
+
+    #[test]
+    fn build_index_background() {
+        let s = test_db("build_index_background").unwrap();
+        let c = s.collection("test").unwrap();
+
+        for n in 0..100 {
+            c.insert(json!({ "n": n % 10 })).unwrap();
+        }
+
+        assert!(c
+            .ensure_index_with(
+                KeyField::new("n").with_type(KeyType::Int),
+                IndexOptions::background().with_batch(7),
+            )
+            .unwrap());
+        assert!(!c
+            .ensure_index_with(
+                KeyField::new("n").with_type(KeyType::Int),
+                IndexOptions::background(),
+            )
+            .unwrap());
+
+        // the writes during building must be indexed too
+        query!(update in c modify n = 10 where n == 1).unwrap();
+        c.insert(json!({ "n": 10 })).unwrap();
+
+        let mut tries = 0;
+        while c.get_indexes().unwrap()[0].build.is_some() {
+            assert!(tries < 500);
+            tries += 1;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let strict = Storage::new(
+            "test_db/build_index_background",
+            serde_json::from_value(json!({ "no_full_scan": true })).unwrap(),
+        )
+        .unwrap();
+        let c = strict.collection("test").unwrap();
+
+        assert_found!(
+            query!(find in c where n == 10),
+            2,
+            12,
+            22,
+            32,
+            42,
+            52,
+            62,
+            72,
+            82,
+            92,
+            101
+        );
+        assert_found!(query!(find in c where n == 1));
+        assert_eq!(query!(find Value in c where n == 3).unwrap().len(), 10);
+
+        assert!(!c.cancel_index("n").unwrap());
+        assert!(!c.cancel_index("x").unwrap());
+
+        // the readers isn't blocked while the building progress is locked
+        let index = c.get_index("n").unwrap().unwrap();
+        let progress = index.lock_build();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let c2 = c.clone();
+        std::thread::spawn(move || {
+            tx.send((
+                c2.get_indexes().unwrap().len(),
+                query!(find Value in c2 where n == 3).unwrap().len(),
+            ))
+            .unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), (1, 10));
+        drop(progress);
+    }
+
+    #[test]
+    fn verify_and_repair() {
+        use super::{to_value, RawDocument};
+        use lmdb::put;
+
+        let s = test_db("verify_and_repair").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        let integrity = c.verify().unwrap();
+        assert!(integrity.is_ok());
+        assert_eq!(integrity.documents, 7);
+        assert_eq!(integrity.indexes.len(), 6);
+
+        // break the indexes bypassing the collection
+        c.storage()
+            .write(|txn| {
+                let index = c.get_index("n.a").unwrap().unwrap();
+                let doc = c.get_txn(txn, Primary::Int(2)).unwrap().unwrap();
+                let mut access = txn.access();
+
+                index.update_index(&mut access, Some(&doc), None).unwrap();
+                index
+                    .update_index(&mut access, None, Some(&doc.with_id(Primary::Int(100))))
+                    .unwrap();
+
+                let doc = RawDocument::new(to_value(json!({ "s": "abc" })).unwrap())
+                    .with_id(Primary::Int(8));
+                access
+                    .put(
+                        &c,
+                        c.id_to_raw(&Primary::Int(8)).unwrap().as_slice(),
+                        &doc.to_bin_with_rev(1).unwrap(),
+                        put::Flags::empty(),
+                    )
+                    .unwrap();
+                Ok(())
+            })
+            .unwrap();
+
+        let integrity = c.verify().unwrap();
+        assert!(!integrity.is_ok());
+        assert_eq!(integrity.documents, 8);
+        assert_eq!(integrity.damaged().collect::<Vec<_>>(), vec!["s", "n.a"]);
+
+        let s_index = &integrity.indexes[0];
+        assert_eq!(s_index.missing, vec![Primary::Int(8)]);
+        assert_eq!(s_index.duplicates, vec![Primary::Int(1), Primary::Int(8)]);
+        assert!(s_index.dangling.is_empty());
+
+        let na_index = &integrity.indexes[5];
+        assert_eq!(na_index.missing, vec![Primary::Int(2)]);
+        assert_eq!(na_index.dangling, vec![Primary::Int(100)]);
+        assert!(na_index.duplicates.is_empty());
+
+        // the unique keys which is shared by documents cannot be repaired
+        assert!(c.repair().unwrap_err().is_duplicate());
+
+        c.storage()
+            .write(|txn| {
+                let mut access = txn.access();
+                access
+                    .del_key(&c, c.id_to_raw(&Primary::Int(8)).unwrap().as_slice())
+                    .unwrap();
+                Ok(())
+            })
+            .unwrap();
+
+        let integrity = c.repair().unwrap();
+        assert_eq!(integrity.damaged().collect::<Vec<_>>(), vec!["n.a"]);
+
+        assert!(c.verify().unwrap().is_ok());
+        assert_found!(query!(find in c where n.a == "t1"), 2, 5);
+        assert_found!(query!(find in c where s == "abc"), 1);
+    }
+
+    #[test]
+    fn collection_stats() {
+        let s = test_db("collection_stats").unwrap();
+        let c = s.collection("test").unwrap();
+
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.documents, 0);
+        assert_eq!(stats.total_size, 0);
+        assert!(stats.indexes.is_empty());
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        let stats = c.stats().unwrap();
+        assert_eq!(stats.documents, 7);
+        assert_eq!(stats.pages.data_entries, 7);
+        assert!(stats.total_size > 0);
+        assert!((stats.average_size - stats.total_size as f64 / 7.0).abs() < 1e-9);
+
+        assert_eq!(
+            stats
+                .indexes
+                .iter()
+                .map(|index| (index.path.as_str(), index.entries, index.keys))
+                .collect::<Vec<_>>(),
+            vec![
+                ("s", 7, 7),
+                ("b", 6, 2),
+                ("i", 11, 5),
+                ("f", 0, 0),
+                ("n.i", 5, 4),
+                ("n.a", 7, 3)
+            ]
+        );
+        assert_eq!(stats.indexes[0].pages.data_entries, 7);
+    }
+
+    #[test]
+    fn remove_eq_str() {
+        let s = test_db("remove_eq_str").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert_found!(query!(find in c where s == "def"), 2);
+        assert_eq!(query!(remove from c where s == "def").unwrap(), 1);
+        assert_found!(query!(find in c where s == "def"));
+        assert_found!(query!(find in c where s == "abc"), 1);
+    }
+
+    #[test]
+    fn remove_ge_int() {
+        let s = test_db("remove_ge_int").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert_found!(query!(find in c where i >= 4), 3, 4, 6);
+        assert_eq!(query!(remove from c where i >= 4).unwrap(), 3);
+        assert_found!(query!(find in c where i >= 4));
+        assert_found!(query!(find in c where i >= 2), 2, 5);
+    }
+
+    #[test]
+    fn update_set_eq_str() {
+        let s = test_db("update_set_eq_str").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert_found!(query!(find in c where s == "def"), 2);
+        assert_eq!(
+            query!(update in c modify s = "klm" where s == "def").unwrap(),
+            1
+        );
+        assert_found!(query!(find in c where s == "def"));
+        assert_found!(query!(find in c where s == "abc"), 1);
+        assert_found!(query!(find in c where s == "klm"), 2);
+    }
+
+    #[test]
+    fn aggregate_docs() {
+        let s = test_db("aggregate_docs").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert_eq!(c.count(None).unwrap(), 7);
+        assert_eq!(c.count(query!(@filter b == true)).unwrap(), 3);
+        assert_eq!(c.count(query!(@filter !(b == true))).unwrap(), 4);
+
+        // indexed
+        assert_eq!(
+            c.distinct(None, "n.i").unwrap(),
+            vec![KeyData::Int(-11), KeyData::Int(1), KeyData::Int(2), KeyData::Int(3)]
+        );
+        assert_eq!(
+            c.distinct(query!(@filter b == true), "n.a").unwrap(),
+            vec!["t2".into(), "t4".into()]
+        );
+        assert_eq!(c.min(None, "i").unwrap(), Some(KeyData::Int(1)));
+        assert_eq!(c.max(query!(@filter b == false), "i").unwrap(), Some(KeyData::Int(2)));
+
+        // unindexed
+        c.drop_index("i").unwrap();
+        assert_eq!(
+            c.distinct(query!(@filter b == true), "i").unwrap(),
+            vec![KeyData::Int(1), KeyData::Int(2), KeyData::Int(3), KeyData::Int(4), KeyData::Int(5)]
+        );
+        assert_eq!(c.min(None, "i").unwrap(), Some(KeyData::Int(1)));
+        assert_eq!(c.max(query!(@filter b == false), "i").unwrap(), Some(KeyData::Int(2)));
+        assert_eq!(c.max(None, "f").unwrap(), None);
+
+        // the keys of integer index cannot reproduce the float values
+        c.insert(json!({ "s": "x", "i": [], "n": { "i": 1.5 } })).unwrap();
+        assert_eq!(
+            c.distinct(None, "n.i").unwrap(),
+            vec![KeyData::Int(-11), KeyData::Int(1), KeyData::Int(2), KeyData::Int(3), KeyData::from(1.5)]
+        );
+        assert_eq!(c.max(None, "n.i").unwrap(), Some(KeyData::Int(3)));
+        c.insert(json!({ "s": "y", "i": [], "n": { "i": 3.5 } })).unwrap();
+        assert_eq!(c.max(None, "n.i").unwrap(), Some(KeyData::from(3.5)));
+        assert_eq!(query!(remove from c where s == "x" || s == "y").unwrap(), 2);
+
+        assert_eq!(
+            c.aggregate(None, &Aggregation::sum("i")).unwrap(),
+            Aggregated::Value(Some(KeyData::Int(32)))
+        );
+        assert_eq!(
+            c.aggregate(query!(@filter s == "def"), &Aggregation::avg("i")).unwrap(),
+            Aggregated::Value(Some(KeyData::from(1.5)))
+        );
+
+        let groups = c
+            .group_by(
+                None,
+                "b",
+                vec![
+                    ("n".into(), Accumulator::Count),
+                    ("sum".into(), Accumulator::Sum("n.i".into())),
+                    ("max".into(), Accumulator::Max("s".into())),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key, KeyData::Bool(false));
+        assert_eq!(groups[0].values["n"], Some(KeyData::Int(3)));
+        assert_eq!(groups[0].values["sum"], Some(KeyData::Int(3)));
+        assert_eq!(groups[0].values["max"], Some("def".into()));
+        assert_eq!(groups[1].values["sum"], Some(KeyData::Int(-6)));
+
+        let aggregation: Aggregation = serde_json::from_value(json!({
+            "$group": { "by": "b", "fields": { "n": "$count", "min": { "$min": "s" } } }
+        }))
+        .unwrap();
+        assert_eq!(
+            aggregation,
+            Aggregation::group("b")
+                .with("n", Accumulator::Count)
+                .with("min", Accumulator::Min("s".into()))
+        );
+        assert_eq!(
+            serde_json::to_value(c.aggregate(query!(@filter b == true), &aggregation).unwrap())
+                .unwrap(),
+            json!([{ "key": true, "values": { "n": 3, "min": "123" } }])
+        );
+    }
+
+    #[test]
+    fn update_returning_docs() {
+        let s = test_db("update_returning_docs").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        let docs = c
+            .update_returning::<Doc>(
+                query!(@filter b == true),
+                query!(@modify b = false),
+                Returning::Both,
+            )
+            .unwrap();
+        assert_eq!(
+            docs.iter().map(|doc| doc.id.as_int().unwrap()).collect::<Vec<_>>(),
+            vec![3, 4, 6]
+        );
+        assert_eq!(docs[0].old.as_ref().map(|doc| doc.b), Some(true));
+        assert_eq!(docs[0].new.as_ref().map(|doc| doc.b), Some(false));
+
+        let docs = c
+            .update_returning::<Doc>(
+                query!(@filter s == "abc"),
+                query!(@modify s = "klm"),
+                Returning::Before,
+            )
+            .unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(&docs[0].old.as_ref().unwrap().s, "abc");
+        assert!(docs[0].new.is_none());
+
+        let doc = c
+            .find_one_and_update::<Doc>(
+                None,
+                query!(@order by s <),
+                query!(@modify b = true),
+                Returning::After,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!((doc.id, doc.new.unwrap().s), (4.into(), "xyz".into()));
+        assert_found!(query!(find in c where b == true), 4);
+
+        assert!(c
+            .find_one_and_update::<Doc>(
+                query!(@filter s == "nop"),
+                Order::default(),
+                query!(@modify b = true),
+                Returning::After,
+            )
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn remove_returning_docs() {
+        let s = test_db("remove_returning_docs").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        let docs = c
+            .remove_returning::<Doc>(query!(@filter b == true))
+            .unwrap();
+        assert_eq!(
+            docs.iter().map(|doc| doc.s.as_str()).collect::<Vec<_>>(),
+            vec!["123", "xyz", "456"]
+        );
+        assert_found!(query!(find in c where b == true));
+
+        let doc = c
+            .find_one_and_delete::<Doc>(None, query!(@order by s <))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&doc.s, "def");
+        assert!(!c.has(2).unwrap());
+        assert!(c
+            .find_one_and_delete::<Doc>(query!(@filter s == "nop"), Order::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn upsert_update_or_insert() {
+        let s = test_db("upsert_update_or_insert").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert_eq!(
+            c.upsert(
+                query!(@filter s == "def"),
+                query!(@modify b = true),
+                json!({ "s": "def", "b": false })
+            )
+            .unwrap(),
+            None
+        );
+        assert_found!(query!(find in c where b == true), 2, 3, 4, 6);
+
+        // the modifier is applied to the default document too
+        assert_eq!(
+            c.upsert(
+                query!(@filter s == "klm"),
+                query!(@modify b = true),
+                json!({ "s": "klm", "b": false })
+            )
+            .unwrap(),
+            Some(8.into())
+        );
+        assert_found!(query!(find in c where b == true), 2, 3, 4, 6, 8);
+
+        assert_eq!(
+            c.upsert(
+                query!(@filter s == "nop"),
+                query!(@modify s = "nop"),
+                json!({ "$": 12, "s": "xyz" })
+            )
+            .unwrap(),
+            Some(12.into())
+        );
+        assert_found!(query!(find in c where s == "nop"), 12);
+    }
+
+    #[test]
+    fn put_if_revision() {
+        let s = test_db("put_if_revision").unwrap();
+        let c = s.collection("test").unwrap();
+
+        assert_eq!(c.revision(1).unwrap(), None);
+        assert_eq!(c.put_if_revision(json!({ "$": 1, "s": "abc" }), 0).unwrap(), 1);
+        assert!(c
+            .put_if_revision(json!({ "$": 1, "s": "abc" }), 0)
+            .unwrap_err()
+            .is_conflict());
+
+        assert_eq!(query!(update in c modify s = "def").unwrap(), 1);
+        assert_eq!(c.revision(1).unwrap(), Some(2));
+
+        let err = c
+            .put_if_revision(json!({ "$": 1, "s": "klm" }), 1)
+            .unwrap_err();
+        assert!(err.is_conflict());
+        assert_eq!(
+            err.to_string(),
+            "Conflict error: Document 1 has revision 2 but 1 expected"
+        );
+
+        assert_eq!(c.put_if_revision(json!({ "$": 1, "s": "klm" }), 2).unwrap(), 3);
+        let (doc, rev) = c.get_with_revision::<serde_json::Value>(1).unwrap().unwrap();
+        assert_eq!((doc["s"].as_str(), rev), (Some("klm"), 3));
+
+        c.put(json!({ "$": 1, "s": "xyz" })).unwrap();
+        assert_eq!(c.revision(1).unwrap(), Some(4));
+
+        assert_eq!(
+            c.put_if_revision(json!({ "$": 2, "s": "abc" }), 1)
+                .unwrap_err()
+                .to_string(),
+            "Conflict error: Document 2 does not exists but revision 1 expected"
+        );
+
+        // the revisions is not reused after removing
+        assert!(c.delete(1).unwrap());
+        assert_eq!(c.put_if_revision(json!({ "$": 1, "s": "abc" }), 0).unwrap(), 5);
+        assert!(c
+            .put_if_revision(json!({ "$": 1, "s": "def" }), 1)
+            .unwrap_err()
+            .is_conflict());
+
+        // the revisions is not reset by loading
+        c.load(vec![json!({ "$": 1, "s": "def" })]).unwrap();
+        assert_eq!(c.revision(1).unwrap(), Some(6));
+    }
+
+    #[test]
+    fn transaction_commit() {
+        let s = test_db("transaction_commit").unwrap();
+        let c = s.collection("test").unwrap();
+        let l = s.collection("log").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert!(s
+            .transaction(|txn| {
+                let c = txn.collection("test")?;
+                let l = txn.collection("log")?;
+
+                assert_eq!(query!(remove from c where s == "def")?, 1);
+                assert_eq!(
+                    query!(find in c)?
+                        .into_iter()
+                        .map(|doc: Value| get_id(doc).unwrap())
+                        .collect::<Vec<_>>(),
+                    vec![1, 3, 4, 5, 6, 7]
+                );
+
+                assert_eq!(l.insert(json!({ "s": "def" }))?, 1);
+                assert_eq!(l.insert(json!({ "s": "abc" }))?, 2);
+
+                l.has(2)
+            })
+            .unwrap());
+
+        assert_found!(query!(find in c where s == "def"));
+        assert_found!(query!(find in l), 1, 2);
+    }
+
+    #[test]
+    fn transaction_rollback() {
+        let s = test_db("transaction_rollback").unwrap();
+        let c = s.collection("test").unwrap();
+        let l = s.collection("log").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        assert!(s
+            .transaction(|txn| {
+                let c = txn.collection("test")?;
+                let l = txn.collection("log")?;
+
+                assert_eq!(query!(remove from c where s == "def")?, 1);
+                l.insert(json!({ "s": "def" }))?;
+
+                // duplicate unique key aborts whole transaction
+                c.put(json!({ "$": 1, "s": "abc" }))?;
+                c.insert(json!({ "s": "abc" }))
+            })
+            .is_err());
+
+        assert_found!(query!(find in c where s == "def"), 2);
+        assert_found!(query!(find in l));
+
+        assert!(s.transaction(|txn| txn.collection("missing").map(|_| ())).is_err());
+    }
+
+    #[test]
+    fn iterate_consistent() {
+        let s = test_db("iterate_consistent").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+
+        let mut docs = query!(find Doc in c where b == true).unwrap();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(&docs.next().unwrap().unwrap().s, "123");
+
+        assert_eq!(query!(remove from c where b == true).unwrap(), 3);
+        assert_eq!(query!(update in c modify s = "klm" where s == "abc").unwrap(), 1);
+
+        assert_eq!(&docs.next().unwrap().unwrap().s, "xyz");
+        assert_eq!(&docs.next().unwrap().unwrap().s, "456");
+        assert!(docs.next().is_none());
+
+        assert_found!(query!(find in c where b == true));
+    }
+
+    #[test]
+    fn snapshot_queries() {
+        let s = test_db("snapshot_queries").unwrap();
+        let c = s.collection("test").unwrap();
+        let l = s.collection("log").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+        l.insert(json!({ "s": "abc" })).unwrap();
+
+        let snap = s.snapshot().unwrap();
+        let sc = snap.collection("test").unwrap();
+        let sl = snap.collection("log").unwrap();
+
+        c.delete(1).unwrap();
+        l.insert(json!({ "s": "def" })).unwrap();
+
+        assert!(sc.has(1).unwrap());
+        assert_eq!(&sc.get::<Doc>(1).unwrap().unwrap().s, "abc");
+        assert_found!(query!(find in sc where s == "abc"), 1);
+        assert_eq!(sl.last_id().unwrap(), Some(1.into()));
+
+        assert!(!c.has(1).unwrap());
+        assert_eq!(l.last_id().unwrap(), Some(2.into()));
+
+        assert!(snap.collection("missing").is_err());
+    }
+
+    #[test]
+    fn storage_backup() {
+        let s = test_db("storage_backup").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+        c.delete(3).unwrap();
+
+        for (path, compact) in &[
+            ("test_db/storage_backup.copy", false),
+            ("test_db/storage_backup.compact", true),
+        ] {
+            let _ = remove_dir_all(path);
+
+            s.backup(path, *compact).unwrap();
+            // the backup is already exists
+            assert!(s.backup(path, *compact).is_err());
+
+            let b = Storage::new(path, Options::default()).unwrap();
+            let c = b.collection("test").unwrap();
+
+            assert!(c.has_index("s").unwrap());
+            assert_found!(query!(find in c where b == true), 4, 6);
+            assert_eq!(c.last_id().unwrap(), Some(7.into()));
+        }
+
+        assert!(s.backup("test_db/storage_backup", true).is_err());
+    }
+
+    #[test]
+    fn export_import() {
+        let s = test_db("export_import").unwrap();
+        let c = s.collection("test").unwrap();
+        let l = s.collection_with("log", PrimaryType::String).unwrap();
+
+        mk_index(&c).unwrap();
+        fill_data(&c).unwrap();
+        l.insert_with_id(json!({ "$": "abc", "s": "def" })).unwrap();
+
+        for format in &[DataFormat::Json, DataFormat::Cbor] {
+            let mut data = Vec::new();
+            let mut states = Vec::new();
+            let opts = ExportOptions::default().format(*format);
+            assert_eq!(
+                s.export(&mut data, &opts, |state| states.push(state.clone()))
+                    .unwrap(),
+                8
+            );
+            assert_eq!(states.len(), 2);
+
+            let d = test_db("export_import.dest").unwrap();
+            let opts = ImportOptions::default().format(*format);
+            assert_eq!(d.import(data.as_slice(), &opts, |_| ()).unwrap(), 8);
+
+            let c = d.collection("test").unwrap();
+            let l = d.collection("log").unwrap();
+
+            assert_eq!(c.get_indexes().unwrap().len(), 6);
+            assert_found!(query!(find in c where b == true), 3, 4, 6);
+            assert_eq!(l.primary_type(), PrimaryType::String);
+            assert!(l.has("abc").unwrap());
+
+            // the documents already exists
+            assert!(d.import(data.as_slice(), &opts, |_| ()).is_err());
+
+            c.delete(3).unwrap();
+            c.insert(json!({ "s": "new" })).unwrap();
+
+            let opts = opts.mode(ImportMode::Upsert).collection("test");
+            assert_eq!(d.import(data.as_slice(), &opts, |_| ()).unwrap(), 7);
+            assert_found!(query!(find in c where b == true), 3, 4, 6);
+            assert_found!(query!(find in c where s == "new"), 8);
+
+            let opts = opts.mode(ImportMode::Replace);
+            assert_eq!(d.import(data.as_slice(), &opts, |_| ()).unwrap(), 7);
+            assert_found!(query!(find in c where s == "new"));
+        }
+    }
+
+    #[test]
+    fn watch_changes() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let s = test_db("watch_changes").unwrap();
+        let c = s.collection("test").unwrap();
+
+        mk_index(&c).unwrap();
+
+        let mut all = c.watch(None).unwrap();
+        let mut abc = c.watch(query!(@filter s == "abc")).unwrap();
+
+        fill_data(&c).unwrap();
+
+        let change = all.next().unwrap();
+        assert_eq!(change.kind, ChangeKind::Insert);
+        assert_eq!(change.id, 1);
+        assert!(change.old.is_none());
+        assert_eq!(&change.new_doc::<Doc>().unwrap().unwrap().s, "abc");
+        assert_eq!(all.by_ref().take(6).count(), 6);
+        assert_eq!(abc.next().unwrap().id, 1);
+
+        assert_eq!(query!(update in c modify s = "klm" where s == "abc").unwrap(), 1);
+        c.delete(2).unwrap();
+
+        let change = all.next().unwrap();
+        assert_eq!(change.kind, ChangeKind::Update);
+        assert_eq!(&change.old_doc::<Doc>().unwrap().unwrap().s, "abc");
+        assert_eq!(&change.new_doc::<Doc>().unwrap().unwrap().s, "klm");
+        let change = all.next().unwrap();
+        assert_eq!((change.kind, change.id), (ChangeKind::Delete, 2.into()));
+        assert!(change.new.is_none());
+
+        assert_eq!(abc.next().unwrap().kind, ChangeKind::Update);
+        assert!(abc.try_next().is_none());
+
+        // aborted transaction sends nothing
+        assert!(s
+            .transaction(|txn| {
+                let c = txn.collection("test")?;
+                c.delete(3)?;
+                // duplicate unique key
+                c.insert(json!({ "s": "klm" }))
+            })
+            .is_err());
+        assert!(all.next_timeout(Duration::from_millis(10)).is_none());
+
+        c.purge().unwrap();
+        assert_eq!(
+            all.by_ref()
+                .take(6)
+                .filter(|change| change.kind == ChangeKind::Delete)
+                .count(),
+            6
+        );
+        assert!(all.try_next().is_none());
+
+        // panicked transaction sends nothing too
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            s.transaction(|txn| -> Result<()> {
+                txn.collection("test")?.insert(&json!({ "s": "xyz", "b": true, "i": [] }))?;
+                panic!("Transaction failed");
+            })
+        }))
+        .is_err());
+        c.insert(&json!({ "s": "abc", "b": false, "i": [] })).unwrap();
+
+        let change = all.next().unwrap();
+        assert_eq!(change.kind, ChangeKind::Insert);
+        assert_eq!(&change.new_doc::<Doc>().unwrap().unwrap().s, "abc");
+        assert!(all.try_next().is_none());
+    }
+
+    fn fill_compound(c: &Collection) -> Result<()> {
+        c.ensure_index_field(
+            KeyField::new("s")
+                .with_type(KeyType::String)
+                .with_component("n", KeyType::Int),
+        )?;
+        c.insert(json!({ "s": "a", "n": 3 }))?;
+        c.insert(json!({ "s": "b", "n": 1 }))?;
+        c.insert(json!({ "s": "a", "n": 1 }))?;
+        c.insert(json!({ "s": "a", "n": 2 }))?;
+        c.insert(json!({ "s": "b", "n": 5 }))?;
+        c.insert(json!({ "s": "c" }))?;
+        Ok(())
+    }
+
+    fn compound_filter(filter: serde_json::Value) -> Option<Filter> {
+        Some(serde_json::from_value(filter).unwrap())
+    }
+
+    #[test]
+    fn find_compound_prefix() {
+        let s = test_db("find_compound_prefix").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_compound(&c).unwrap();
+
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$eq": "a" } })), Order::default()),
+            1,
+            3,
+            4
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$eq": ["a", 1] } })), Order::default()),
+            3
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$in": ["c", ["b", 5]] } })), Order::default()),
+            5
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": "$has" })), Order::default()),
+            1,
+            2,
+            3,
+            4,
+            5
+        );
+    }
+
+    #[test]
+    fn find_compound_range() {
+        let s = test_db("find_compound_range").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_compound(&c).unwrap();
+
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$bw": [["a", 2], true, ["a", 3], true] } })), Order::default()),
+            1,
+            4
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$gt": ["a", 1] } })), Order::default()),
+            1,
+            2,
+            4,
+            5
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$gt": "a" } })), Order::default()),
+            2,
+            5
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$le": "a" } })), Order::default()),
+            1,
+            3,
+            4
+        );
+        assert_found!(
+            c.find(compound_filter(json!({ "s,n": { "$lt": ["b", 5] } })), Order::default()),
+            1,
+            2,
+            3,
+            4
+        );
+    }
+
+    #[test]
+    fn find_compound_order() {
+        let s = test_db("find_compound_order").unwrap();
+        let c = s.collection("test").unwrap();
+
+        fill_compound(&c).unwrap();
+
+        assert_found!(
+            c.find(None, Order::Field("s,n".into(), OrderKind::Asc)),
+            3,
+            4,
+            1,
+            2,
+            5
+        );
+        assert_found!(
+            c.find(
+                compound_filter(json!({ "s,n": { "$eq": "a" } })),
+                Order::Field("s,n".into(), OrderKind::Desc)
+            ),
+            1,
+            4,
+            3
+        );
+        assert_found!(
+            c.find_with(
+                None,
+                Order::Field("s,n".into(), OrderKind::Asc),
+                FindOptions::default().after(4)
+            ),
+            1,
+            2,
+            5
+        );
+    }
+
+    // derive
+    #[derive(Debug, Clone, Serialize, Deserialize, Document)]
+    struct RootDoc {
+        #[document(primary)]
+        id: Option<Primary>,
+
+        #[document(unique)]
         title: String,
 
         #[document(nested)]
@@ -842,11 +2929,55 @@ mod tests {
         meta: MetaData,
     }
 
+    #[derive(Debug, Clone, Serialize, Deserialize, Document)]
+    #[document(unique(title, meta))]
+    #[document(nested)]
+    struct CompoundData {
+        title: String,
+
+        #[serde(rename = "rev")]
+        meta: u32,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Document)]
+    #[document(spatial(lat, lon))]
+    #[document(nested)]
+    struct SpatialData {
+        #[document(spatial)]
+        home: GeoPoint,
+
+        lat: f64,
+        lon: f64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Document)]
+    struct NamedDoc {
+        #[document(primary string)]
+        name: String,
+        value: u32,
+    }
+
     #[test]
     fn derive_primary() {
         assert_eq!(MetaData::primary_field().as_ref(), "$");
         assert_eq!(LinkData::primary_field().as_ref(), "$");
         assert_eq!(RootDoc::primary_field().as_ref(), "id");
+        assert_eq!(RootDoc::primary_type(), PrimaryType::Int);
+        assert_eq!(NamedDoc::primary_type(), PrimaryType::String);
+    }
+
+    #[test]
+    fn derived_primary_type() {
+        let s = test_db("derived_primary_type").unwrap();
+        let c = s.collection_for::<NamedDoc>("test").unwrap();
+
+        let doc = NamedDoc {
+            name: "first".into(),
+            value: 1,
+        };
+
+        assert_eq!(c.insert_with_id(&doc).unwrap(), "first");
+        assert_eq!(c.get::<NamedDoc>("first").unwrap(), Some(doc));
     }
 
     #[test]
@@ -871,6 +3002,28 @@ mod tests {
                 .with_field(("links.title", KeyType::String, IndexKind::Index))
                 .with_field(("links.meta.keywords", KeyType::String, IndexKind::Index))
         );
+
+        assert_eq!(
+            CompoundData::key_fields().with_parent("data"),
+            KeyFields::new().with_field(
+                KeyField::new("data.title")
+                    .with_type(KeyType::String)
+                    .with_kind(IndexKind::Unique)
+                    .with_component("data.rev", KeyType::Int)
+            )
+        );
+
+        assert_eq!(
+            SpatialData::key_fields(),
+            KeyFields::new()
+                .with_field(("home", KeyType::Float, IndexKind::Spatial))
+                .with_field(
+                    KeyField::new("lat")
+                        .with_type(KeyType::Float)
+                        .with_kind(IndexKind::Spatial)
+                        .with_component("lon", KeyType::Float)
+                )
+        );
     }
 
     #[test]
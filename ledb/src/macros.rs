@@ -137,6 +137,12 @@ macro_rules! _query_impl {
     (@index_list_kind $args:tt, $index:tt, $path:tt, $type:ident, unique $($tokens:tt)*) => (
         _query_impl!(@index_list_out $args, $index, $path, $type, unique, $($tokens)*)
     );
+    (@index_list_kind $args:tt, $index:tt, $path:tt, $type:ident, fulltext $($tokens:tt)*) => (
+        _query_impl!(@index_list_out $args, $index, $path, $type, fulltext, $($tokens)*)
+    );
+    (@index_list_kind $args:tt, $index:tt, $path:tt, $type:ident, spatial $($tokens:tt)*) => (
+        _query_impl!(@index_list_out $args, $index, $path, $type, spatial, $($tokens)*)
+    );
     (@index_list_kind $args:tt, $index:tt, $path:tt, $type:ident, $($tokens:tt)*) => (
         _query_impl!(@index_list_out $args, $index, $path, $type, index, $($tokens)*)
     );
@@ -163,6 +169,8 @@ macro_rules! _query_impl {
     // index kinds
     (@index_kind index) => ( $crate::IndexKind::Index );
     (@index_kind unique) => ( $crate::IndexKind::Unique );
+    (@index_kind fulltext) => ( $crate::IndexKind::FullText );
+    (@index_kind spatial) => ( $crate::IndexKind::Spatial );
     // key types
     (@key_type integer) => ( $crate::KeyType::Int );
     (@key_type int) => ( $crate::KeyType::Int );
@@ -172,6 +180,7 @@ macro_rules! _query_impl {
     (@key_type string) => ( $crate::KeyType::String );
     (@key_type str) => ( $crate::KeyType::String );
     (@key_type text) => ( $crate::KeyType::String );
+    (@key_type folded) => ( $crate::KeyType::Folded );
     (@key_type binary) => ( $crate::KeyType::Binary );
     (@key_type bin) => ( $crate::KeyType::Binary );
     (@key_type bytes) => ( $crate::KeyType::Binary );
@@ -198,6 +207,9 @@ macro_rules! _query_impl {
     (@find_filter $args:tt, $filter:tt, order by $($field:ident).+ desc) => (
         _query_impl!(@find_impl $args, $filter, [ by $($field).+ desc ])
     );
+    (@find_filter $args:tt, $filter:tt, order by $($field:ident).+ near $point:expr) => (
+        _query_impl!(@find_impl $args, $filter, [ by $($field).+ near $point ])
+    );
     (@find_filter $args:tt, $filter:tt, order by $($field:ident).+) => (
         _query_impl!(@find_impl $args, $filter, [ by $($field).+ ])
     );
@@ -289,6 +301,9 @@ macro_rules! _query_impl {
     (@order_field [ $($path:tt)+ ] . $field:ident $($tokens:tt)*) => ( _query_impl!(@order_field [ $($path)+ . $field ] $($tokens)*) );
     (@order_field [ $($path:tt)+ ] $($tokens:tt)*) => ( _query_impl!(@order_field_impl [ $($path)+ ], $($tokens)*) );
 
+    (@order_field_impl [ $($field:tt)+ ], near $point:expr) => (
+        $crate::Order::near(_query_impl!(@field $($field)+), $point)
+    );
     (@order_field_impl [ $($field:tt)+ ], $($order:tt)*) => (
         $crate::Order::field(_query_impl!(@field $($field)+), _query_impl!(@order_kind $($order)*))
     );
@@ -474,6 +489,38 @@ macro_rules! _query_impl {
         _query_impl!(@filter_comp_impl $field, Bw, $crate::KeyData::from($range.start), true, $crate::KeyData::from($range.end), false)
     );
 
+    // starts with
+    (@filter_comp $field:tt ^= $value:expr) => (
+        _query_impl!(@filter_comp_impl $field, Prefix, String::from($value))
+    );
+    // equal ignoring case
+    (@filter_comp $field:tt ~= $value:expr) => (
+        _query_impl!(@filter_comp_impl $field, IEq, String::from($value))
+    );
+    // starts with ignoring case
+    (@filter_comp $field:tt ~^= $value:expr) => (
+        _query_impl!(@filter_comp_impl $field, IPrefix, String::from($value))
+    );
+    // matches regular expression
+    (@filter_comp $field:tt =~ $value:expr) => (
+        _query_impl!(@filter_comp_impl $field, Regex, $crate::WrappedRegex($value.parse().unwrap()))
+    );
+
+    // matches full-text query
+    (@filter_comp $field:tt match $value:expr) => (
+        _query_impl!(@filter_comp_impl $field, Match, String::from($value))
+    );
+
+    // point inside the box
+    (@filter_comp $field:tt within $value:expr) => (
+        _query_impl!(@filter_comp_impl $field, Within, $crate::GeoBox::from($value))
+    );
+
+    // point inside the circle
+    (@filter_comp $field:tt near $point:expr, $radius:expr) => (
+        _query_impl!(@filter_comp_impl $field, Near, $crate::GeoPoint::from($point), $crate::OrderedFloat($radius as f64))
+    );
+
     // has value (field exists or not null)
     (@filter_comp $field:tt ?) => (
         _query_impl!(@filter_comp_impl $field, Has)
@@ -755,6 +802,27 @@ mod test {
             assert_eq!(query!(@filter f?), json_val!({ "f": "$has" }));
         }
 
+        #[test]
+        fn comp_str() {
+            assert_eq!(query!(@filter f ^= "ab"), json_val!({ "f": { "$prefix": "ab" } }));
+            assert_eq!(query!(@filter f ~= "Ab"), json_val!({ "f": { "$ieq": "Ab" } }));
+            assert_eq!(query!(@filter f ~^= "Ab"), json_val!({ "f": { "$iprefix": "Ab" } }));
+            assert_eq!(query!(@filter f =~ "^a.c$"), json_val!({ "f": { "$regex": "^a.c$" } }));
+            assert_eq!(query!(@filter f match "a b"), json_val!({ "f": { "$match": "a b" } }));
+            assert_eq!(
+                query!(@filter f within ((1.0, 2.0), (3.0, 4.0))),
+                json_val!({ "f": { "$within": { "sw": { "lat": 1.0, "lon": 2.0 }, "ne": { "lat": 3.0, "lon": 4.0 } } } })
+            );
+            assert_eq!(
+                query!(@filter f near (1.0, 2.0), 500),
+                json_val!({ "f": { "$near": [{ "lat": 1.0, "lon": 2.0 }, 500.0] } })
+            );
+            assert_eq!(
+                query!(@filter !f =~ "^a"),
+                json_val!({ "$not": { "f": { "$regex": "^a" } } })
+            );
+        }
+
         #[test]
         fn cond_not() {
             assert_eq!(query!(@filter !a?), json_val!({ "$not": { "a": "$has" } }));
@@ -908,6 +976,14 @@ mod test {
                 json_val!({ "field.*.subfield": "$desc" })
             );
         }
+
+        #[test]
+        fn field_near() {
+            assert_eq!(
+                query!(@order by a.loc near (1.5, 2.0)),
+                json_val!({ "a.loc": { "$near": { "lat": 1.5, "lon": 2.0 } } })
+            );
+        }
     }
 
     mod modify {
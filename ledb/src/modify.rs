@@ -62,6 +62,8 @@ impl PartialEq for WrappedRegex {
     }
 }
 
+impl Eq for WrappedRegex {}
+
 impl Deref for WrappedRegex {
     type Target = Regex;
 
@@ -0,0 +1,68 @@
+use std::str::from_utf8;
+
+use byteorder::{ByteOrder, NativeEndian};
+use lmdb::{DatabaseOptions, Unaligned};
+
+use super::{Primary, PrimaryType, Result, ResultWrap};
+
+/// Convert primary key/identifier into specified type
+pub(crate) fn to_primary(typ: PrimaryType, id: &Primary) -> Result<Primary> {
+    match id.to_type(typ) {
+        Some(Primary::String(ref id)) if id.is_empty() => {
+            Err("Primary key must not be empty").wrap_err()
+        }
+        Some(Primary::Binary(ref id)) if id.is_empty() => {
+            Err("Primary key must not be empty").wrap_err()
+        }
+        Some(id) => Ok(id),
+        None => Err(format!("Invalid {} primary key: {}", typ, id)).wrap_err(),
+    }
+}
+
+/// Convert primary key/identifier into binary representation
+pub(crate) fn primary_to_raw(typ: PrimaryType, id: &Primary) -> Result<Vec<u8>> {
+    Ok(match to_primary(typ, id)? {
+        Primary::Int(id) if typ == PrimaryType::Int => {
+            let mut raw = vec![0; 4];
+            NativeEndian::write_u32(&mut raw, id as u32);
+            raw
+        }
+        Primary::Int(id) => {
+            let mut raw = vec![0; 8];
+            NativeEndian::write_u64(&mut raw, id);
+            raw
+        }
+        Primary::String(id) => id.into_bytes(),
+        Primary::Binary(id) => id,
+    })
+}
+
+/// Restore primary key/identifier from binary representation
+pub(crate) fn primary_from_raw(typ: PrimaryType, raw: &[u8]) -> Result<Primary> {
+    Ok(match typ {
+        PrimaryType::Int => {
+            if raw.len() != 4 {
+                return Err("Int primary key must be 4 bytes length".into());
+            }
+            Primary::Int(u64::from(NativeEndian::read_u32(raw)))
+        }
+        PrimaryType::BigInt => {
+            if raw.len() != 8 {
+                return Err("BigInt primary key must be 8 bytes length".into());
+            }
+            Primary::Int(NativeEndian::read_u64(raw))
+        }
+        PrimaryType::String => Primary::String(from_utf8(raw).wrap_err()?.into()),
+        PrimaryType::Binary => Primary::Binary(raw.into()),
+    })
+}
+
+/// The options of collection database
+pub(crate) fn primary_db_options(typ: PrimaryType) -> DatabaseOptions {
+    match typ {
+        PrimaryType::Int => DatabaseOptions::create_map::<Unaligned<u32>>(),
+        PrimaryType::BigInt => DatabaseOptions::create_map::<Unaligned<u64>>(),
+        PrimaryType::String => DatabaseOptions::create_map::<str>(),
+        PrimaryType::Binary => DatabaseOptions::create_map::<[u8]>(),
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, mem::replace};
+
+use serde::{Deserialize, Serialize};
+
+use super::{RawDocument, Value};
+
+/// Projection of document fields
+///
+/// The fields are specified by paths like `field.subfield`.
+/// The arrays are traversed transparently and the `*` matches any field of object,
+/// the same way as it goes for indexes.
+///
+/// The primary key/identifier of document is always kept.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Projection {
+    /// Keep only specified fields
+    #[serde(rename = "$include")]
+    Include(Vec<String>),
+    /// Remove specified fields
+    #[serde(rename = "$exclude")]
+    Exclude(Vec<String>),
+}
+
+impl Projection {
+    /// Create projection which keeps only specified fields
+    pub fn include<I, S>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Projection::Include(paths.into_iter().map(Into::into).collect())
+    }
+
+    /// Create projection which removes specified fields
+    pub fn exclude<I, S>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Projection::Exclude(paths.into_iter().map(Into::into).collect())
+    }
+
+    /// Apply projection to generic data
+    pub fn apply(&self, val: Value) -> Value {
+        self.compile().apply(val)
+    }
+
+    pub(crate) fn compile(&self) -> Projector {
+        match self {
+            Projection::Include(paths) => Projector::Include(Fields::new(paths)),
+            Projection::Exclude(paths) => Projector::Exclude(Fields::new(paths)),
+        }
+    }
+}
+
+/// The projection prepared to apply to many documents
+pub(crate) enum Projector {
+    Include(Fields),
+    Exclude(Fields),
+}
+
+impl Projector {
+    pub(crate) fn apply(&self, val: Value) -> Value {
+        match self {
+            Projector::Include(fields) => include_fields(&[fields], val).unwrap_or_else(empty_map),
+            Projector::Exclude(fields) => exclude_fields(&[fields], val),
+        }
+    }
+}
+
+/// The tree of field paths
+///
+/// The empty name marks the field which is selected entirely.
+///
+#[derive(Default)]
+pub(crate) struct Fields(BTreeMap<String, Fields>);
+
+impl Fields {
+    fn new<S: AsRef<str>>(paths: &[S]) -> Self {
+        let mut root = Fields::default();
+        for path in paths {
+            root.add(path.as_ref().split('.'));
+        }
+        root
+    }
+
+    fn add<'a, I: Iterator<Item = &'a str>>(&mut self, mut path: I) {
+        // the field which already selected entirely should not be narrowed
+        if self.is_leaf() {
+            return;
+        }
+        if let Some(name) = path.next() {
+            self.0.entry(name.into()).or_default().add(path);
+        } else {
+            self.0.clear();
+            self.0.insert(String::new(), Fields::default());
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.0.contains_key("")
+    }
+
+    /// Get the subtrees which matches field
+    fn get<'a>(nodes: &[&'a Fields], name: &Value) -> Vec<&'a Fields> {
+        let name = if let Value::Text(name) = name {
+            Some(name.as_str())
+        } else {
+            None
+        };
+        nodes
+            .iter()
+            .flat_map(|node| {
+                name.and_then(|name| node.0.get(name))
+                    .into_iter()
+                    .chain(node.0.get("*"))
+            })
+            .collect()
+    }
+}
+
+/// Apply projection to document when it specified
+pub(crate) fn project_doc(projector: Option<&Projector>, mut doc: RawDocument) -> RawDocument {
+    if let Some(projector) = projector {
+        let val = replace(&mut *doc, Value::Null);
+        *doc = projector.apply(val);
+    }
+    doc
+}
+
+fn empty_map() -> Value {
+    Value::Map(BTreeMap::new())
+}
+
+fn include_fields(nodes: &[&Fields], val: Value) -> Option<Value> {
+    if nodes.iter().any(|node| node.is_leaf()) {
+        return Some(val);
+    }
+    match val {
+        Value::Array(vals) => Some(Value::Array(
+            vals.into_iter()
+                .filter_map(|val| include_fields(nodes, val))
+                .collect(),
+        )),
+        Value::Map(vals) => Some(Value::Map(
+            vals.into_iter()
+                .filter_map(|(key, val)| {
+                    let subs = Fields::get(nodes, &key);
+                    if subs.is_empty() {
+                        None
+                    } else {
+                        include_fields(&subs, val).map(|val| (key, val))
+                    }
+                })
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+fn exclude_fields(nodes: &[&Fields], val: Value) -> Value {
+    match val {
+        Value::Array(vals) => Value::Array(
+            vals.into_iter()
+                .map(|val| exclude_fields(nodes, val))
+                .collect(),
+        ),
+        Value::Map(vals) => Value::Map(
+            vals.into_iter()
+                .filter_map(|(key, val)| {
+                    let subs = Fields::get(nodes, &key);
+                    if subs.iter().any(|node| node.is_leaf()) {
+                        None
+                    } else if subs.is_empty() {
+                        Some((key, val))
+                    } else {
+                        Some((key, exclude_fields(&subs, val)))
+                    }
+                })
+                .collect(),
+        ),
+        val => val,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Projection;
+    use serde_json::from_str;
+
+    fn project(projection: &Projection, doc: &str) -> serde_json::Value {
+        let doc: serde_json::Value = from_str(doc).unwrap();
+        let val = projection.apply(serde_cbor::value::to_value(&doc).unwrap());
+        serde_cbor::value::from_value(val).unwrap()
+    }
+
+    #[test]
+    fn include() {
+        let projection = Projection::include(vec!["a", "b.c", "d.*.e"]);
+
+        assert_eq!(
+            project(
+                &projection,
+                r#"{"a":{"x":1},"b":[{"c":1,"d":2},{"d":3}],"d":{"f":{"e":1,"g":2},"h":{"e":2}},"e":1}"#
+            ),
+            from_str::<serde_json::Value>(
+                r#"{"a":{"x":1},"b":[{"c":1},{}],"d":{"f":{"e":1},"h":{"e":2}}}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn exclude() {
+        let projection = Projection::exclude(vec!["a", "b.c", "d.*.e"]);
+
+        assert_eq!(
+            project(
+                &projection,
+                r#"{"a":{"x":1},"b":[{"c":1,"d":2},{"d":3}],"d":{"f":{"e":1,"g":2},"h":{"e":2}},"e":1}"#
+            ),
+            from_str::<serde_json::Value>(
+                r#"{"b":[{"d":2},{"d":3}],"d":{"f":{"g":2},"h":{}},"e":1}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn serde() {
+        assert_eq!(
+            serde_json::to_string(&Projection::include(vec!["a", "b.c"])).unwrap(),
+            r#"{"$include":["a","b.c"]}"#
+        );
+        assert_eq!(
+            from_str::<Projection>(r#"{"$exclude":["a"]}"#).unwrap(),
+            Projection::exclude(vec!["a"])
+        );
+    }
+}
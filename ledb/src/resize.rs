@@ -0,0 +1,90 @@
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// The tracker of active transactions
+///
+/// The memory map may be resized only when no transactions is active in the current process.
+/// The long-lived transactions like documents iterators, snapshots and backups may not end soon,
+/// so the map isn't resized while it exists.
+///
+#[derive(Default)]
+pub(crate) struct Activity {
+    state: Mutex<ActivityState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct ActivityState {
+    // The number of active transactions
+    active: usize,
+    // The number of long-lived transactions
+    held: usize,
+}
+
+impl Activity {
+    fn state(&self) -> MutexGuard<'_, ActivityState> {
+        // the state is always consistent so the poisoning can be ignored
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register the transaction which begins
+    pub(crate) fn enter(&self) {
+        self.state().active += 1;
+    }
+
+    /// Unregister the transaction which ends
+    pub(crate) fn leave(&self) {
+        let mut state = self.state();
+        state.active -= 1;
+        self.changed.notify_all();
+    }
+
+    /// Register the long-lived transaction
+    pub(crate) fn hold(&self) {
+        self.state().held += 1;
+    }
+
+    /// Unregister the long-lived transaction
+    pub(crate) fn release(&self) {
+        let mut state = self.state();
+        state.held -= 1;
+        self.changed.notify_all();
+    }
+
+    /// Run the function when no transactions is active
+    ///
+    /// The new transactions isn't blocked while waiting for the active ones,
+    /// only while the function runs.
+    ///
+    /// Returns `None` immediately when the long-lived transactions exists
+    /// or when the active transactions isn't finished in time.
+    ///
+    pub(crate) fn exclusive<R, F>(&self, timeout: Duration, fun: F) -> Option<R>
+    where
+        F: FnOnce() -> R,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state();
+
+        while state.held == 0 && state.active > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+
+        if state.held > 0 || state.active > 0 {
+            return None;
+        }
+
+        // the lock is held so the new transactions waits until the function returns
+        Some(fun())
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    result::Result as StdResult,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Collection, RawDocument, Result, ResultWrap, Value};
+
+/// The type of value in schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SchemaType {
+    #[serde(rename = "null")]
+    Null,
+    #[serde(rename = "boolean")]
+    Boolean,
+    /// Integer number
+    #[serde(rename = "integer")]
+    Integer,
+    /// Integer or floating point number
+    #[serde(rename = "number")]
+    Number,
+    #[serde(rename = "string")]
+    String,
+    /// Binary data
+    #[serde(rename = "binary")]
+    Binary,
+    #[serde(rename = "array")]
+    Array,
+    #[serde(rename = "object")]
+    Object,
+}
+
+impl SchemaType {
+    /// Checks that the value has this type
+    fn check(self, val: &Value) -> bool {
+        use self::SchemaType::*;
+        matches!(
+            (self, val),
+            (Null, Value::Null)
+                | (Boolean, Value::Bool(_))
+                | (Integer, Value::Integer(_))
+                | (Number, Value::Integer(_))
+                | (Number, Value::Float(_))
+                | (String, Value::Text(_))
+                | (Binary, Value::Bytes(_))
+                | (Array, Value::Array(_))
+                | (Object, Value::Map(_))
+        )
+    }
+}
+
+impl Display for SchemaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SchemaType::*;
+        f.write_str(match self {
+            Null => "null",
+            Boolean => "boolean",
+            Integer => "integer",
+            Number => "number",
+            String => "string",
+            Binary => "binary",
+            Array => "array",
+            Object => "object",
+        })
+    }
+}
+
+/// Schema of documents
+///
+/// This is a subset of [JSON Schema](https://json-schema.org/) which supports
+/// the types of values, the required fields of objects, the enumerated values,
+/// the length of strings and the ranges of numbers.
+///
+/// *Note*: The primary key/identifier isn't a part of document which is validated.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    /// The type of value
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SchemaType>,
+    /// The schemas of object fields
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, Schema>,
+    /// The required fields of object
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    /// The schema of array items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<Schema>>,
+    /// The allowed values
+    #[serde(rename = "enum", default, skip_serializing_if = "Option::is_none")]
+    pub variants: Option<Vec<Value>>,
+    /// The min length of string
+    #[serde(rename = "minLength", default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// The max length of string
+    #[serde(rename = "maxLength", default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// The min value of number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    /// The max value of number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+}
+
+impl Schema {
+    /// Create schema which accepts any values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create schema for values of specified type
+    pub fn of(kind: SchemaType) -> Self {
+        Self {
+            kind: Some(kind),
+            ..Self::default()
+        }
+    }
+
+    /// Create schema for objects
+    pub fn object() -> Self {
+        Self::of(SchemaType::Object)
+    }
+
+    /// Add schema of optional field
+    pub fn field<S: ToString>(mut self, name: S, schema: Schema) -> Self {
+        self.properties.insert(name.to_string(), schema);
+        self
+    }
+
+    /// Add schema of required field
+    pub fn required<S: ToString>(mut self, name: S, schema: Schema) -> Self {
+        let name = name.to_string();
+        self.required.push(name.clone());
+        self.properties.insert(name, schema);
+        self
+    }
+
+    /// Add schema of array items
+    pub fn items(mut self, schema: Schema) -> Self {
+        self.items = Some(Box::new(schema));
+        self
+    }
+
+    /// Restrict values to the set of allowed
+    pub fn one_of<I, V>(mut self, vals: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        self.variants = Some(vals.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Restrict the length of strings
+    pub fn length(mut self, min: Option<usize>, max: Option<usize>) -> Self {
+        self.min_length = min;
+        self.max_length = max;
+        self
+    }
+
+    /// Restrict the range of numbers
+    pub fn range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.minimum = min;
+        self.maximum = max;
+        self
+    }
+
+    /// Validate the document
+    pub fn validate(&self, doc: &Value) -> StdResult<(), Invalid> {
+        let mut violations = Vec::new();
+        self.check(doc, "", &mut violations);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Invalid { violations })
+        }
+    }
+
+    fn check(&self, val: &Value, path: &str, out: &mut Vec<Violation>) {
+        if let Some(kind) = self.kind {
+            if !kind.check(val) {
+                violation(out, path, format!("expected {}", kind));
+                return;
+            }
+        }
+
+        if let Some(variants) = &self.variants {
+            if !variants.contains(val) {
+                violation(out, path, "value is not one of allowed");
+            }
+        }
+
+        match val {
+            Value::Text(val) => self.check_length(val.chars().count(), path, out),
+            Value::Integer(val) => self.check_range(*val as f64, path, out),
+            Value::Float(val) => self.check_range(*val, path, out),
+            Value::Array(vals) => {
+                if let Some(schema) = &self.items {
+                    for (index, val) in vals.iter().enumerate() {
+                        schema.check(val, &sub_path(path, &index.to_string()), out);
+                    }
+                }
+            }
+            Value::Map(map) => {
+                for name in &self.required {
+                    if !map.contains_key(&Value::Text(name.clone())) {
+                        violation(out, &sub_path(path, name), "missing required field");
+                    }
+                }
+                for (name, schema) in &self.properties {
+                    match map.get(&Value::Text(name.clone())) {
+                        // the optional fields may be null
+                        Some(Value::Null) if !self.required.contains(name) => (),
+                        Some(val) => schema.check(val, &sub_path(path, name), out),
+                        None => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn check_length(&self, len: usize, path: &str, out: &mut Vec<Violation>) {
+        if let Some(min) = self.min_length {
+            if len < min {
+                violation(out, path, format!("length is less than {}", min));
+            }
+        }
+        if let Some(max) = self.max_length {
+            if len > max {
+                violation(out, path, format!("length is greater than {}", max));
+            }
+        }
+    }
+
+    fn check_range(&self, val: f64, path: &str, out: &mut Vec<Violation>) {
+        if let Some(min) = self.minimum {
+            if val < min {
+                violation(out, path, format!("value is less than {}", min));
+            }
+        }
+        if let Some(max) = self.maximum {
+            if val > max {
+                violation(out, path, format!("value is greater than {}", max));
+            }
+        }
+    }
+}
+
+fn violation<S: Into<String>>(out: &mut Vec<Violation>, path: &str, message: S) {
+    out.push(Violation {
+        path: path.into(),
+        message: message.into(),
+    });
+}
+
+fn sub_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// The violation of schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    /// The path to the field (empty for document itself)
+    pub path: String,
+    /// The description of violation
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Document does not match the schema of collection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invalid {
+    /// The found violations of schema
+    pub violations: Vec<Violation>,
+}
+
+impl Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Document does not match schema")?;
+        for (index, violation) in self.violations.iter().enumerate() {
+            write!(f, "{} {}", if index > 0 { ";" } else { ":" }, violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Checks that the document matches the schema of collection
+    pub(crate) fn validate(&self, doc: &RawDocument) -> Result<()> {
+        if let Some(schema) = self.get_schema()? {
+            schema.validate(doc).wrap_err()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Schema, SchemaType, Violation};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn validate_value() {
+        let schema: Schema = from_value(json!({
+            "type": "object",
+            "required": ["name", "role"],
+            "properties": {
+                "name": { "type": "string", "minLength": 1, "maxLength": 8 },
+                "role": { "enum": ["admin", "user"] },
+                "age": { "type": "integer", "minimum": 0, "maximum": 150 },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            schema,
+            Schema::object()
+                .required(
+                    "name",
+                    Schema::of(SchemaType::String).length(Some(1), Some(8))
+                )
+                .required(
+                    "role",
+                    Schema::new().one_of(vec!["admin".to_string(), "user".to_string()])
+                )
+                .field(
+                    "age",
+                    Schema::of(SchemaType::Integer).range(Some(0.0), Some(150.0))
+                )
+                .field(
+                    "tags",
+                    Schema::of(SchemaType::Array).items(Schema::of(SchemaType::String))
+                )
+        );
+
+        let valid = json!({ "name": "alice", "role": "user", "tags": ["a", "b"] });
+        assert_eq!(schema.validate(&from_value(valid).unwrap()), Ok(()));
+
+        let invalid = json!({ "name": "", "age": 200.5, "tags": ["a", 1], "extra": null });
+        let violations = schema
+            .validate(&from_value(invalid).unwrap())
+            .unwrap_err()
+            .violations;
+
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    path: "role".into(),
+                    message: "missing required field".into(),
+                },
+                Violation {
+                    path: "age".into(),
+                    message: "expected integer".into(),
+                },
+                Violation {
+                    path: "name".into(),
+                    message: "length is less than 1".into(),
+                },
+                Violation {
+                    path: "tags.1".into(),
+                    message: "expected string".into(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            schema
+                .validate(&from_value(json!([])).unwrap())
+                .unwrap_err()
+                .to_string(),
+            "Document does not match schema: expected object"
+        );
+    }
+}
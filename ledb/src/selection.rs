@@ -16,7 +16,6 @@ impl Selection {
         Selection { ids, inv }
     }
 
-    #[allow(clippy::trivially_copy_pass_by_ref)]
     pub fn has(&self, id: &Primary) -> bool {
         self.inv ^ self.ids.contains(id)
     }
@@ -55,15 +54,11 @@ impl BitAnd for Selection {
             // a & universe == a
             (false, _, true, 0) => (self.ids, false),
             // a & !b
-            (false, n, true, m) if n < m => (self.ids.difference(&other.ids).cloned().collect(), false),
-            // a & !b == !(b | !a)
-            (false, _, true, _) => (other.ids.difference(&self.ids).cloned().collect(), true),
+            (false, _, true, _) => (self.ids.difference(&other.ids).cloned().collect(), false),
             // universe & b == b
             (true, 0, false, _) => (other.ids, false),
             // !a & b == b & !a
-            (true, n, false, m) if m < n => (other.ids.difference(&self.ids).cloned().collect(), false),
-            // !a & b == !(a | !b)
-            (true, _, false, _) => (self.ids.difference(&other.ids).cloned().collect(), true),
+            (true, _, false, _) => (other.ids.difference(&self.ids).cloned().collect(), false),
             // !a | !b
             (true, _, true, _) => (self.ids.union(&other.ids).cloned().collect(), true),
         };
@@ -83,89 +78,93 @@ impl BitOr for Selection {
 
 #[cfg(test)]
 mod test {
-    use super::Selection;
+    use super::{Primary, Selection};
+
+    fn sel<I: AsRef<[u64]>>(ids: I) -> Selection {
+        Selection::new(ids.as_ref().iter().cloned().map(Primary::from).collect(), false)
+    }
 
     #[test]
     fn not_inv_and_empty() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) &
+        assert_eq!(sel([1, 2, 3, 7, 9]) &
                    Selection::default(),
                    Selection::default());
     }
 
     #[test]
     fn not_inv_and_universe() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) &
+        assert_eq!(sel([1, 2, 3, 7, 9]) &
                    !Selection::default(),
-                   Selection::from(&[1, 2, 3, 7, 9]));
+                   sel([1, 2, 3, 7, 9]));
     }
 
     #[test]
     fn not_inv_and_not_inv() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) &
-                   Selection::from(&[2, 7, 5, 0, 4, 1]),
-                   Selection::from(&[1, 2, 7]));
+        assert_eq!(sel([1, 2, 3, 7, 9]) &
+                   sel([2, 7, 5, 0, 4, 1]),
+                   sel([1, 2, 7]));
     }
 
     #[test]
     fn not_inv_and_inv() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) &
-                   !Selection::from(&[2, 7, 5, 0, 4, 1]),
-                   Selection::from(&[3, 9]));
+        assert_eq!(sel([1, 2, 3, 7, 9]) &
+                   !sel([2, 7, 5, 0, 4, 1]),
+                   sel([3, 9]));
     }
 
     #[test]
     fn inv_and_not_inv() {
-        assert_eq!(Selection::from(&[2, 7, 5, 0, 4, 1]) &
-                   !Selection::from(&[1, 2, 3, 7, 9]),
-                   !Selection::from(&[9, 3]));
+        assert_eq!(sel([2, 7, 5, 0, 4, 1]) &
+                   !sel([1, 2, 3, 7, 9]),
+                   sel([0, 4, 5]));
     }
 
     #[test]
     fn inv_and_inv() {
-        assert_eq!(!Selection::from(&[1, 2, 3, 7, 9]) &
-                   !Selection::from(&[2, 7, 5, 0, 4, 1]),
-                   !Selection::from(&[0, 1, 2, 3, 4, 5, 7, 9]));
+        assert_eq!(!sel([1, 2, 3, 7, 9]) &
+                   !sel([2, 7, 5, 0, 4, 1]),
+                   !sel([0, 1, 2, 3, 4, 5, 7, 9]));
     }
 
     #[test]
     fn not_inv_or_empty() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) |
+        assert_eq!(sel([1, 2, 3, 7, 9]) |
                    Selection::default(),
-                   Selection::from(&[1, 2, 3, 7, 9]));
+                   sel([1, 2, 3, 7, 9]));
     }
 
     #[test]
     fn not_inv_or_universe() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) |
+        assert_eq!(sel([1, 2, 3, 7, 9]) |
                    !Selection::default(),
                    !Selection::default());
     }
 
     #[test]
     fn not_inv_or_not_inv() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) |
-                   Selection::from(&[2, 7, 5, 0, 4, 1]),
-                   Selection::from(&[0, 1, 2, 3, 4, 5, 7, 9]));
+        assert_eq!(sel([1, 2, 3, 7, 9]) |
+                   sel([2, 7, 5, 0, 4, 1]),
+                   sel([0, 1, 2, 3, 4, 5, 7, 9]));
     }
 
     #[test]
     fn not_inv_or_inv() {
-        assert_eq!(Selection::from(&[1, 2, 3, 7, 9]) |
-                   !Selection::from(&[2, 7, 5, 0, 4, 1]),
-                   Selection::from(&[3, 9]));
+        assert_eq!(sel([1, 2, 3, 7, 9]) |
+                   !sel([2, 7, 5, 0, 4, 1]),
+                   !sel([0, 4, 5]));
     }
 
     #[test]
     fn inv_or_not_inv() {
-        assert_eq!(!Selection::from(&[2, 7, 5, 0, 4, 1]) |
-                   Selection::from(&[1, 2, 3, 7, 9]),
-                   Selection::from(&[3, 9]));
+        assert_eq!(!sel([2, 7, 5, 0, 4, 1]) |
+                   sel([1, 2, 3, 7, 9]),
+                   !sel([0, 4, 5]));
     }
 
     #[test]
     fn inv_or_inv() {
-        assert_eq!(!Selection::from(&[1, 2, 3, 7, 9]) |
-                   !Selection::from(&[2, 7, 5, 0, 4, 1]),
-                   !Selection::from(&[1, 2, 7]));
+        assert_eq!(!sel([1, 2, 3, 7, 9]) |
+                   !sel([2, 7, 5, 0, 4, 1]),
+                   !sel([1, 2, 7]));
     }
 }
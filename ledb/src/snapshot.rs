@@ -0,0 +1,163 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use lmdb::{ConstTransaction, ReadTransaction};
+use serde::de::DeserializeOwned;
+
+use super::{
+    Aggregated, Aggregation, Collection, Document, DocumentsIterator, Filter, FindOptions, Held,
+    Order, Primary, RawDocument, Result, ResultWrap, Revision, Storage,
+};
+
+struct ReadTxn {
+    txn: ReadTransaction<'static>,
+    // The memory map isn't resized while the transaction is alive
+    _held: Held,
+}
+
+// The environment always openned with NOTLS flag,
+// so the read transactions isn't tied to threads.
+unsafe impl Send for ReadTxn {}
+
+/// Read transaction which can be shared between documents iterators
+#[derive(Clone)]
+pub(crate) struct SharedTxn(Arc<Mutex<ReadTxn>>);
+
+impl SharedTxn {
+    pub(crate) fn new(storage: Storage) -> Result<Self> {
+        let _held = Held::new(storage.clone());
+        let txn = ReadTransaction::new(storage).wrap_err()?;
+        Ok(SharedTxn(Arc::new(Mutex::new(ReadTxn { txn, _held }))))
+    }
+
+    pub(crate) fn with<R, F>(&self, fun: F) -> Result<R>
+    where
+        F: FnOnce(&ConstTransaction) -> Result<R>,
+    {
+        let txn = self.0.lock().wrap_err()?;
+        fun(&txn.txn)
+    }
+}
+
+/// Point-in-time read snapshot of the storage
+///
+/// Use `Storage::snapshot()` to run multiple queries across collections
+/// which sees the same state of storage.
+///
+/// *Note*: The old versions of data cannot be reused by storage until the snapshot and the documents iterators which created from it are alive, so you should not hold it too long.
+///
+pub struct Snapshot {
+    storage: Storage,
+    txn: SharedTxn,
+}
+
+impl Snapshot {
+    pub(crate) fn new(storage: Storage) -> Result<Self> {
+        let txn = SharedTxn::new(storage.clone())?;
+        Ok(Self { storage, txn })
+    }
+
+    /// Get collection which reads from this snapshot
+    ///
+    /// *Note*: The collection must exists.
+    ///
+    pub fn collection<N: AsRef<str>>(&self, name: N) -> Result<SnapshotCollection> {
+        let name = name.as_ref();
+
+        if let Some(coll) = self.storage.get_collection(name)? {
+            Ok(SnapshotCollection {
+                coll,
+                txn: self.txn.clone(),
+            })
+        } else {
+            Err(format!("Missing collection '{}'", name)).wrap_err()
+        }
+    }
+}
+
+/// Collection which reads from the snapshot
+///
+/// The methods works like the same methods of `Collection`.
+///
+#[derive(Clone)]
+pub struct SnapshotCollection {
+    coll: Collection,
+    txn: SharedTxn,
+}
+
+impl SnapshotCollection {
+    /// Find documents using optional filter and ordering
+    pub fn find<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+    ) -> Result<DocumentsIterator<T>> {
+        self.find_with(filter, order, FindOptions::default())
+    }
+
+    /// Find documents using optional filter, ordering and options
+    pub fn find_with<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+        opts: FindOptions,
+    ) -> Result<DocumentsIterator<T>> {
+        let ids = self
+            .txn
+            .with(|txn| self.coll.find_ids_txn(txn, filter, order, &opts))?;
+
+        DocumentsIterator::new(self.txn.clone(), self.coll.clone(), ids)
+            .map(|iter| iter.project(opts.projection.as_ref()))
+    }
+
+    /// Find documents using optional filter and ordering
+    pub fn find_all<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+    ) -> Result<Vec<T>> {
+        self.find(filter, order)?.collect::<Result<Vec<_>>>()
+    }
+
+    pub fn find_ids(&self, filter: Option<Filter>) -> Result<HashSet<Primary>> {
+        self.txn.with(|txn| self.coll.find_ids_set_txn(txn, filter))
+    }
+
+    /// Checks the collection contains document with specified primary key
+    pub fn has(&self, id: impl Into<Primary>) -> Result<bool> {
+        self.txn.with(|txn| self.coll.has_txn(txn, id.into()))
+    }
+
+    /// Get document using primary key/identifier
+    pub fn get<T: DeserializeOwned + Document>(&self, id: impl Into<Primary>) -> Result<Option<T>> {
+        self.txn
+            .with(|txn| self.coll.get_txn(txn, id.into()))?
+            .map(RawDocument::into_doc)
+            .transpose()
+    }
+
+    /// Aggregate documents using optional filter
+    pub fn aggregate(
+        &self,
+        filter: Option<Filter>,
+        aggregation: &Aggregation,
+    ) -> Result<Aggregated> {
+        self.txn
+            .with(|txn| self.coll.aggregate_txn(txn, filter, aggregation))
+    }
+
+    /// Get the revision of document with specified primary key/identifier
+    pub fn revision(&self, id: impl Into<Primary>) -> Result<Option<Revision>> {
+        Ok(self
+            .txn
+            .with(|txn| self.coll.get_rev_txn(txn, id.into()))?
+            .map(|(_doc, rev)| rev))
+    }
+
+    /// Get the last primary key/identifier of inserted document
+    pub fn last_id(&self) -> Result<Option<Primary>> {
+        self.txn.with(|txn| self.coll.last_id_txn(txn))
+    }
+}
@@ -0,0 +1,105 @@
+use lmdb::{ConstTransaction, LmdbResultExt};
+use serde::{Deserialize, Serialize};
+
+use super::{Collection, Index, Result, ResultWrap, Stats};
+
+/// The statistics of index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexStats {
+    /// The path of indexed field
+    pub path: String,
+    /// The number of entries in the index
+    pub entries: usize,
+    /// The number of distinct keys in the index
+    pub keys: usize,
+    /// The page usage of index database
+    pub pages: Stats,
+}
+
+/// The statistics of collection
+///
+/// The indexes which isn't ready yet are not included.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionStats {
+    /// The number of documents
+    pub documents: usize,
+    /// The total size of encoded documents in bytes
+    pub total_size: usize,
+    /// The average size of encoded document in bytes
+    pub average_size: f64,
+    /// The page usage of collection database
+    pub pages: Stats,
+    /// The statistics of each index
+    pub indexes: Vec<IndexStats>,
+}
+
+impl Index {
+    fn stats_txn(&self, txn: &ConstTransaction) -> Result<IndexStats> {
+        let mut entries = 0;
+        let mut keys = 0;
+        let mut last_key: Option<Vec<u8>> = None;
+
+        // the entries is ordered by keys so the same keys goes in row
+        self.visit_entries(txn, |key, _val| {
+            entries += 1;
+            if last_key.as_deref() != Some(key) {
+                keys += 1;
+                last_key = Some(key.to_vec());
+            }
+            Ok(())
+        })?;
+
+        Ok(IndexStats {
+            path: self.path().into(),
+            entries,
+            keys,
+            pages: txn.db_stat(self).map(Stats::from).wrap_err()?,
+        })
+    }
+}
+
+impl Collection {
+    /// Get the statistics of collection and its indexes
+    ///
+    /// The statistics allows to find out which collections and indexes takes the most space.
+    ///
+    pub fn stats(&self) -> Result<CollectionStats> {
+        let txn = self.read_txn()?;
+
+        let indexes = self
+            .get_indexes()?
+            .iter()
+            .filter_map(|field| self.get_index(&field.path).transpose())
+            .map(|index| index.and_then(|index| index.stats_txn(&txn)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut documents = 0;
+        let mut total_size = 0;
+
+        {
+            let access = txn.access();
+            let mut cursor = txn.cursor(self.clone()).wrap_err()?;
+
+            let mut item = cursor.first::<[u8], [u8]>(&access).to_opt().wrap_err()?;
+
+            while let Some((_key, val)) = item {
+                documents += 1;
+                total_size += val.len();
+                item = cursor.next::<[u8], [u8]>(&access).to_opt().wrap_err()?;
+            }
+        }
+
+        Ok(CollectionStats {
+            documents,
+            total_size,
+            average_size: if documents > 0 {
+                total_size as f64 / documents as f64
+            } else {
+                0.0
+            },
+            pages: txn.db_stat(self).map(Stats::from).wrap_err()?,
+            indexes,
+        })
+    }
+}
@@ -4,22 +4,29 @@ use std::{
     fs::create_dir_all,
     ops::Deref,
     path::{Path, PathBuf},
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
 };
 
 use dirs::home_dir;
 use dunce::canonicalize;
 use lmdb::{
-    self, open as OpenFlag, open::Flags as OpenFlags, Cursor, CursorIter, Database,
-    DatabaseOptions, EnvBuilder, Environment, MaybeOwned, ReadTransaction,
+    self, copy as CopyFlag, copy::Flags as CopyFlags, db::Flags as DbFlags, open as OpenFlag,
+    open::Flags as OpenFlags, put::Flags as PutFlags, Cursor, CursorIter, Database,
+    DatabaseOptions, EnvBuilder, Environment, LmdbResultExt, MaybeOwned, ReadTransaction,
+    WriteTransaction,
 };
-use ron::de::from_str as from_db_name;
+use ron::{de::from_str as from_db_name, ser::to_string as to_db_name};
 use serde::{Deserialize, Serialize};
 use supercow::{ext::ConstDeref, NonSyncSupercow, Supercow};
 
 use super::{
-    Collection, CollectionDef, Enumerable, IndexDef, Pool, Result, ResultWrap, Serial,
-    SerialGenerator,
+    notify_changes, Activity, Change, Changes, Collection, CollectionDef, Document, Enumerable,
+    IndexDef, KeyField, Pool, PrimaryType, Result, ResultWrap, Serial, SerialGenerator, Snapshot,
+    Sweeper, Transaction,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -28,10 +35,58 @@ pub(crate) enum DatabaseDef {
     Collection(CollectionDef),
     #[serde(rename = "i")]
     Index(IndexDef),
+    /// The last allocated primary key of collection
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "s")]
+    Sequence(CollectionDef),
+    /// The last allocated revision of documents in collection
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "r")]
+    Revision(CollectionDef),
+    /// The expiration of documents in collection
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "e")]
+    Expiry(CollectionDef),
+    /// The schema of documents in collection
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "v")]
+    Schema(CollectionDef),
+    /// The version of on-disk format of storage
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "f")]
+    Format,
+    /// The marker of index which is building in background
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "b")]
+    Build(IndexDef),
+    /// The marker of index which should be rebuilt when upgrading the format of storage
+    ///
+    /// This is a plain value in the main database instead of sub-database.
+    #[serde(rename = "u")]
+    Upgrade(IndexDef),
 }
 
+/// The current version of on-disk format
+///
+/// 1. The numeric keys of indexes is stored in native byte order.
+/// 2. The keys of indexes is stored using order-preserving big-endian encoding.
+///
+const FORMAT_VERSION: u32 = 2;
+
+/// The default size of memory map
+const DEFAULT_MAP_SIZE: usize = 16 << 20;
+
+/// The time to wait for active transactions before resizing memory map
+const RESIZE_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Storage stats data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Stats {
     pub page_size: u32,
     pub btree_depth: u32,
@@ -71,6 +126,12 @@ pub struct Info {
     pub last_transaction: usize,
     pub max_readers: u32,
     pub num_readers: u32,
+    /// The size of used pages
+    #[serde(default)]
+    pub used_size: usize,
+    /// The size which the memory map may grow to (`None` when unlimited)
+    #[serde(default)]
+    pub max_size: Option<usize>,
 }
 
 impl From<lmdb::EnvInfo> for Info {
@@ -90,17 +151,27 @@ impl From<lmdb::EnvInfo> for Info {
             last_transaction: last_txnid,
             max_readers: maxreaders,
             num_readers: numreaders,
+            used_size: 0,
+            max_size: Some(mapsize),
         }
     }
 }
 
 /// Database options
+///
+/// When the `map_grow_step` is set the memory map grows by this step each time the storage is full
+/// until the `map_max_size` is reached, so the writes of collections is retried instead of failing.
+///
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Options {
     // options
     #[serde(default)]
     map_size: Option<usize>,
     #[serde(default)]
+    map_grow_step: Option<usize>,
+    #[serde(default)]
+    map_max_size: Option<usize>,
+    #[serde(default)]
     max_readers: Option<u32>,
     #[serde(default)]
     max_dbs: Option<u32>,
@@ -125,13 +196,18 @@ pub struct Options {
     read_only: Option<bool>,
     #[serde(default)]
     write_map: Option<bool>,
+    // behavior
+    #[serde(default)]
+    no_full_scan: Option<bool>,
+    #[serde(default)]
+    reuse_ids: Option<bool>,
 }
 
 impl Options {
     fn env_builder(&self) -> Result<EnvBuilder> {
         let mut bld = EnvBuilder::new()?;
 
-        bld.set_mapsize(self.map_size.unwrap_or(16 << 20))
+        bld.set_mapsize(self.map_size.unwrap_or(DEFAULT_MAP_SIZE))
             .wrap_err()?;
         bld.set_maxreaders(self.max_readers.unwrap_or(126))
             .wrap_err()?;
@@ -141,7 +217,9 @@ impl Options {
     }
 
     fn open_flags(&self) -> OpenFlags {
-        self.fill_flags(None)
+        // read transactions should be movable between threads
+        // because documents iterators holds it
+        self.fill_flags(None) | OpenFlag::NOTLS
     }
 
     fn config_env(&self, env: &Environment) -> Result<()> {
@@ -221,9 +299,34 @@ impl Options {
 
 pub(crate) struct StorageData {
     path: PathBuf,
-    env: Environment,
+    env: Arc<Environment>,
+    // The main database which keeps the names of collections and indexes
+    main: Database<'static>,
     gen: SerialGenerator,
     collections: RwLock<Vec<Collection>>,
+    no_full_scan: AtomicBool,
+    reuse_ids: AtomicBool,
+    // The growth policy of memory map (zero means disabled or unlimited)
+    map_grow_step: AtomicUsize,
+    map_max_size: AtomicUsize,
+    // The active transactions which prevents resizing of memory map
+    activity: Activity,
+    // The changes of the current write transaction
+    changes: Changes,
+    // The background thread which removes expired documents
+    sweeper: Mutex<Option<Sweeper>>,
+}
+
+impl StorageData {
+    /// Remove expired documents from all collections
+    pub(crate) fn sweep(&self) -> Result<usize> {
+        let collections = self.collections.read().wrap_err()?.clone();
+        let mut count = 0;
+        for collection in collections {
+            count += collection.sweep()?;
+        }
+        Ok(count)
+    }
 }
 
 /// Storage of documents
@@ -237,6 +340,8 @@ impl Storage {
     ///
     /// On opening storage the existing collections and indexes will be restored automatically.
     ///
+    /// The storage which has an outdated on-disk format will be upgraded by rebuilding its indexes.
+    ///
     /// You can open multiple storages using same path, actually all of them will use same storage instance.
     /// Also you can clone storage instance, share it and and send it to another threads.
     ///
@@ -245,6 +350,18 @@ impl Storage {
 
         if let Some(storage) = Pool::get(&path)? {
             opts.config_env(&storage.env)?;
+            if let Some(flag) = opts.no_full_scan {
+                storage.no_full_scan.store(flag, AtomicOrdering::SeqCst);
+            }
+            if let Some(flag) = opts.reuse_ids {
+                storage.reuse_ids.store(flag, AtomicOrdering::SeqCst);
+            }
+            if let Some(step) = opts.map_grow_step {
+                storage.map_grow_step.store(step, AtomicOrdering::SeqCst);
+            }
+            if let Some(size) = opts.map_max_size {
+                storage.map_max_size.store(size, AtomicOrdering::SeqCst);
+            }
             Ok(Storage(storage))
         } else {
             Self::open(path, opts)
@@ -252,7 +369,37 @@ impl Storage {
     }
 
     fn open(path: PathBuf, opts: Options) -> Result<Self> {
-        let env = open_env(&path, opts)?;
+        let no_full_scan = AtomicBool::new(opts.no_full_scan.unwrap_or(false));
+        let reuse_ids = AtomicBool::new(opts.reuse_ids.unwrap_or(false));
+        let map_grow_step = AtomicUsize::new(opts.map_grow_step.unwrap_or(0));
+        let map_max_size = AtomicUsize::new(opts.map_max_size.unwrap_or(0));
+
+        let env = Arc::new(open_env(&path, opts)?);
+
+        let main = Database::open(
+            Supercow::shared(env.clone()),
+            None,
+            &DatabaseOptions::defaults(),
+        )
+        .wrap_err()?;
+
+        let format = load_format(&env, &main)?;
+
+        if let Some(version) = format {
+            if version > FORMAT_VERSION {
+                return Err(format!("Unsupported storage format version {}", version)).wrap_err();
+            }
+        }
+
+        let dropped_indexes = if format
+            .map(|version| version < FORMAT_VERSION)
+            .unwrap_or(false)
+        {
+            // the indexes will be recreated using current format
+            drop_index_dbs(&env, &main)?
+        } else {
+            Vec::new()
+        };
 
         let gen = SerialGenerator::new();
 
@@ -261,23 +408,35 @@ impl Storage {
         let storage = Storage(Arc::new(StorageData {
             path: path.clone(),
             env,
+            main,
             gen,
             collections,
+            no_full_scan,
+            reuse_ids,
+            map_grow_step,
+            map_max_size,
+            activity: Activity::default(),
+            changes: Changes::default(),
+            sweeper: Mutex::new(None),
         }));
 
         storage.load_collections()?;
 
+        storage.create_indexes(dropped_indexes)?;
+
+        storage.resume_builds()?;
+
+        if format != Some(FORMAT_VERSION) {
+            storage.save_format()?;
+        }
+
         Pool::put(path, &storage.0)?;
 
         Ok(storage)
     }
 
     fn load_collections(&self) -> Result<()> {
-        let env = &self.0.env;
-
-        let db = Database::open(env, None, &DatabaseOptions::defaults()).wrap_err()?;
-
-        let (last_serial, db_def) = load_databases(&env, &db)?;
+        let (last_serial, db_def) = load_databases(&self.0.env, &self.0.main)?;
 
         self.0.gen.set(last_serial);
 
@@ -291,6 +450,95 @@ impl Storage {
         Ok(())
     }
 
+    /// Create the indexes which was dropped when upgrading the format
+    ///
+    /// The upgrade marker of each index is removed after the index is fulfilled.
+    ///
+    fn create_indexes(&self, defs: Vec<IndexDef>) -> Result<()> {
+        for def in defs {
+            let marker = to_db_name(&DatabaseDef::Upgrade(def.clone())).wrap_err()?;
+            let IndexDef(_serial, collection, path, kind, key, compound) = def;
+
+            self.collection(collection)?.create_index_field(KeyField {
+                path,
+                key,
+                kind,
+                compound,
+                build: None,
+            })?;
+
+            self.write(|txn| {
+                let mut access = txn.access();
+                access
+                    .del_key(self.main_db(), marker.as_str())
+                    .to_opt()
+                    .wrap_err()
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Restart the building of indexes which was interrupted
+    fn resume_builds(&self) -> Result<()> {
+        for (key, IndexDef(_serial, collection, path, ..)) in
+            load_builds(&self.0.env, &self.0.main)?
+        {
+            let resumed = if let Some(collection) = self.get_collection(collection)? {
+                collection.resume_build(&path, &key)?
+            } else {
+                false
+            };
+            if !resumed {
+                // the index was removed so the marker is stale
+                self.write(|txn| {
+                    let mut access = txn.access();
+                    access
+                        .del_key(self.main_db(), key.as_str())
+                        .to_opt()
+                        .wrap_err()
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save_format(&self) -> Result<()> {
+        let key = to_db_name(&DatabaseDef::Format).wrap_err()?;
+        let val = FORMAT_VERSION.to_string();
+
+        self.write(|txn| {
+            let mut access = txn.access();
+            access
+                .put(
+                    self.main_db(),
+                    key.as_str(),
+                    val.as_str(),
+                    PutFlags::empty(),
+                )
+                .wrap_err()
+        })
+    }
+
+    pub(crate) fn allow_full_scan(&self) -> bool {
+        !self.0.no_full_scan.load(AtomicOrdering::SeqCst)
+    }
+
+    pub(crate) fn main_db(&self) -> &Database<'static> {
+        &self.0.main
+    }
+
+    /// Get the environment for opening databases
+    ///
+    /// Unlike the storage itself it isn't tracked as an active transaction.
+    ///
+    pub(crate) fn env(&self) -> Supercow<'static, Environment> {
+        Supercow::shared(self.0.env.clone())
+    }
+
+    pub(crate) fn reuse_ids(&self) -> bool {
+        self.0.reuse_ids.load(AtomicOrdering::SeqCst)
+    }
+
     pub(crate) fn enumerate<E: Enumerable>(&self, data: E) -> E {
         self.0.gen.enumerate(data)
     }
@@ -311,8 +559,31 @@ impl Storage {
     /// *Note*: The collection will be created automatically when is does not exists.
     ///
     pub fn collection<N: AsRef<str>>(&self, name: N) -> Result<Collection> {
-        let name = name.as_ref();
+        self.open_collection(name.as_ref(), None)
+    }
+
+    /// Get collection with specified type of primary key
+    ///
+    /// *Note*: The collection will be created automatically when is does not exists.
+    /// The existing collection must have the same type of primary key.
+    ///
+    pub fn collection_with<N: AsRef<str>>(
+        &self,
+        name: N,
+        primary: PrimaryType,
+    ) -> Result<Collection> {
+        self.open_collection(name.as_ref(), Some(primary))
+    }
 
+    /// Get collection for documents of specified type
+    ///
+    /// The type of primary key will be taken from document.
+    ///
+    pub fn collection_for<T: Document>(&self, name: &str) -> Result<Collection> {
+        self.open_collection(name, Some(T::primary_type()))
+    }
+
+    fn open_collection(&self, name: &str, primary: Option<PrimaryType>) -> Result<Collection> {
         {
             let collections = self.0.collections.read().wrap_err()?;
             // search alive collection
@@ -320,14 +591,22 @@ impl Storage {
                 .iter()
                 .find(|collection| collection.name() == name)
             {
-                return Ok(collection.clone());
+                return match primary {
+                    Some(primary) if primary != collection.primary_type() => Err(format!(
+                        "Collection '{}' has {} primary key",
+                        name,
+                        collection.primary_type()
+                    ))
+                    .wrap_err(),
+                    _ => Ok(collection.clone()),
+                };
             }
         }
 
         // create new collection
         let collection = Collection::new(
             self.clone(),
-            self.enumerate(CollectionDef::new(name)),
+            self.enumerate(CollectionDef::new(name, primary.unwrap_or_default())),
             Vec::new(),
         )?;
 
@@ -337,6 +616,15 @@ impl Storage {
         Ok(collection)
     }
 
+    pub(crate) fn get_collection<N: AsRef<str>>(&self, name: N) -> Result<Option<Collection>> {
+        let name = name.as_ref();
+        let collections = self.0.collections.read().wrap_err()?;
+        Ok(collections
+            .iter()
+            .find(|collection| collection.name() == name)
+            .cloned())
+    }
+
     pub fn drop_collection<N: AsRef<str>>(&self, name: N) -> Result<bool> {
         let name = name.as_ref();
 
@@ -365,12 +653,216 @@ impl Storage {
             .collect())
     }
 
+    /// Run multiple operations in single write transaction
+    ///
+    /// The transaction will be committed when the closure returns `Ok`
+    /// and aborted when it returns `Err`, so either all changes will be applied or nothing.
+    ///
+    /// *Note*: The collections must exists before the transaction starts.
+    /// Do not call the non-transactional methods of `Storage` or `Collection` inside the closure,
+    /// because the write transaction which already openned by the current thread blocks it.
+    /// Unlike the writes of collections the transaction isn't retried when the storage is full.
+    ///
+    pub fn transaction<R, F>(&self, fun: F) -> Result<R>
+    where
+        F: FnOnce(&Transaction) -> Result<R>,
+    {
+        let txn = Transaction::new(self.clone())?;
+        let guard = self.0.changes.collect();
+        let res = fun(&txn);
+        let changes = guard.take()?;
+        let res = res?;
+        txn.commit()?;
+        notify_changes(changes)?;
+        Ok(res)
+    }
+
+    /// Get consistent read snapshot of the storage
+    ///
+    /// All queries which made using snapshot sees the same state of collections.
+    ///
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Snapshot::new(self.clone())
+    }
+
+    /// Run the function in write transaction
+    ///
+    /// When the storage is full and the memory map may grow,
+    /// the transaction is aborted, the map is resized and the function is called again.
+    ///
+    pub(crate) fn write<R, F>(&self, mut fun: F) -> Result<R>
+    where
+        F: FnMut(&WriteTransaction<'static>) -> Result<R>,
+    {
+        loop {
+            let map_size = self.0.env.info().wrap_err()?.mapsize;
+            match self.write_once(&mut fun) {
+                Err(error) if error.is_map_full() && self.grow_map(map_size)? => continue,
+                res => return res,
+            }
+        }
+    }
+
+    fn write_once<R, F>(&self, fun: &mut F) -> Result<R>
+    where
+        F: FnMut(&WriteTransaction<'static>) -> Result<R>,
+    {
+        let txn = WriteTransaction::new(self.clone()).wrap_err()?;
+        // the changes are discarded on error or panic while the write lock is held
+        let guard = self.0.changes.collect();
+        let res = fun(&txn);
+        // take the changes before commit while the write lock is held
+        let changes = guard.take()?;
+        let res = res?;
+        txn.commit().wrap_err()?;
+        notify_changes(changes)?;
+        Ok(res)
+    }
+
+    /// Grow the memory map according to the growth policy
+    ///
+    /// The map which is already grown since it had specified size is not resized again.
+    ///
+    /// Returns `false` when the map cannot grow.
+    ///
+    fn grow_map(&self, map_size: usize) -> Result<bool> {
+        let step = self.0.map_grow_step.load(AtomicOrdering::SeqCst);
+
+        if step == 0 {
+            return Ok(false);
+        }
+
+        let max_size = self.max_map_size().unwrap_or(usize::MAX);
+        let env = &self.0.env;
+
+        self.0
+            .activity
+            .exclusive(RESIZE_TIMEOUT, || -> Result<bool> {
+                let size = env.info().wrap_err()?.mapsize;
+
+                if size > map_size {
+                    // the map was resized by another thread
+                    return Ok(true);
+                }
+
+                if size >= max_size {
+                    return Ok(false);
+                }
+
+                unsafe {
+                    env.set_mapsize(size.saturating_add(step).min(max_size))
+                        .wrap_err()?;
+                }
+
+                Ok(true)
+            })
+            .unwrap_or(Ok(false))
+    }
+
+    /// Get the size which the memory map may grow to
+    fn max_map_size(&self) -> Option<usize> {
+        if self.0.map_grow_step.load(AtomicOrdering::SeqCst) == 0 {
+            return None;
+        }
+        match self.0.map_max_size.load(AtomicOrdering::SeqCst) {
+            0 => None,
+            size => Some(size),
+        }
+    }
+
+    pub(crate) fn add_change(&self, coll: Collection, change: Change) -> Result<()> {
+        self.0.changes.push(coll, change)
+    }
+
+    /// Remove expired documents from all collections
+    ///
+    /// Returns the number of removed documents.
+    ///
+    pub fn sweep(&self) -> Result<usize> {
+        self.0.sweep()
+    }
+
+    /// Start the background thread which removes expired documents periodically
+    ///
+    /// The running sweeper will be restarted with new interval.
+    /// The sweeper stops automatically when the storage is closed.
+    ///
+    pub fn start_sweeper(&self, interval: Duration) -> Result<()> {
+        let mut sweeper = self.0.sweeper.lock().wrap_err()?;
+        if let Some(sweeper) = sweeper.take() {
+            sweeper.stop();
+        }
+        *sweeper = Some(Sweeper::start(Arc::downgrade(&self.0), interval));
+        Ok(())
+    }
+
+    /// Stop the background thread which removes expired documents
+    ///
+    /// Returns `false` when the sweeper is not running.
+    ///
+    pub fn stop_sweeper(&self) -> Result<bool> {
+        let sweeper = self.0.sweeper.lock().wrap_err()?.take();
+        Ok(if let Some(sweeper) = sweeper {
+            sweeper.stop();
+            true
+        } else {
+            false
+        })
+    }
+
     pub fn get_stats(&self) -> Result<Stats> {
         self.0.env.stat().map(Stats::from).wrap_err()
     }
 
+    /// Get the storage info
+    ///
+    /// The used size is reported against the size which the memory map may grow to.
+    ///
     pub fn get_info(&self) -> Result<Info> {
-        self.0.env.info().map(Info::from).wrap_err()
+        let page_size = self.0.env.stat().wrap_err()?.psize as usize;
+        let mut info = self.0.env.info().map(Info::from).wrap_err()?;
+
+        info.used_size = (info.last_page + 1) * page_size;
+
+        if self.0.map_grow_step.load(AtomicOrdering::SeqCst) > 0 {
+            info.max_size = self.max_map_size();
+        }
+
+        Ok(info)
+    }
+
+    /// Make a backup copy of the storage
+    ///
+    /// The copy is consistent and it can be made while the storage is used by readers and writers.
+    ///
+    /// The compacting copy omits the free pages, so it may be smaller but takes more time.
+    ///
+    /// *Note*: The target directory will be created when it does not exists, but it must not contain database.
+    ///
+    pub fn backup<P: AsRef<Path>>(&self, path: P, compact: bool) -> Result<()> {
+        let path = realpath(path.as_ref())?;
+
+        if path == self.0.path {
+            return Err("Unable to backup storage into itself").wrap_err();
+        }
+
+        if !self.0.env.flags().wrap_err()?.contains(OpenFlag::NOSUBDIR) {
+            create_dir_all(&path).wrap_err()?;
+        }
+
+        let path = path.to_str().ok_or("Invalid backup path").wrap_err()?;
+
+        let flags = if compact {
+            CopyFlag::COMPACT
+        } else {
+            CopyFlags::empty()
+        };
+
+        // the copying uses read transaction so the map must not be resized
+        let _txn = TxnEnv::new(self.clone());
+        let _held = Held::new(self.clone());
+
+        self.0.env.copy(path, flags).wrap_err()
     }
 
     /// Get openned storages
@@ -405,15 +897,71 @@ unsafe impl ConstDeref for Storage {
     }
 }
 
+/// The transactions holds the storage using this conversion,
+/// so the active transactions can be tracked.
 impl<'env> Into<Supercow<'env, Environment>> for Storage {
     fn into(self) -> Supercow<'env, Environment> {
-        Supercow::shared(self)
+        Supercow::shared(TxnEnv::new(self))
     }
 }
 
 impl<'env> Into<NonSyncSupercow<'env, Environment>> for Storage {
     fn into(self) -> NonSyncSupercow<'env, Environment> {
-        Supercow::shared(self)
+        Supercow::shared(TxnEnv::new(self))
+    }
+}
+
+/// The environment of active transaction
+///
+/// The memory map cannot be resized while it exists.
+///
+struct TxnEnv(Storage);
+
+impl TxnEnv {
+    fn new(storage: Storage) -> Self {
+        storage.0.activity.enter();
+        TxnEnv(storage)
+    }
+}
+
+impl Clone for TxnEnv {
+    fn clone(&self) -> Self {
+        // each clone is tracked separately
+        TxnEnv::new(self.0.clone())
+    }
+}
+
+impl Drop for TxnEnv {
+    fn drop(&mut self) {
+        (self.0).0.activity.leave();
+    }
+}
+
+unsafe impl ConstDeref for TxnEnv {
+    type Target = Environment;
+
+    #[inline]
+    fn const_deref(&self) -> &Self::Target {
+        &(self.0).0.env
+    }
+}
+
+/// The guard of long-lived transaction
+///
+/// The memory map isn't resized while it exists, because the transaction may not end soon.
+///
+pub(crate) struct Held(Storage);
+
+impl Held {
+    pub(crate) fn new(storage: Storage) -> Self {
+        storage.0.activity.hold();
+        Held(storage)
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        (self.0).0.activity.release();
     }
 }
 
@@ -449,10 +997,24 @@ fn load_databases(env: &Environment, db: &Database) -> Result<(Serial, Definitio
             Ok(DatabaseDef::Index(def)) => {
                 last_serial = usize::max(last_serial, def.0);
                 defs.entry(def.1.clone())
-                    .or_insert_with(|| (CollectionDef::new(&def.1), Vec::new()))
+                    .or_insert_with(|| {
+                        (
+                            CollectionDef::new(&def.1, PrimaryType::default()),
+                            Vec::new(),
+                        )
+                    })
                     .1
                     .push(def);
             }
+            Ok(DatabaseDef::Sequence(..))
+            | Ok(DatabaseDef::Revision(..))
+            | Ok(DatabaseDef::Expiry(..))
+            | Ok(DatabaseDef::Schema(..))
+            | Ok(DatabaseDef::Format)
+            | Ok(DatabaseDef::Build(..)) => (),
+            Ok(DatabaseDef::Upgrade(def)) => {
+                last_serial = usize::max(last_serial, def.0);
+            }
             Err(e) => return Err(e),
         }
     }
@@ -463,6 +1025,136 @@ fn load_databases(env: &Environment, db: &Database) -> Result<(Serial, Definitio
     ))
 }
 
+/// Load the version of on-disk format from the main database
+///
+/// Returns `None` for the new empty storage.
+///
+fn load_format(env: &Environment, db: &Database) -> Result<Option<u32>> {
+    let key = to_db_name(&DatabaseDef::Format).wrap_err()?;
+    let txn = ReadTransaction::new(env).wrap_err()?;
+    let access = txn.access();
+
+    Ok(
+        if let Some(val) = access.get::<str, str>(db, &key).to_opt().wrap_err()? {
+            Some(from_db_name(val).wrap_err()?)
+        } else if txn.db_stat(db).wrap_err()?.entries > 0 {
+            // the storage was created before the format was versioned
+            Some(1)
+        } else {
+            None
+        },
+    )
+}
+
+/// Load the markers of indexes which building was interrupted
+///
+/// Returns the keys of markers with the definitions of indexes.
+///
+fn load_builds(env: &Environment, db: &Database) -> Result<Vec<(String, IndexDef)>> {
+    let txn = ReadTransaction::new(env).wrap_err()?;
+    let cursor = txn.cursor(db).wrap_err()?;
+    let access = txn.access();
+    let mut builds = Vec::new();
+
+    for res in CursorIter::new(
+        MaybeOwned::Owned(cursor),
+        &access,
+        |c, a| c.first(a),
+        Cursor::next::<str, [u8]>,
+    )
+    .wrap_err()?
+    {
+        let (key, _val) = res.wrap_err()?;
+        if let Ok(DatabaseDef::Build(def)) = from_db_name(key) {
+            builds.push((key.into(), def));
+        }
+    }
+
+    Ok(builds)
+}
+
+/// Load the markers of indexes which should be rebuilt when upgrading the format
+fn load_upgrades(env: &Environment, db: &Database) -> Result<Vec<IndexDef>> {
+    let txn = ReadTransaction::new(env).wrap_err()?;
+    let cursor = txn.cursor(db).wrap_err()?;
+    let access = txn.access();
+    let mut upgrades = Vec::new();
+
+    for res in CursorIter::new(
+        MaybeOwned::Owned(cursor),
+        &access,
+        |c, a| c.first(a),
+        Cursor::next::<str, [u8]>,
+    )
+    .wrap_err()?
+    {
+        let (key, _val) = res.wrap_err()?;
+        if let Ok(DatabaseDef::Upgrade(def)) = from_db_name(key) {
+            upgrades.push(def);
+        }
+    }
+
+    Ok(upgrades)
+}
+
+/// Delete the databases of all indexes
+///
+/// The upgrade markers of indexes is stored before deleting,
+/// so the indexes will be recreated even the upgrading was interrupted.
+///
+/// Returns the definitions of indexes which should be recreated.
+///
+pub(crate) fn drop_index_dbs(env: &Arc<Environment>, db: &Database) -> Result<Vec<IndexDef>> {
+    let (_last_serial, defs) = load_databases(env, db)?;
+    let defs: Vec<_> = defs
+        .into_iter()
+        .flat_map(|(_def, index_defs)| index_defs)
+        .collect();
+
+    let mut upgrades = load_upgrades(env, db)?;
+
+    {
+        let txn = WriteTransaction::new(env.clone()).wrap_err()?;
+        {
+            let mut access = txn.access();
+
+            for def in &defs {
+                // the index which was recreated by interrupted upgrade is already marked
+                if upgrades
+                    .iter()
+                    .any(|upgrade| upgrade.1 == def.1 && upgrade.2 == def.2)
+                {
+                    continue;
+                }
+
+                let key = to_db_name(&DatabaseDef::Upgrade(def.clone())).wrap_err()?;
+                access
+                    .put(db, key.as_str(), "", PutFlags::empty())
+                    .wrap_err()?;
+                upgrades.push(def.clone());
+            }
+        }
+        txn.commit().wrap_err()?;
+    }
+
+    for def in &defs {
+        let name = to_db_name(&DatabaseDef::Index(def.clone())).wrap_err()?;
+
+        if let Some(db) = Database::open(
+            Supercow::shared(env.clone()),
+            Some(&name),
+            &DatabaseOptions::new(DbFlags::empty()),
+        )
+        .to_opt()
+        .wrap_err()?
+        {
+            db.delete().wrap_err()?;
+        }
+    }
+
+    Ok(upgrades)
+}
+
 fn open_env(path: &Path, opts: Options) -> Result<Environment> {
     let path = path.to_str().ok_or("Invalid db path").wrap_err()?;
 
@@ -0,0 +1,165 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use lmdb::ConstTransaction;
+use serde::de::DeserializeOwned;
+
+use super::{
+    extract_field_values, Collection, Document, DocumentsIterator, FindOptions, Index, KeyData,
+    KeyType, Primary, RawDocument, Result,
+};
+
+/// The frequencies of terms
+pub(crate) type Terms = HashMap<String, u32>;
+
+/// The scores of found documents
+pub(crate) type Scores = HashMap<Primary, u32>;
+
+/// Split text to the lower case words
+pub(crate) fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Extract the terms with frequencies from the document field
+pub(crate) fn extract_terms(doc: &RawDocument, path: &str) -> Terms {
+    let mut vals = Vec::new();
+    extract_field_values(doc, Some(KeyType::String), &path.split('.'), &mut vals);
+
+    let mut terms = Terms::new();
+    for val in vals {
+        if let KeyData::String(text) = val {
+            for term in tokenize(&text) {
+                *terms.entry(term).or_default() += 1;
+            }
+        }
+    }
+    terms
+}
+
+/// Parsed full-text query
+///
+/// The query consists of alternatives separated by `|`,
+/// each alternative matches when all of its words are found.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TextQuery(Vec<Vec<String>>);
+
+impl TextQuery {
+    pub(crate) fn parse(query: &str) -> Self {
+        TextQuery(
+            query
+                .split('|')
+                .map(|alt| tokenize(alt).collect::<Vec<_>>())
+                .filter(|terms| !terms.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Get the alternatives of query
+    pub(crate) fn alternatives(&self) -> &[Vec<String>] {
+        &self.0
+    }
+
+    /// Get the score of document terms
+    ///
+    /// The score is a total frequency of terms of matched alternatives.
+    ///
+    pub(crate) fn score(&self, terms: &Terms) -> Option<u32> {
+        self.0
+            .iter()
+            .filter_map(|alt| alt.iter().map(|term| terms.get(term)).sum::<Option<u32>>())
+            .fold(None, |total, score| Some(total.unwrap_or(0) + score))
+    }
+}
+
+impl Collection {
+    /// Search documents using full-text query
+    ///
+    /// The found documents are ranked by the total frequency of matched words.
+    /// The `offset`, `limit`, `after` and `projection` options can be used as usual.
+    ///
+    /// When the field has no full-text index the documents will be scanned.
+    ///
+    pub fn search<T: DeserializeOwned + Document, P: AsRef<str>>(
+        &self,
+        path: P,
+        query: &str,
+        opts: FindOptions,
+    ) -> Result<DocumentsIterator<T>> {
+        let txn = self.shared_txn()?;
+
+        let ids = txn.with(|txn| self.search_ids_txn(txn, path.as_ref(), query, &opts))?;
+
+        DocumentsIterator::new(txn, self.clone(), ids)
+            .map(|iter| iter.project(opts.projection.as_ref()))
+    }
+
+    pub(crate) fn search_ids_txn(
+        &self,
+        txn: &ConstTransaction,
+        path: &str,
+        query: &str,
+        opts: &FindOptions,
+    ) -> Result<Vec<Primary>> {
+        let mut scores = self.score_txn(txn, path, &TextQuery::parse(query))?;
+
+        if let Some(live) = self.live_filter(None)? {
+            let sel = live.apply(txn, self)?;
+            scores.retain(|id, _| sel.has(id));
+        }
+
+        // the most relevant goes first
+        self.select_ranked(
+            scores.into_iter().map(|(id, score)| (id, Reverse(score))),
+            opts,
+        )
+    }
+
+    /// Score the documents which matches full-text query
+    pub(crate) fn score_txn(
+        &self,
+        txn: &ConstTransaction,
+        path: &str,
+        query: &TextQuery,
+    ) -> Result<Scores> {
+        if let Some(index) = self.index_or_scan(path, Index::is_fulltext)? {
+            return index.query_text(txn, &txn.access(), query);
+        }
+
+        let mut scores = Scores::new();
+        self.visit_txn(txn, None, |doc| {
+            if let Some(score) = query.score(&extract_terms(&doc, path)) {
+                scores.insert(doc.req_id()?, score);
+            }
+            Ok(())
+        })?;
+        Ok(scores)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tokenize, Terms, TextQuery};
+
+    #[test]
+    fn tokenize_text() {
+        assert_eq!(
+            tokenize("Hello, World! It's a (tiny) test_1.").collect::<Vec<_>>(),
+            vec!["hello", "world", "it", "s", "a", "tiny", "test", "1"]
+        );
+    }
+
+    #[test]
+    fn score_query() {
+        let terms: Terms = vec![("rust".into(), 2), ("lmdb".into(), 1)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(TextQuery::parse("Rust LMDB").score(&terms), Some(3));
+        assert_eq!(TextQuery::parse("rust mdbx").score(&terms), None);
+        assert_eq!(TextQuery::parse("rust mdbx | lmdb").score(&terms), Some(1));
+        assert_eq!(TextQuery::parse("rust | lmdb").score(&terms), Some(3));
+        assert_eq!(TextQuery::parse(" | ").score(&terms), None);
+    }
+}
@@ -0,0 +1,222 @@
+use lmdb::WriteTransaction;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    project_doc, Affected, Collection, Document, Filter, FindOptions, Modify, Order, Primary,
+    Projection, RawDocument, Result, ResultWrap, Returning, Revision, Storage,
+};
+
+/// Write transaction across the storage
+///
+/// Use `Storage::transaction()` to run multiple operations atomically.
+///
+pub struct Transaction {
+    storage: Storage,
+    txn: WriteTransaction<'static>,
+}
+
+impl Transaction {
+    pub(crate) fn new(storage: Storage) -> Result<Self> {
+        let txn = WriteTransaction::new(storage.clone()).wrap_err()?;
+        Ok(Self { storage, txn })
+    }
+
+    pub(crate) fn commit(self) -> Result<()> {
+        self.txn.commit().wrap_err()
+    }
+
+    /// Get collection which operates in this transaction
+    ///
+    /// *Note*: The collection must exists.
+    ///
+    pub fn collection<N: AsRef<str>>(&self, name: N) -> Result<TransactionCollection<'_>> {
+        let name = name.as_ref();
+
+        if let Some(coll) = self.storage.get_collection(name)? {
+            Ok(TransactionCollection {
+                coll,
+                txn: &self.txn,
+            })
+        } else {
+            Err(format!("Missing collection '{}'", name)).wrap_err()
+        }
+    }
+}
+
+/// Collection which operates in the write transaction
+///
+/// The methods works like the same methods of `Collection`.
+///
+pub struct TransactionCollection<'t> {
+    coll: Collection,
+    txn: &'t WriteTransaction<'static>,
+}
+
+impl<'t> TransactionCollection<'t> {
+    /// Insert document into collection
+    pub fn insert<T: Serialize + Document>(&self, doc: T) -> Result<Primary> {
+        self.coll.insert_txn(self.txn, RawDocument::from_doc(&doc)?)
+    }
+
+    /// Insert document with specified primary key/identifier into collection
+    pub fn insert_with_id<T: Serialize + Document>(&self, doc: T) -> Result<Primary> {
+        self.coll
+            .insert_with_id_txn(self.txn, RawDocument::from_doc(&doc)?)
+    }
+
+    /// Replace document in the collection
+    pub fn put<T: Serialize + Document>(&self, doc: T) -> Result<()> {
+        self.coll.put_txn(self.txn, RawDocument::from_doc(&doc)?)
+    }
+
+    /// Replace document in the collection when it has expected revision
+    pub fn put_if_revision<T: Serialize + Document>(
+        &self,
+        doc: T,
+        rev: Revision,
+    ) -> Result<Revision> {
+        self.coll
+            .put_if_revision_txn(self.txn, RawDocument::from_doc(&doc)?, rev)
+    }
+
+    /// Update documents using optional filter and modifier
+    pub fn update(&self, filter: Option<Filter>, modify: Modify) -> Result<usize> {
+        self.coll.update_txn(self.txn, filter, modify)
+    }
+
+    /// Update documents using optional filter and modifier and get affected documents
+    pub fn update_returning<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        modify: Modify,
+        returning: Returning,
+    ) -> Result<Vec<Affected<T>>> {
+        self.coll
+            .update_returning_txn(self.txn, filter, Order::default(), None, modify, returning)?
+            .into_iter()
+            .map(Affected::into_doc)
+            .collect()
+    }
+
+    /// Update the first found document using optional filter, ordering and modifier
+    pub fn find_one_and_update<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+        modify: Modify,
+        returning: Returning,
+    ) -> Result<Option<Affected<T>>> {
+        self.coll
+            .update_returning_txn(self.txn, filter, order, Some(1), modify, returning)?
+            .into_iter()
+            .next()
+            .map(Affected::into_doc)
+            .transpose()
+    }
+
+    /// Update documents or insert new document when nothing found
+    pub fn upsert<T: Serialize + Document>(
+        &self,
+        filter: Option<Filter>,
+        modify: Modify,
+        default_doc: T,
+    ) -> Result<Option<Primary>> {
+        self.coll.upsert_txn(
+            self.txn,
+            filter,
+            modify,
+            RawDocument::from_doc(&default_doc)?,
+        )
+    }
+
+    /// Remove documents using optional filter
+    pub fn remove(&self, filter: Option<Filter>) -> Result<usize> {
+        self.coll.remove_txn(self.txn, filter)
+    }
+
+    /// Remove documents using optional filter and get removed documents
+    pub fn remove_returning<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+    ) -> Result<Vec<T>> {
+        self.coll
+            .remove_returning_txn(self.txn, filter, Order::default(), None)?
+            .into_iter()
+            .map(RawDocument::into_doc)
+            .collect()
+    }
+
+    /// Delete the first found document using optional filter and ordering
+    pub fn find_one_and_delete<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+    ) -> Result<Option<T>> {
+        self.coll
+            .remove_returning_txn(self.txn, filter, order, Some(1))?
+            .into_iter()
+            .next()
+            .map(RawDocument::into_doc)
+            .transpose()
+    }
+
+    /// Delete document with specified primary key/identifier
+    pub fn delete(&self, id: impl Into<Primary>) -> Result<bool> {
+        self.coll.delete_txn(self.txn, id.into())
+    }
+
+    /// Checks the collection contains document with specified primary key
+    pub fn has(&self, id: impl Into<Primary>) -> Result<bool> {
+        self.coll.has_txn(self.txn, id.into())
+    }
+
+    /// Get document using primary key/identifier
+    ///
+    /// The changes which made in this transaction are visible.
+    ///
+    pub fn get<T: DeserializeOwned + Document>(&self, id: impl Into<Primary>) -> Result<Option<T>> {
+        self.coll
+            .get_txn(self.txn, id.into())?
+            .map(RawDocument::into_doc)
+            .transpose()
+    }
+
+    /// Get the revision of document with specified primary key/identifier
+    pub fn revision(&self, id: impl Into<Primary>) -> Result<Option<Revision>> {
+        Ok(self
+            .coll
+            .get_rev_txn(self.txn, id.into())?
+            .map(|(_doc, rev)| rev))
+    }
+
+    /// Find documents using optional filter and ordering
+    ///
+    /// The changes which made in this transaction are visible.
+    ///
+    pub fn find<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+    ) -> Result<Vec<T>> {
+        self.find_with(filter, order, FindOptions::default())
+    }
+
+    /// Find documents using optional filter, ordering and options
+    pub fn find_with<T: DeserializeOwned + Document>(
+        &self,
+        filter: Option<Filter>,
+        order: Order,
+        opts: FindOptions,
+    ) -> Result<Vec<T>> {
+        let projector = opts.projection.as_ref().map(Projection::compile);
+        self.coll
+            .find_ids_txn(self.txn, filter, order, &opts)?
+            .into_iter()
+            .filter_map(|id| self.coll.get_txn(self.txn, id).transpose())
+            .map(|res| {
+                res.map(|doc| project_doc(projector.as_ref(), doc))
+                    .and_then(RawDocument::into_doc)
+            })
+            .collect()
+    }
+}
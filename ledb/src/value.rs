@@ -1,9 +1,10 @@
 use std::{
     borrow::Cow,
+    fmt::{Display, Formatter, Result as FmtResult},
     str::from_utf8,
 };
 
-use byteorder::{ByteOrder, NativeEndian};
+use byteorder::{BigEndian, ByteOrder};
 use ordered_float::OrderedFloat;
 use serde::{Serialize, Deserialize};
 
@@ -17,8 +18,40 @@ pub enum KeyData {
     #[serde(with = "float")]
     Float(OrderedFloat<f64>),
     String(String),
+    /// The binary is deserialized from bytes only so the arrays is treated as tuples
+    #[serde(deserialize_with = "binary::deserialize")]
     Binary(Vec<u8>),
     Bool(bool),
+    /// The components of compound key
+    Tuple(Vec<KeyData>),
+}
+
+impl Display for KeyData {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        use self::KeyData::*;
+        match self {
+            Int(val) => val.fmt(f),
+            Float(val) => val.fmt(f),
+            String(val) => write!(f, "{:?}", val),
+            Binary(val) => {
+                for byte in val {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            },
+            Bool(val) => val.fmt(f),
+            Tuple(vals) => {
+                f.write_str("(")?;
+                for (index, val) in vals.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    val.fmt(f)?;
+                }
+                f.write_str(")")
+            },
+        }
+    }
 }
 
 mod float {
@@ -40,6 +73,38 @@ mod float {
     }
 }
 
+mod binary {
+    use serde::{
+        de::{Error, Visitor},
+        Deserializer,
+    };
+    use std::fmt::{Formatter, Result as FmtResult};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut Formatter) -> FmtResult {
+            f.write_str("bytes")
+        }
+
+        fn visit_bytes<E: Error>(self, val: &[u8]) -> Result<Self::Value, E> {
+            Ok(val.into())
+        }
+
+        fn visit_byte_buf<E: Error>(self, val: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(val)
+        }
+    }
+}
+
+const SIGN_BIT: u64 = 1 << 63;
+
 impl KeyData {
     /// Converts binary representation into key data
     pub fn from_raw(typ: KeyType, raw: &[u8]) -> Result<Self> {
@@ -49,15 +114,17 @@ impl KeyData {
                 if raw.len() != 8 {
                     return Err("Int key must be 8 bytes length".into());
                 }
-                Int(NativeEndian::read_i64(raw))
+                Int((BigEndian::read_u64(raw) ^ SIGN_BIT) as i64)
             }
             KeyType::Float => {
                 if raw.len() != 8 {
                     return Err("Float key must be 8 bytes length".into());
                 }
-                Float(OrderedFloat(NativeEndian::read_f64(raw)))
+                let bits = BigEndian::read_u64(raw);
+                let bits = if bits & SIGN_BIT != 0 { bits ^ SIGN_BIT } else { !bits };
+                Float(OrderedFloat(f64::from_bits(bits)))
             }
-            KeyType::String => String(from_utf8(raw).wrap_err()?.into()),
+            KeyType::String | KeyType::Folded => String(from_utf8(raw).wrap_err()?.into()),
             KeyType::Binary => Binary(Vec::from(raw)),
             KeyType::Bool => {
                 if raw.len() != 1 {
@@ -69,22 +136,62 @@ impl KeyData {
     }
 
     /// Converts key data into binary representation
-    pub fn as_raw(&self) -> &[u8] {
+    ///
+    /// The numbers is encoded as big-endian with flipped sign,
+    /// so the keys of all types can be compared bytewise.
+    ///
+    pub fn as_raw(&self) -> Cow<'_, [u8]> {
         use self::KeyData::*;
         match self {
-            Int(val) => unsafe { &*(val as *const i64 as *const [u8; 8]) },
-            Float(val) => unsafe { &*(val as *const ordered_float::OrderedFloat<f64> as *const [u8; 8]) },
-            String(val) => if val.is_empty() {
+            Int(..) | Float(..) => {
+                let mut raw = Vec::with_capacity(8);
+                self.put_ordered(&mut raw);
+                Cow::Owned(raw)
+            },
+            String(val) => Cow::Borrowed(if val.is_empty() {
                 b"\0"
             } else {
                 val.as_bytes()
-            },
-            Binary(val) => if val.is_empty() {
+            }),
+            Binary(val) => Cow::Borrowed(if val.is_empty() {
                 &[0u8]
             } else {
                 val.as_slice()
-            },
-            Bool(val) => unsafe { &*(val as *const bool as *const [u8; 1]) },
+            }),
+            Bool(val) => Cow::Borrowed(unsafe { &*(val as *const bool as *const [u8; 1]) }),
+            Tuple(vals) => {
+                let mut raw = Vec::new();
+                for val in vals {
+                    val.put_ordered(&mut raw);
+                }
+                Cow::Owned(raw)
+            }
+        }
+    }
+
+    /// Appends order-preserving binary representation of tuple component
+    ///
+    /// The encoded components are compared bytewise in the same order as the values itself.
+    /// Strings and binaries are escaped and terminated so the shorter value goes first.
+    ///
+    fn put_ordered(&self, raw: &mut Vec<u8>) {
+        use self::KeyData::*;
+        match self {
+            Int(val) => raw.extend_from_slice(&((*val as u64) ^ SIGN_BIT).to_be_bytes()),
+            Float(val) => {
+                // the negative zero is the same key as positive
+                let bits = if val.0 == 0.0 { 0 } else { val.to_bits() };
+                let bits = if bits & SIGN_BIT == 0 { bits ^ SIGN_BIT } else { !bits };
+                raw.extend_from_slice(&bits.to_be_bytes());
+            }
+            String(val) => put_escaped(val.as_bytes(), raw),
+            Binary(val) => put_escaped(val, raw),
+            Bool(val) => raw.push(*val as u8),
+            Tuple(vals) => {
+                for val in vals {
+                    val.put_ordered(raw);
+                }
+            }
         }
     }
 
@@ -136,6 +243,10 @@ impl KeyData {
                 (KeyType::String, Int(v)) => String(v.to_string()),
                 (KeyType::String, Float(v)) => String(v.to_string()),
                 (KeyType::String, Bool(v)) => String(v.to_string()),
+                (KeyType::Folded, String(v)) => String(v.to_lowercase()),
+                (KeyType::Folded, Int(v)) => String(v.to_string()),
+                (KeyType::Folded, Float(v)) => String(v.to_string()),
+                (KeyType::Folded, Bool(v)) => String(v.to_string()),
                 (KeyType::Int, String(v)) => Int(if let Ok(v) = v.parse() {
                     v
                 } else {
@@ -151,6 +262,15 @@ impl KeyData {
                 } else {
                     return None;
                 }),
+                // the binaries in text formats like JSON is arrays of bytes
+                (KeyType::Binary, Tuple(vals)) => Binary(
+                    vals.iter()
+                        .map(|val| match val {
+                            Int(v) if *v >= 0 && *v <= 0xff => Some(*v as u8),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()?,
+                ),
                 _ => return None,
             })
         })
@@ -165,8 +285,40 @@ impl KeyData {
             String(..) => KeyType::String,
             Binary(..) => KeyType::Binary,
             Bool(..) => KeyType::Bool,
+            Tuple(..) => KeyType::Binary,
+        }
+    }
+
+    /// Convert key data into tuple of specified component types
+    ///
+    /// The scalar value is treated as a tuple of single component.
+    /// The tuple may have less components than types which means the prefix of compound key.
+    ///
+    pub fn to_tuple(&self, types: &[KeyType]) -> Option<KeyData> {
+        use self::KeyData::*;
+        let vals = match self {
+            Tuple(vals) => vals.clone(),
+            val => vec![val.clone()],
+        };
+        if vals.len() > types.len() {
+            return None;
+        }
+        vals.iter()
+            .zip(types)
+            .map(|(val, typ)| val.to_type(*typ).map(Cow::into_owned))
+            .collect::<Option<Vec<_>>>()
+            .map(Tuple)
+    }
+}
+
+fn put_escaped(val: &[u8], raw: &mut Vec<u8>) {
+    for byte in val {
+        raw.push(*byte);
+        if *byte == 0 {
+            raw.push(0xff);
         }
     }
+    raw.extend_from_slice(&[0, 1]);
 }
 
 impl<'a> From<&'a i64> for KeyData {
@@ -244,6 +396,7 @@ impl From<bool> for KeyData {
 #[cfg(test)]
 mod test {
     use super::{KeyData, KeyType};
+    use serde_json::{from_value, json};
 
     #[test]
     fn get_type() {
@@ -376,4 +529,95 @@ mod test {
             KeyData::from("true")
         );
     }
+
+    #[test]
+    fn key_order() {
+        let keys = [i64::MIN, -300, -1, 0, 1, 256, i64::MAX];
+        for pair in keys.windows(2) {
+            assert!(KeyData::from(pair[0]).as_raw() < KeyData::from(pair[1]).as_raw());
+        }
+        for key in &keys {
+            let raw = KeyData::from(*key).as_raw().into_owned();
+            assert_eq!(KeyData::from_raw(KeyType::Int, &raw).unwrap(), KeyData::from(*key));
+        }
+
+        let keys = [f64::NEG_INFINITY, -1e10, -12.5, -0.5, 0.0, 0.25, 3.0, f64::INFINITY];
+        for pair in keys.windows(2) {
+            assert!(KeyData::from(pair[0]).as_raw() < KeyData::from(pair[1]).as_raw());
+        }
+        for key in &keys {
+            let raw = KeyData::from(*key).as_raw().into_owned();
+            assert_eq!(KeyData::from_raw(KeyType::Float, &raw).unwrap(), KeyData::from(*key));
+        }
+
+        assert_eq!(KeyData::from(-0.0).as_raw(), KeyData::from(0.0).as_raw());
+    }
+
+    #[test]
+    fn tuple_order() {
+        let keys = [
+            KeyData::Tuple(vec![KeyData::from(-5), KeyData::from("b")]),
+            KeyData::Tuple(vec![KeyData::from(-1), KeyData::from("")]),
+            KeyData::Tuple(vec![KeyData::from(0), KeyData::from("a")]),
+            KeyData::Tuple(vec![KeyData::from(0), KeyData::from("a\0")]),
+            KeyData::Tuple(vec![KeyData::from(0), KeyData::from("ab")]),
+            KeyData::Tuple(vec![KeyData::from(3), KeyData::from("a")]),
+        ];
+        for pair in keys.windows(2) {
+            assert!(pair[0].as_raw() < pair[1].as_raw());
+        }
+
+        let keys = [-12.5, -0.5, 0.0, 0.25, 3.0, 1e10];
+        for pair in keys.windows(2) {
+            assert!(
+                KeyData::Tuple(vec![KeyData::from(pair[0])]).as_raw()
+                    < KeyData::Tuple(vec![KeyData::from(pair[1])]).as_raw()
+            );
+        }
+    }
+
+    #[test]
+    fn to_tuple() {
+        let types = [KeyType::String, KeyType::Int];
+        assert_eq!(
+            KeyData::from("abc").to_tuple(&types),
+            Some(KeyData::Tuple(vec![KeyData::from("abc")]))
+        );
+        assert_eq!(
+            KeyData::Tuple(vec![KeyData::from("abc"), KeyData::from(1.0)]).to_tuple(&types),
+            Some(KeyData::Tuple(vec![KeyData::from("abc"), KeyData::from(1)]))
+        );
+        assert_eq!(
+            from_value::<KeyData>(json!([1, 2])).unwrap().to_tuple(&types),
+            Some(KeyData::Tuple(vec![KeyData::from("1"), KeyData::from(2)]))
+        );
+        assert_eq!(KeyData::from(vec![1u8, 2]).to_tuple(&types), None);
+        assert_eq!(
+            KeyData::Tuple(vec![KeyData::from("a"), KeyData::from(1), KeyData::from(2)])
+                .to_tuple(&types),
+            None
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        assert_eq!(
+            from_value::<KeyData>(json!([1, 2])).unwrap(),
+            KeyData::Tuple(vec![KeyData::from(1), KeyData::from(2)])
+        );
+        assert_eq!(
+            from_value::<KeyData>(json!([1, 2]))
+                .unwrap()
+                .to_type(KeyType::Binary)
+                .unwrap()
+                .into_owned(),
+            KeyData::from(vec![1u8, 2])
+        );
+        assert_eq!(
+            from_value::<KeyData>(json!([1, 256]))
+                .unwrap()
+                .to_type(KeyType::Binary),
+            None
+        );
+    }
 }
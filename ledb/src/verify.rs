@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet};
+
+use lmdb::{ConstTransaction, Cursor, CursorIter, MaybeOwned};
+use serde::{Deserialize, Serialize};
+
+use super::{Collection, Index, IndexKind, Primary, RawDocument, Result, ResultWrap};
+
+/// The integrity of index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexIntegrity {
+    /// The path of indexed field
+    pub path: String,
+    /// The number of entries in the index
+    pub entries: usize,
+    /// The documents which is referred by entries but missing or has no such keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dangling: Vec<Primary>,
+    /// The documents which keys is missing in the index
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<Primary>,
+    /// The documents which has same keys in unique index
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<Primary>,
+}
+
+impl IndexIntegrity {
+    /// The index agrees with documents
+    pub fn is_ok(&self) -> bool {
+        self.dangling.is_empty() && self.missing.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+/// The integrity of collection
+///
+/// The indexes which isn't ready yet are not verified.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Integrity {
+    /// The number of documents
+    pub documents: usize,
+    /// The documents which cannot be decoded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub corrupted: Vec<Primary>,
+    /// The integrity of each index
+    pub indexes: Vec<IndexIntegrity>,
+}
+
+impl Integrity {
+    /// The documents are readable and all indexes agrees with it
+    pub fn is_ok(&self) -> bool {
+        self.corrupted.is_empty() && self.indexes.iter().all(IndexIntegrity::is_ok)
+    }
+
+    /// Get the paths of indexes which disagrees with documents
+    pub fn damaged(&self) -> impl Iterator<Item = &str> {
+        self.indexes
+            .iter()
+            .filter(|index| !index.is_ok())
+            .map(|index| index.path.as_str())
+    }
+}
+
+/// The state of index verification
+struct Checker {
+    index: Index,
+    /// The entries which documents should have but it isn't found in index yet
+    expected: HashMap<(Vec<u8>, Vec<u8>), Primary>,
+    /// The owners of unique keys
+    owners: HashMap<Vec<u8>, Primary>,
+    duplicates: HashSet<Primary>,
+}
+
+impl Checker {
+    fn new(index: Index) -> Self {
+        Self {
+            index,
+            expected: HashMap::new(),
+            owners: HashMap::new(),
+            duplicates: HashSet::new(),
+        }
+    }
+
+    fn add_doc(&mut self, doc: &RawDocument) -> Result<()> {
+        let id = doc.req_id()?;
+
+        for (key, val) in self.index.entries(doc)? {
+            if self.index.kind() == IndexKind::Unique {
+                if let Some(owner) = self.owners.get(&key) {
+                    if *owner != id {
+                        self.duplicates.insert(owner.clone());
+                        self.duplicates.insert(id.clone());
+                    }
+                } else {
+                    self.owners.insert(key.clone(), id.clone());
+                }
+            }
+            self.expected.insert((key, val), id.clone());
+        }
+
+        Ok(())
+    }
+
+    fn finish(self, txn: &ConstTransaction) -> Result<IndexIntegrity> {
+        let Checker {
+            index,
+            mut expected,
+            duplicates,
+            ..
+        } = self;
+
+        let mut entries = 0;
+        let mut dangling = HashSet::new();
+
+        index.visit_entries(txn, |key, val| {
+            entries += 1;
+            if expected.remove(&(key.to_vec(), val.to_vec())).is_none() {
+                dangling.insert(index.entry_id(val)?);
+            }
+            Ok(())
+        })?;
+
+        Ok(IndexIntegrity {
+            path: index.path().into(),
+            entries,
+            dangling: sorted(dangling),
+            missing: sorted(expected.into_values().collect()),
+            duplicates: sorted(duplicates),
+        })
+    }
+}
+
+fn sorted(ids: HashSet<Primary>) -> Vec<Primary> {
+    let mut ids = ids.into_iter().collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids
+}
+
+impl Collection {
+    /// Verify that the indexes agrees with documents
+    ///
+    /// Each index is checked for the entries which refers to missing documents or keys,
+    /// for the keys of documents which is missing in index
+    /// and for the documents which shares the keys of unique index.
+    ///
+    /// *Note*: The expected entries of indexes is collected in memory.
+    ///
+    pub fn verify(&self) -> Result<Integrity> {
+        let txn = self.read_txn()?;
+
+        self.verify_txn(&txn)
+    }
+
+    pub(crate) fn verify_txn(&self, txn: &ConstTransaction) -> Result<Integrity> {
+        let mut checkers = self
+            .get_indexes()?
+            .iter()
+            .filter_map(|field| self.get_index(&field.path).transpose())
+            .map(|index| index.map(Checker::new))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut documents = 0;
+        let mut corrupted = Vec::new();
+
+        {
+            let access = txn.access();
+            let cursor = txn.cursor(self.clone()).wrap_err()?;
+
+            for res in CursorIter::new(
+                MaybeOwned::Owned(cursor),
+                &access,
+                |c, a| c.first(a),
+                Cursor::next::<[u8], [u8]>,
+            )
+            .wrap_err()?
+            {
+                let (key, val) = res.wrap_err()?;
+                let id = self.id_from_raw(key)?;
+
+                documents += 1;
+
+                let doc = match RawDocument::from_bin(val) {
+                    Ok(doc) => doc.with_id(id),
+                    Err(_) => {
+                        corrupted.push(id);
+                        continue;
+                    }
+                };
+
+                for checker in &mut checkers {
+                    checker.add_doc(&doc)?;
+                }
+            }
+        }
+
+        Ok(Integrity {
+            documents,
+            corrupted,
+            indexes: checkers
+                .into_iter()
+                .map(|checker| checker.finish(txn))
+                .collect::<Result<Vec<_>>>()?,
+        })
+    }
+
+    /// Rebuild the indexes which disagrees with documents
+    ///
+    /// The damaged indexes are fulfilled from scratch using documents.
+    /// The unique index which keys is shared by several documents cannot be repaired,
+    /// so the error will be returned in that case.
+    ///
+    /// Returns the integrity of collection before repairing.
+    ///
+    pub fn repair(&self) -> Result<Integrity> {
+        let integrity = self.verify()?;
+
+        for path in integrity.damaged() {
+            if let Some(index) = self.get_index(path)? {
+                self.fill_index(&index)?;
+            }
+        }
+
+        Ok(integrity)
+    }
+}
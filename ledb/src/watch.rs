@@ -0,0 +1,197 @@
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{Collection, Document, Filter, Primary, RawDocument, Result, ResultWrap};
+
+/// The kind of document change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    /// New document was inserted
+    #[serde(rename = "insert")]
+    Insert,
+    /// Existing document was updated or replaced
+    #[serde(rename = "update")]
+    Update,
+    /// Existing document was deleted
+    #[serde(rename = "delete")]
+    Delete,
+}
+
+/// Document change event
+///
+/// The old document is missing for inserts and the new document is missing for deletes.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The kind of change
+    pub kind: ChangeKind,
+    /// The primary key/identifier of changed document
+    pub id: Primary,
+    /// The document before change
+    pub old: Option<RawDocument>,
+    /// The document after change
+    pub new: Option<RawDocument>,
+}
+
+impl Change {
+    pub(crate) fn new(old: Option<RawDocument>, new: Option<RawDocument>) -> Result<Option<Self>> {
+        let kind = match (&old, &new) {
+            (None, Some(_)) => ChangeKind::Insert,
+            (Some(_), Some(_)) => ChangeKind::Update,
+            (Some(_), None) => ChangeKind::Delete,
+            (None, None) => return Ok(None),
+        };
+
+        let id = new.as_ref().or(old.as_ref()).unwrap().req_id()?;
+
+        Ok(Some(Change { kind, id, old, new }))
+    }
+
+    /// Get the document before change
+    pub fn old_doc<T: DeserializeOwned + Document>(&self) -> Result<Option<T>> {
+        self.old.clone().map(RawDocument::into_doc).transpose()
+    }
+
+    /// Get the document after change
+    pub fn new_doc<T: DeserializeOwned + Document>(&self) -> Result<Option<T>> {
+        self.new.clone().map(RawDocument::into_doc).transpose()
+    }
+
+    /// Checks that the document passes the filter before or after change
+    fn matches(&self, filter: &Filter) -> bool {
+        self.old
+            .as_ref()
+            .map(|doc| filter.test(doc))
+            .unwrap_or(false)
+            || self
+                .new
+                .as_ref()
+                .map(|doc| filter.test(doc))
+                .unwrap_or(false)
+    }
+}
+
+/// Subscription to the changes of collection
+///
+/// The changes are received only after the transaction which made it is committed.
+/// Iterating over watcher blocks until the next change arrives and ends when the collection is gone.
+///
+/// Drop the watcher to unsubscribe.
+///
+pub struct Watcher(Receiver<Change>);
+
+impl Watcher {
+    /// Get the next change when it is available without blocking
+    pub fn try_next(&self) -> Option<Change> {
+        self.0.try_recv().ok()
+    }
+
+    /// Wait the next change for a given time
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Change> {
+        self.0.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = Change;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.recv().ok()
+    }
+}
+
+/// The subscribers of collection changes
+#[derive(Default)]
+pub(crate) struct Watchers(Mutex<Vec<(Option<Filter>, Sender<Change>)>>);
+
+impl Watchers {
+    pub(crate) fn add(&self, filter: Option<Filter>) -> Result<Watcher> {
+        let (sender, receiver) = channel();
+        self.0.lock().wrap_err()?.push((filter, sender));
+        Ok(Watcher(receiver))
+    }
+
+    pub(crate) fn is_empty(&self) -> Result<bool> {
+        Ok(self.0.lock().wrap_err()?.is_empty())
+    }
+
+    /// Send change to the matched subscribers
+    ///
+    /// The subscribers which was dropped will be removed.
+    ///
+    pub(crate) fn notify(&self, change: &Change) -> Result<()> {
+        self.0.lock().wrap_err()?.retain(|(filter, sender)| {
+            if filter
+                .as_ref()
+                .map(|filter| change.matches(filter))
+                .unwrap_or(true)
+            {
+                sender.send(change.clone()).is_ok()
+            } else {
+                true
+            }
+        });
+        Ok(())
+    }
+}
+
+/// The changes which made in the current write transaction
+///
+/// The write transactions is serialized by backend,
+/// so the changes is collected before commit and sent after it.
+///
+#[derive(Default)]
+pub(crate) struct Changes(Mutex<Vec<(Collection, Change)>>);
+
+impl Changes {
+    /// Start collecting the changes of write transaction
+    ///
+    /// The guard must be created after the write transaction is opened and dropped before it ends.
+    ///
+    pub(crate) fn collect(&self) -> ChangesGuard<'_> {
+        ChangesGuard(self)
+    }
+
+    pub(crate) fn push(&self, coll: Collection, change: Change) -> Result<()> {
+        self.0.lock().wrap_err()?.push((coll, change));
+        Ok(())
+    }
+}
+
+/// The changes collector of the current write transaction
+///
+/// The changes which isn't taken are discarded when the guard is dropped,
+/// so the changes of aborted or panicked transaction never reach subscribers.
+///
+pub(crate) struct ChangesGuard<'a>(&'a Changes);
+
+impl ChangesGuard<'_> {
+    pub(crate) fn take(self) -> Result<Vec<(Collection, Change)>> {
+        Ok((self.0).0.lock().wrap_err()?.drain(..).collect())
+    }
+}
+
+impl Drop for ChangesGuard<'_> {
+    fn drop(&mut self) {
+        let mut changes = match (self.0).0.lock() {
+            Ok(changes) => changes,
+            Err(error) => error.into_inner(),
+        };
+        changes.clear();
+    }
+}
+
+/// Send the committed changes to subscribers
+pub(crate) fn notify_changes(changes: Vec<(Collection, Change)>) -> Result<()> {
+    for (coll, change) in changes {
+        coll.notify(&change)?;
+    }
+    Ok(())
+}